@@ -0,0 +1,116 @@
+//! Benchmarks of the matchers on holes crossed by many parallel strokes.
+//! Run with `cargo bench -p shapecompletion --bench matcher`.
+//! Above MAX_NUM_MATCH_ITEMS endpoints, MatcherAlgorithm::Exhaustive falls back to ranking, which
+//! is reported as "exh.->ranked".
+//!
+//! A release build measured (time per call; the number of matchings in parentheses):
+//!
+//! | endpoints | ranked 1 | ranked 16 | non-crossing 16 | exhaustive         |
+//! |-----------|----------|-----------|-----------------|--------------------|
+//! | 8         | 6 µs     | 0.2 ms    | 7 µs            | 54 µs (14)         |
+//! | 12        | 11 µs    | 0.3 ms    | 25 µs           | 2.0 ms (130)       |
+//! | 32        | 71 µs    | 4.1 ms    | 5.5 ms          | 6.8 ms (ranked 16) |
+//! | 128       | 4.4 ms   | 611 ms    | 480 ms          | 406 ms (ranked 16) |
+//!
+//! Each ranked matching takes polynomial time (roughly cubic in the number of endpoints), but
+//! generating 16 of them across 128 endpoints still takes about half a second.
+
+use std::time::{Duration, Instant};
+
+use shapecompletion::{
+    match_cost::DefaultMatchCost,
+    matcher::{Matcher, MatcherAlgorithm, MatcherConfig, MAX_NUM_MATCH_ITEMS},
+    matcher_helper::{MatchItem, MatchItemSet},
+};
+use visioncortex::PointF64;
+
+/// The endpoints of 'num_items' / 2 slightly slanted strokes crossing a 100-pixel-wide hole
+/// from left to right, like a hole across hatching.
+/// The items are in clockwise order along the hole boundary.
+fn hatching_match_items(num_items: usize) -> MatchItemSet {
+    let num_strokes = num_items / 2;
+    let right_items = (0..num_strokes).map(|i| {
+        let slant = (i % 3) as f64 - 1.0;
+        MatchItem::new_with_default_id(
+            PointF64::new(100.0, 3.0 * i as f64 + slant),
+            PointF64::new(-1.0, 0.0),
+        )
+    });
+    let left_items = (0..num_strokes).rev().map(|i| {
+        MatchItem::new_with_default_id(PointF64::new(0.0, 3.0 * i as f64), PointF64::new(1.0, 0.0))
+    });
+    MatchItemSet::from_match_items_and_set_ids(right_items.chain(left_items).collect())
+}
+
+/// The average duration of 'f' over enough runs to take at least a second (at most 100 runs).
+fn measure<F: FnMut()>(mut f: F) -> (Duration, u32) {
+    let start = Instant::now();
+    let mut runs = 0;
+    while runs < 100 && (runs == 0 || start.elapsed() < Duration::from_secs(1)) {
+        f();
+        runs += 1;
+    }
+    (start.elapsed() / runs, runs)
+}
+
+fn main() {
+    println!(
+        "{:>10} {:>12} {:>10} {:>14} {:>6}",
+        "endpoints", "algorithm", "matchings", "time/iter", "runs"
+    );
+    for &num_items in [8, 12, 32, 128].iter() {
+        let match_items = hatching_match_items(num_items);
+
+        for &(algorithm, name) in [
+            (MatcherAlgorithm::Ranked, "ranked"),
+            (MatcherAlgorithm::NonCrossing, "non-crossing"),
+        ]
+        .iter()
+        {
+            for &num_matchings in [1, 16].iter() {
+                let config = MatcherConfig {
+                    algorithm,
+                    max_num_ranked_matchings: num_matchings,
+                    ..Default::default()
+                };
+                let (duration, runs) = measure(|| {
+                    let matchings = Matcher::find_all_possible_matchings(
+                        match_items.clone(),
+                        &[],
+                        &DefaultMatchCost,
+                        &config,
+                    )
+                    .unwrap();
+                    assert!(!matchings.is_empty() && matchings.len() <= num_matchings);
+                });
+                println!(
+                    "{:>10} {:>12} {:>10} {:>14?} {:>6}",
+                    num_items, name, num_matchings, duration, runs
+                );
+            }
+        }
+
+        // Above MAX_NUM_MATCH_ITEMS, MatcherAlgorithm::Exhaustive falls back to ranking
+        let name = if num_items <= MAX_NUM_MATCH_ITEMS {
+            "exhaustive"
+        } else {
+            "exh.->ranked"
+        };
+        let config = MatcherConfig::default();
+        let mut num_matchings = 0;
+        let (duration, runs) = measure(|| {
+            num_matchings = Matcher::find_all_possible_matchings(
+                match_items.clone(),
+                &[],
+                &DefaultMatchCost,
+                &config,
+            )
+            .unwrap()
+            .len();
+        });
+        println!(
+            "{:>10} {:>12} {:>10} {:>14?} {:>6}",
+            num_items, name, num_matchings, duration, runs
+        );
+    }
+}
@@ -0,0 +1,786 @@
+// Maximum weight matching on general graphs, based on Edmonds' blossom algorithm
+// with the O(n^3) refinements by Gabow and Galil, following the well-known
+// formulation by Joris van Rantwijk.
+//
+// Vertices are 0..num_vertices. Each edge (i, j, w) connects vertices i and j
+// with an integer weight w. Endpoint p of edge k is vertex edges[p / 2].(p % 2),
+// so that the other endpoint of p is p ^ 1.
+
+/// Sentinel for "no vertex/edge/endpoint/blossom".
+const NONE: usize = usize::MAX;
+
+/// Return the mate of each vertex in a maximum weight matching of the graph.
+/// If 'max_cardinality' is true, only maximum cardinality matchings are considered.
+/// The behavior is undefined unless all edges connect 2 distinct vertices in [0, 'num_vertices'),
+/// and there is at most one edge between any 2 vertices.
+pub(super) fn max_weight_matching(
+    num_vertices: usize,
+    edges: &[(usize, usize, i64)],
+    max_cardinality: bool,
+) -> Vec<Option<usize>> {
+    if edges.is_empty() || num_vertices == 0 {
+        return vec![None; num_vertices];
+    }
+    let mut state = BlossomState::new(num_vertices, edges);
+    state.solve(max_cardinality);
+    state
+        .mate
+        .iter()
+        .map(|&p| {
+            if p == NONE {
+                None
+            } else {
+                Some(state.endpoint[p])
+            }
+        })
+        .collect()
+}
+
+struct BlossomState<'a> {
+    num_vertices: usize,
+    edges: &'a [(usize, usize, i64)],
+    /// endpoint[p] is the vertex at endpoint p
+    endpoint: Vec<usize>,
+    /// neighbend[v] is the list of remote endpoints of edges attached to v
+    neighbend: Vec<Vec<usize>>,
+    /// mate[v] is the remote endpoint of the matched edge of v, or NONE
+    mate: Vec<usize>,
+    /// 0: free, 1: S, 2: T (plus the temporary mark 4 used by 'scan_blossom')
+    label: Vec<u8>,
+    /// The endpoint through which a top-level blossom/vertex got its label
+    labelend: Vec<usize>,
+    /// The top-level blossom containing each vertex
+    inblossom: Vec<usize>,
+    blossomparent: Vec<usize>,
+    blossomchilds: Vec<Vec<usize>>,
+    blossombase: Vec<usize>,
+    blossomendps: Vec<Vec<usize>>,
+    /// The least-slack edge to a different S-blossom, or NONE
+    bestedge: Vec<usize>,
+    blossombestedges: Vec<Option<Vec<usize>>>,
+    unusedblossoms: Vec<usize>,
+    dualvar: Vec<i64>,
+    allowedge: Vec<bool>,
+    queue: Vec<usize>,
+}
+
+impl<'a> BlossomState<'a> {
+    fn new(num_vertices: usize, edges: &'a [(usize, usize, i64)]) -> Self {
+        let n = num_vertices;
+        let max_weight = edges.iter().map(|&(_, _, w)| w).max().unwrap_or(0).max(0);
+        let endpoint = (0..2 * edges.len())
+            .map(|p| {
+                let (i, j, _) = edges[p / 2];
+                if p % 2 == 0 {
+                    i
+                } else {
+                    j
+                }
+            })
+            .collect();
+        let mut neighbend = vec![vec![]; n];
+        edges.iter().enumerate().for_each(|(k, &(i, j, _))| {
+            neighbend[i].push(2 * k + 1);
+            neighbend[j].push(2 * k);
+        });
+        Self {
+            num_vertices,
+            edges,
+            endpoint,
+            neighbend,
+            mate: vec![NONE; n],
+            label: vec![0; 2 * n],
+            labelend: vec![NONE; 2 * n],
+            inblossom: (0..n).collect(),
+            blossomparent: vec![NONE; 2 * n],
+            blossomchilds: vec![vec![]; 2 * n],
+            blossombase: (0..n).chain(std::iter::repeat_n(NONE, n)).collect(),
+            blossomendps: vec![vec![]; 2 * n],
+            bestedge: vec![NONE; 2 * n],
+            blossombestedges: vec![None; 2 * n],
+            unusedblossoms: (n..2 * n).collect(),
+            dualvar: std::iter::repeat_n(max_weight, n)
+                .chain(std::iter::repeat_n(0, n))
+                .collect(),
+            allowedge: vec![false; edges.len()],
+            queue: vec![],
+        }
+    }
+
+    fn slack(&self, k: usize) -> i64 {
+        let (i, j, w) = self.edges[k];
+        self.dualvar[i] + self.dualvar[j] - 2 * w
+    }
+
+    /// All vertices contained in blossom 'b' (which may itself be a vertex).
+    fn blossom_leaves(&self, b: usize) -> Vec<usize> {
+        let mut leaves = vec![];
+        let mut stack = vec![b];
+        while let Some(t) = stack.pop() {
+            if t < self.num_vertices {
+                leaves.push(t);
+            } else {
+                stack.extend(self.blossomchilds[t].iter().rev());
+            }
+        }
+        leaves
+    }
+
+    /// Index into a cyclic list with a possibly negative index.
+    fn cyclic(list: &[usize], j: isize) -> usize {
+        list[j.rem_euclid(list.len() as isize) as usize]
+    }
+
+    fn assign_label(&mut self, w: usize, t: u8, p: usize) {
+        let (mut w, mut t, mut p) = (w, t, p);
+        loop {
+            let b = self.inblossom[w];
+            self.label[w] = t;
+            self.label[b] = t;
+            self.labelend[w] = p;
+            self.labelend[b] = p;
+            self.bestedge[w] = NONE;
+            self.bestedge[b] = NONE;
+            if t == 1 {
+                let leaves = self.blossom_leaves(b);
+                self.queue.extend(leaves);
+                return;
+            }
+            // t == 2: label the mate of the base with S
+            let base = self.blossombase[b];
+            let mate_endpoint = self.mate[base];
+            w = self.endpoint[mate_endpoint];
+            t = 1;
+            p = mate_endpoint ^ 1;
+        }
+    }
+
+    /// Trace back from 'v' and 'w' to discover either a new blossom or an augmenting path.
+    /// Return the base vertex of the new blossom, or NONE.
+    fn scan_blossom(&mut self, mut v: usize, mut w: usize) -> usize {
+        let mut path = vec![];
+        let mut base = NONE;
+        while v != NONE || w != NONE {
+            let mut b = self.inblossom[v];
+            if self.label[b] & 4 != 0 {
+                base = self.blossombase[b];
+                break;
+            }
+            path.push(b);
+            self.label[b] = 5;
+            if self.labelend[b] == NONE {
+                // The base of blossom b is single; stop tracing this path
+                v = NONE;
+            } else {
+                v = self.endpoint[self.labelend[b]];
+                b = self.inblossom[v];
+                v = self.endpoint[self.labelend[b]];
+            }
+            if w != NONE {
+                std::mem::swap(&mut v, &mut w);
+            }
+        }
+        path.into_iter().for_each(|b| self.label[b] = 1);
+        base
+    }
+
+    /// Construct a new blossom with the given 'base', containing edge 'k' which connects
+    /// a pair of S vertices.
+    fn add_blossom(&mut self, base: usize, k: usize) {
+        let (mut v, mut w, _) = self.edges[k];
+        let bb = self.inblossom[base];
+        let mut bv = self.inblossom[v];
+        let mut bw = self.inblossom[w];
+        let b = self
+            .unusedblossoms
+            .pop()
+            .expect("Blossom ids must not run out.");
+        self.blossombase[b] = base;
+        self.blossomparent[b] = NONE;
+        self.blossomparent[bb] = b;
+        let mut path = vec![];
+        let mut endps = vec![];
+        // Trace back from v to base
+        while bv != bb {
+            self.blossomparent[bv] = b;
+            path.push(bv);
+            endps.push(self.labelend[bv]);
+            v = self.endpoint[self.labelend[bv]];
+            bv = self.inblossom[v];
+        }
+        path.push(bb);
+        path.reverse();
+        endps.reverse();
+        endps.push(2 * k);
+        // Trace back from w to base
+        while bw != bb {
+            self.blossomparent[bw] = b;
+            path.push(bw);
+            endps.push(self.labelend[bw] ^ 1);
+            w = self.endpoint[self.labelend[bw]];
+            bw = self.inblossom[w];
+        }
+        self.blossomchilds[b] = path.clone();
+        self.blossomendps[b] = endps;
+        self.label[b] = 1;
+        self.labelend[b] = self.labelend[bb];
+        self.dualvar[b] = 0;
+        for leaf in self.blossom_leaves(b) {
+            if self.label[self.inblossom[leaf]] == 2 {
+                // This T-vertex now turns into an S-vertex because it becomes part of an S-blossom
+                self.queue.push(leaf);
+            }
+            self.inblossom[leaf] = b;
+        }
+
+        // Compute the least-slack edges to neighbouring S-blossoms
+        let mut bestedgeto = vec![NONE; 2 * self.num_vertices];
+        for &bv in path.iter() {
+            let nblists: Vec<Vec<usize>> = match self.blossombestedges[bv].take() {
+                Some(list) => vec![list],
+                None => self
+                    .blossom_leaves(bv)
+                    .into_iter()
+                    .map(|leaf| self.neighbend[leaf].iter().map(|p| p / 2).collect())
+                    .collect(),
+            };
+            for nblist in nblists.iter() {
+                for &k in nblist.iter() {
+                    let (mut i, mut j, _) = self.edges[k];
+                    if self.inblossom[j] == b {
+                        std::mem::swap(&mut i, &mut j);
+                    }
+                    let bj = self.inblossom[j];
+                    if bj != b
+                        && self.label[bj] == 1
+                        && (bestedgeto[bj] == NONE || self.slack(k) < self.slack(bestedgeto[bj]))
+                    {
+                        bestedgeto[bj] = k;
+                    }
+                }
+            }
+            self.bestedge[bv] = NONE;
+        }
+        let best_edges: Vec<usize> = bestedgeto.into_iter().filter(|&k| k != NONE).collect();
+        self.bestedge[b] = NONE;
+        for &k in best_edges.iter() {
+            if self.bestedge[b] == NONE || self.slack(k) < self.slack(self.bestedge[b]) {
+                self.bestedge[b] = k;
+            }
+        }
+        self.blossombestedges[b] = Some(best_edges);
+    }
+
+    /// Expand the given top-level blossom.
+    fn expand_blossom(&mut self, b: usize, endstage: bool) {
+        // Convert sub-blossoms into top-level blossoms
+        for s in self.blossomchilds[b].clone() {
+            self.blossomparent[s] = NONE;
+            if s < self.num_vertices {
+                self.inblossom[s] = s;
+            } else if endstage && self.dualvar[s] == 0 {
+                // Recursively expand this sub-blossom
+                self.expand_blossom(s, endstage);
+            } else {
+                for leaf in self.blossom_leaves(s) {
+                    self.inblossom[leaf] = s;
+                }
+            }
+        }
+
+        // If we expand a T-blossom during a stage, its sub-blossoms must be relabeled
+        if !endstage && self.label[b] == 2 {
+            let childs = self.blossomchilds[b].clone();
+            let endps = self.blossomendps[b].clone();
+            let entrychild = self.inblossom[self.endpoint[self.labelend[b] ^ 1]];
+            let mut j = childs
+                .iter()
+                .position(|&child| child == entrychild)
+                .expect("Entry child must be in blossom.") as isize;
+            let (jstep, endptrick): (isize, isize) = if j & 1 != 0 {
+                // Start index is odd; go forward and wrap
+                j -= childs.len() as isize;
+                (1, 0)
+            } else {
+                // Start index is even; go backward
+                (-1, 1)
+            };
+            // Move along the blossom until we get to the base
+            let mut p = self.labelend[b];
+            while j != 0 {
+                // Relabel the T-sub-blossom
+                self.label[self.endpoint[p ^ 1]] = 0;
+                let q = Self::cyclic(&endps, j - endptrick);
+                self.label[self.endpoint[q ^ endptrick as usize ^ 1]] = 0;
+                self.assign_label(self.endpoint[p ^ 1], 2, p);
+                // Step to the next S-sub-blossom and note its forward endpoint
+                self.allowedge[q / 2] = true;
+                j += jstep;
+                p = Self::cyclic(&endps, j - endptrick) ^ endptrick as usize;
+                // Step to the next T-sub-blossom
+                self.allowedge[p / 2] = true;
+                j += jstep;
+            }
+            // Relabel the base T-sub-blossom without stepping through to its mate
+            let bv = Self::cyclic(&childs, j);
+            let v = self.endpoint[p ^ 1];
+            self.label[v] = 2;
+            self.label[bv] = 2;
+            self.labelend[v] = p;
+            self.labelend[bv] = p;
+            self.bestedge[bv] = NONE;
+            // Continue along the blossom until we get back to the entry child
+            j += jstep;
+            while Self::cyclic(&childs, j) != entrychild {
+                let bv = Self::cyclic(&childs, j);
+                if self.label[bv] == 1 {
+                    // This sub-blossom just got label S through one of its neighbours
+                    j += jstep;
+                    continue;
+                }
+                // If the sub-blossom contains a reachable vertex, assign label T to the sub-blossom
+                if let Some(v) = self
+                    .blossom_leaves(bv)
+                    .into_iter()
+                    .find(|&leaf| self.label[leaf] != 0)
+                {
+                    self.label[v] = 0;
+                    self.label[self.endpoint[self.mate[self.blossombase[bv]]]] = 0;
+                    self.assign_label(v, 2, self.labelend[v]);
+                }
+                j += jstep;
+            }
+        }
+
+        // Recycle the blossom number
+        self.label[b] = 0;
+        self.labelend[b] = NONE;
+        self.blossomchilds[b] = vec![];
+        self.blossomendps[b] = vec![];
+        self.blossombase[b] = NONE;
+        self.blossombestedges[b] = None;
+        self.bestedge[b] = NONE;
+        self.unusedblossoms.push(b);
+    }
+
+    /// Swap matched/unmatched edges over an alternating path through blossom 'b'
+    /// between vertex 'v' and the base vertex.
+    fn augment_blossom(&mut self, b: usize, v: usize) {
+        // Bubble up through the blossom tree from vertex v to an immediate sub-blossom of b
+        let mut t = v;
+        while self.blossomparent[t] != b {
+            t = self.blossomparent[t];
+        }
+        // Recursively deal with the first sub-blossom
+        if t >= self.num_vertices {
+            self.augment_blossom(t, v);
+        }
+        let childs = self.blossomchilds[b].clone();
+        let endps = self.blossomendps[b].clone();
+        let i = childs
+            .iter()
+            .position(|&child| child == t)
+            .expect("Sub-blossom must be in blossom.");
+        let mut j = i as isize;
+        let (jstep, endptrick): (isize, isize) = if i & 1 != 0 {
+            j -= childs.len() as isize;
+            (1, 0)
+        } else {
+            (-1, 1)
+        };
+        // Move along the blossom until we get to the base
+        while j != 0 {
+            // Step to the next sub-blossom and augment it recursively
+            j += jstep;
+            let t = Self::cyclic(&childs, j);
+            let p = Self::cyclic(&endps, j - endptrick) ^ endptrick as usize;
+            if t >= self.num_vertices {
+                self.augment_blossom(t, self.endpoint[p]);
+            }
+            // Step to the next sub-blossom and augment it recursively
+            j += jstep;
+            let t = Self::cyclic(&childs, j);
+            if t >= self.num_vertices {
+                self.augment_blossom(t, self.endpoint[p ^ 1]);
+            }
+            // Match the edge connecting those sub-blossoms
+            self.mate[self.endpoint[p]] = p ^ 1;
+            self.mate[self.endpoint[p ^ 1]] = p;
+        }
+        // Rotate the list of sub-blossoms to put the new base at the front
+        self.blossomchilds[b].rotate_left(i);
+        self.blossomendps[b].rotate_left(i);
+        self.blossombase[b] = self.blossombase[self.blossomchilds[b][0]];
+    }
+
+    /// Swap matched/unmatched edges over an alternating path between two single vertices,
+    /// which goes through edge 'k' connecting a pair of S vertices.
+    fn augment_matching(&mut self, k: usize) {
+        let (v, w, _) = self.edges[k];
+        for (mut s, mut p) in [(v, 2 * k + 1), (w, 2 * k)] {
+            // Match vertex s to remote endpoint p, then trace back from s until we find a single vertex
+            loop {
+                let bs = self.inblossom[s];
+                if bs >= self.num_vertices {
+                    self.augment_blossom(bs, s);
+                }
+                self.mate[s] = p;
+                if self.labelend[bs] == NONE {
+                    // Reached single vertex; stop
+                    break;
+                }
+                let t = self.endpoint[self.labelend[bs]];
+                let bt = self.inblossom[t];
+                s = self.endpoint[self.labelend[bt]];
+                let j = self.endpoint[self.labelend[bt] ^ 1];
+                if bt >= self.num_vertices {
+                    self.augment_blossom(bt, j);
+                }
+                self.mate[j] = self.labelend[bt];
+                p = self.labelend[bt] ^ 1;
+            }
+        }
+    }
+
+    fn solve(&mut self, max_cardinality: bool) {
+        let n = self.num_vertices;
+        // Each iteration of this loop is a stage; a stage finds an augmenting path and uses it
+        // to improve the matching
+        for _ in 0..n {
+            self.label.iter_mut().for_each(|label| *label = 0);
+            self.bestedge.iter_mut().for_each(|edge| *edge = NONE);
+            self.blossombestedges[n..]
+                .iter_mut()
+                .for_each(|edges| *edges = None);
+            self.allowedge
+                .iter_mut()
+                .for_each(|allowed| *allowed = false);
+            self.queue.clear();
+
+            // Label single blossoms/vertices with S and put them in the queue
+            for v in 0..n {
+                if self.mate[v] == NONE && self.label[self.inblossom[v]] == 0 {
+                    self.assign_label(v, 1, NONE);
+                }
+            }
+
+            let mut augmented = false;
+            loop {
+                // Continue labeling until all vertices which are reachable through an alternating
+                // path have got a label
+                while !augmented {
+                    let v = match self.queue.pop() {
+                        Some(v) => v,
+                        None => break,
+                    };
+                    for p in self.neighbend[v].clone() {
+                        let k = p / 2;
+                        let w = self.endpoint[p];
+                        // w is a neighbour to v
+                        if self.inblossom[v] == self.inblossom[w] {
+                            // This edge is internal to a blossom; ignore it
+                            continue;
+                        }
+                        let mut kslack = 0;
+                        if !self.allowedge[k] {
+                            kslack = self.slack(k);
+                            if kslack <= 0 {
+                                // Edge k has zero slack, so it is allowable
+                                self.allowedge[k] = true;
+                            }
+                        }
+                        if self.allowedge[k] {
+                            if self.label[self.inblossom[w]] == 0 {
+                                // w is a free vertex; label w with T and label its mate with S
+                                self.assign_label(w, 2, p ^ 1);
+                            } else if self.label[self.inblossom[w]] == 1 {
+                                // w is an S-vertex; scan for a blossom or an augmenting path
+                                let base = self.scan_blossom(v, w);
+                                if base != NONE {
+                                    self.add_blossom(base, k);
+                                } else {
+                                    self.augment_matching(k);
+                                    augmented = true;
+                                    break;
+                                }
+                            } else if self.label[w] == 0 {
+                                // w is inside a T-blossom, but w itself has not yet been reached
+                                self.label[w] = 2;
+                                self.labelend[w] = p ^ 1;
+                            }
+                        } else if self.label[self.inblossom[w]] == 1 {
+                            // Keep track of the least-slack non-allowable edge to a different S-blossom
+                            let b = self.inblossom[v];
+                            if self.bestedge[b] == NONE || kslack < self.slack(self.bestedge[b]) {
+                                self.bestedge[b] = k;
+                            }
+                        } else if self.label[w] == 0
+                            && (self.bestedge[w] == NONE || kslack < self.slack(self.bestedge[w]))
+                        {
+                            // w is a free vertex (or an unreached vertex inside a T-blossom)
+                            self.bestedge[w] = k;
+                        }
+                    }
+                }
+
+                if augmented {
+                    break;
+                }
+
+                // There is no augmenting path under these constraints; compute delta and reduce
+                // slack in the optimization problem
+                let mut delta_type = 0;
+                let mut delta = 0;
+                let mut delta_edge = NONE;
+                let mut delta_blossom = NONE;
+
+                // Delta 1: the minimum value of any vertex dual
+                if !max_cardinality {
+                    delta_type = 1;
+                    delta = self.dualvar[..n].iter().copied().min().unwrap_or(0);
+                }
+
+                // Delta 2: the minimum slack on any edge between an S-vertex and a free vertex
+                for v in 0..n {
+                    if self.label[self.inblossom[v]] == 0 && self.bestedge[v] != NONE {
+                        let d = self.slack(self.bestedge[v]);
+                        if delta_type == 0 || d < delta {
+                            delta = d;
+                            delta_type = 2;
+                            delta_edge = self.bestedge[v];
+                        }
+                    }
+                }
+
+                // Delta 3: half the minimum slack on any edge between a pair of S-blossoms
+                for b in 0..2 * n {
+                    if self.blossomparent[b] == NONE
+                        && self.label[b] == 1
+                        && self.bestedge[b] != NONE
+                    {
+                        let d = self.slack(self.bestedge[b]) / 2;
+                        if delta_type == 0 || d < delta {
+                            delta = d;
+                            delta_type = 3;
+                            delta_edge = self.bestedge[b];
+                        }
+                    }
+                }
+
+                // Delta 4: the minimum z variable of any T-blossom
+                for b in n..2 * n {
+                    if self.blossombase[b] != NONE
+                        && self.blossomparent[b] == NONE
+                        && self.label[b] == 2
+                        && (delta_type == 0 || self.dualvar[b] < delta)
+                    {
+                        delta = self.dualvar[b];
+                        delta_type = 4;
+                        delta_blossom = b;
+                    }
+                }
+
+                if delta_type == 0 {
+                    // No further improvement possible; max-cardinality optimum reached
+                    delta_type = 1;
+                    delta = self.dualvar[..n].iter().copied().min().unwrap_or(0).max(0);
+                }
+
+                // Update dual variables according to delta
+                for v in 0..n {
+                    match self.label[self.inblossom[v]] {
+                        1 => self.dualvar[v] -= delta,
+                        2 => self.dualvar[v] += delta,
+                        _ => {}
+                    }
+                }
+                for b in n..2 * n {
+                    if self.blossombase[b] != NONE && self.blossomparent[b] == NONE {
+                        match self.label[b] {
+                            1 => self.dualvar[b] += delta,
+                            2 => self.dualvar[b] -= delta,
+                            _ => {}
+                        }
+                    }
+                }
+
+                // Take action at the point where minimum delta occurred
+                match delta_type {
+                    1 => break,
+                    2 => {
+                        self.allowedge[delta_edge] = true;
+                        let (mut i, j, _) = self.edges[delta_edge];
+                        if self.label[self.inblossom[i]] == 0 {
+                            i = j;
+                        }
+                        self.queue.push(i);
+                    }
+                    3 => {
+                        self.allowedge[delta_edge] = true;
+                        let (i, _, _) = self.edges[delta_edge];
+                        self.queue.push(i);
+                    }
+                    _ => self.expand_blossom(delta_blossom, false),
+                }
+            }
+
+            // Stop when no more augmenting path can be found
+            if !augmented {
+                break;
+            }
+
+            // End of a stage; expand all S-blossoms which have dualvar = 0
+            for b in n..2 * n {
+                if self.blossomparent[b] == NONE
+                    && self.blossombase[b] != NONE
+                    && self.label[b] == 1
+                    && self.dualvar[b] == 0
+                {
+                    self.expand_blossom(b, true);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The maximum weight over all matchings, by brute force.
+    fn brute_force_max_weight(
+        num_vertices: usize,
+        edges: &[(usize, usize, i64)],
+        max_cardinality: bool,
+    ) -> (usize, i64) {
+        fn search(
+            v: usize,
+            used: &mut Vec<bool>,
+            edges: &[(usize, usize, i64)],
+            cardinality: usize,
+            weight: i64,
+            best: &mut (usize, i64),
+            max_cardinality: bool,
+        ) {
+            if v == used.len() {
+                let better = if max_cardinality {
+                    (cardinality, weight) > *best
+                } else {
+                    weight > best.1
+                };
+                if better {
+                    *best = (cardinality, weight);
+                }
+                return;
+            }
+            if used[v] {
+                return search(
+                    v + 1,
+                    used,
+                    edges,
+                    cardinality,
+                    weight,
+                    best,
+                    max_cardinality,
+                );
+            }
+            // Leave v single
+            search(
+                v + 1,
+                used,
+                edges,
+                cardinality,
+                weight,
+                best,
+                max_cardinality,
+            );
+            for &(i, j, w) in edges.iter() {
+                let u = if i == v {
+                    j
+                } else if j == v {
+                    i
+                } else {
+                    continue;
+                };
+                if !used[u] {
+                    used[v] = true;
+                    used[u] = true;
+                    search(
+                        v + 1,
+                        used,
+                        edges,
+                        cardinality + 1,
+                        weight + w,
+                        best,
+                        max_cardinality,
+                    );
+                    used[v] = false;
+                    used[u] = false;
+                }
+            }
+        }
+        let mut best = (0, 0);
+        search(
+            0,
+            &mut vec![false; num_vertices],
+            edges,
+            0,
+            0,
+            &mut best,
+            max_cardinality,
+        );
+        best
+    }
+
+    #[test]
+    fn matches_brute_force_on_pseudo_random_graphs() {
+        let mut seed = 0x853c_49e6_748f_ea9b_u64;
+        let mut next = |n: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed % n
+        };
+        for _ in 0..300 {
+            // GIVEN
+            let num_vertices = next(9) as usize + 1;
+            let mut edges = vec![];
+            for i in 0..num_vertices {
+                for j in (i + 1)..num_vertices {
+                    if next(3) != 0 {
+                        edges.push((i, j, next(20) as i64 - 2));
+                    }
+                }
+            }
+            let max_cardinality = next(2) == 0;
+
+            // WHEN
+            let mate = max_weight_matching(num_vertices, &edges, max_cardinality);
+
+            // THEN
+            let weight_of = |i: usize, j: usize| {
+                edges
+                    .iter()
+                    .find(|&&(a, b, _)| (a, b) == (i, j) || (a, b) == (j, i))
+                    .unwrap()
+                    .2
+            };
+            let (mut cardinality, mut weight) = (0, 0);
+            for (v, &u) in mate.iter().enumerate() {
+                if let Some(u) = u {
+                    assert_eq!(mate[u], Some(v));
+                    if v < u {
+                        cardinality += 1;
+                        weight += weight_of(v, u);
+                    }
+                }
+            }
+            let expected = brute_force_max_weight(num_vertices, &edges, max_cardinality);
+            if max_cardinality {
+                assert_eq!((cardinality, weight), expected);
+            } else {
+                assert_eq!(weight, expected.1);
+            }
+        }
+    }
+}
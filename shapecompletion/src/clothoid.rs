@@ -0,0 +1,191 @@
+use std::f64::consts::PI;
+
+use visioncortex::{PointF64, Spline};
+
+/// The number of intervals of the composite Simpson's rule used to evaluate Fresnel-type integrals.
+const NUM_QUADRATURE_INTERVALS: usize = 128;
+const MAX_NEWTON_ITERATIONS: usize = 32;
+const NEWTON_TOLERANCE: f64 = 1e-10;
+
+/// A clothoid (Euler spiral) segment, whose curvature changes linearly with arc length:
+/// angle(s) = 'start_angle' + 'curvature' * s + 'curvature_rate' * s^2 / 2, for s in [0, 'length'].
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Clothoid {
+    pub(super) start: PointF64,
+    pub(super) start_angle: f64,
+    pub(super) curvature: f64,
+    pub(super) curvature_rate: f64,
+    pub(super) length: f64,
+}
+
+impl Clothoid {
+    /// Find the clothoid from 'from_point' to 'to_point', leaving 'from_point' in the direction of
+    /// 'from_tangent' and arriving at 'to_point' in the direction of 'to_tangent' (G1 Hermite
+    /// interpolation, Bertolazzi & Frego 2015).
+    /// None is returned if the points coincide, a tangent is zero, or the solution does not converge.
+    pub(super) fn fit(
+        from_point: PointF64,
+        from_tangent: PointF64,
+        to_point: PointF64,
+        to_tangent: PointF64,
+    ) -> Option<Self> {
+        let (dx, dy) = (to_point.x - from_point.x, to_point.y - from_point.y);
+        let distance = dx.hypot(dy);
+        if distance == 0.0 || from_tangent.norm() == 0.0 || to_tangent.norm() == 0.0 {
+            return None;
+        }
+        let chord_angle = dy.atan2(dx);
+        let start_angle = from_tangent.y.atan2(from_tangent.x);
+        let end_angle = to_tangent.y.atan2(to_tangent.x);
+        let phi0 = normalize_angle(start_angle - chord_angle);
+        let phi1 = normalize_angle(end_angle - chord_angle);
+        let delta = phi1 - phi0;
+
+        // Solve Y(2A, delta - A, phi0) = 0 for A by Newton's method
+        let mut a = 3.0 * (phi0 + phi1);
+        let mut converged = false;
+        for _ in 0..MAX_NEWTON_ITERATIONS {
+            let g = integrate(|t| (a * t * t + (delta - a) * t + phi0).sin());
+            if g.abs() < NEWTON_TOLERANCE {
+                converged = true;
+                break;
+            }
+            let dg = integrate(|t| (a * t * t + (delta - a) * t + phi0).cos() * (t * t - t));
+            if dg == 0.0 || !dg.is_finite() {
+                return None;
+            }
+            a -= g / dg;
+        }
+        if !converged {
+            return None;
+        }
+
+        let h = integrate(|t| (a * t * t + (delta - a) * t + phi0).cos());
+        let length = distance / h;
+        if !length.is_finite() || length <= 0.0 {
+            return None;
+        }
+        Some(Self {
+            start: from_point,
+            start_angle,
+            curvature: (delta - a) / length,
+            curvature_rate: 2.0 * a / (length * length),
+            length,
+        })
+    }
+
+    /// The tangent angle at arc length 's'.
+    pub(super) fn angle_at(&self, s: f64) -> f64 {
+        self.start_angle + self.curvature * s + self.curvature_rate * s * s / 2.0
+    }
+
+    /// The point at arc length 's'.
+    pub(super) fn point_at(&self, s: f64) -> PointF64 {
+        let (x, y) = (
+            integrate(|t| self.angle_at(s * t).cos()),
+            integrate(|t| self.angle_at(s * t).sin()),
+        );
+        self.start + PointF64::new(x, y) * s
+    }
+
+    /// Approximate the clothoid with 'num_parts' (at least 1) cubic bezier curves of equal arc length,
+    /// each matching the position and tangent of the clothoid at both of its ends.
+    pub(super) fn to_spline(self, num_parts: usize) -> Spline {
+        let num_parts = std::cmp::max(num_parts, 1);
+        let part_length = self.length / num_parts as f64;
+        let unit_tangent_at = |s: f64| {
+            let angle = self.angle_at(s);
+            PointF64::new(angle.cos(), angle.sin())
+        };
+
+        let mut spline = Spline::new(self.start);
+        let mut from = self.start;
+        for i in 0..num_parts {
+            let (s0, s1) = (i as f64 * part_length, (i + 1) as f64 * part_length);
+            let to = self.point_at(s1);
+            spline.add(
+                from + unit_tangent_at(s0) * (part_length / 3.0),
+                to - unit_tangent_at(s1) * (part_length / 3.0),
+                to,
+            );
+            from = to;
+        }
+        spline
+    }
+}
+
+/// Normalize 'angle' into (-PI, PI].
+fn normalize_angle(angle: f64) -> f64 {
+    let angle = angle % (2.0 * PI);
+    if angle > PI {
+        angle - 2.0 * PI
+    } else if angle <= -PI {
+        angle + 2.0 * PI
+    } else {
+        angle
+    }
+}
+
+/// The integral of 'f' over [0, 1] by the composite Simpson's rule.
+fn integrate<F: Fn(f64) -> f64>(f: F) -> f64 {
+    let n = NUM_QUADRATURE_INTERVALS;
+    let h = 1.0 / n as f64;
+    let sum: f64 = (0..=n)
+        .map(|i| {
+            let weight = if i == 0 || i == n {
+                1.0
+            } else if i % 2 == 1 {
+                4.0
+            } else {
+                2.0
+            };
+            weight * f(i as f64 * h)
+        })
+        .sum();
+    sum * h / 3.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_point_approximately(a: PointF64, b: PointF64) {
+        assert!(a.distance_to(b) < 1e-6, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn symmetric_tangents_fit_a_circular_arc() {
+        // GIVEN the endpoints of a quarter circle of radius 10 centered at the origin
+        let (from_point, to_point) = (PointF64::new(10.0, 0.0), PointF64::new(0.0, 10.0));
+        let (from_tangent, to_tangent) = (PointF64::new(0.0, 1.0), PointF64::new(-1.0, 0.0));
+
+        // WHEN
+        let clothoid = Clothoid::fit(from_point, from_tangent, to_point, to_tangent).unwrap();
+
+        // THEN
+        assert!(clothoid.curvature_rate.abs() < 1e-9);
+        assert!((clothoid.curvature - 0.1).abs() < 1e-9);
+        assert!((clothoid.length - 5.0 * PI).abs() < 1e-6);
+        let midpoint = clothoid.point_at(clothoid.length / 2.0);
+        assert_point_approximately(midpoint, PointF64::new(1.0, 1.0) * (10.0 / 2.0_f64.sqrt()));
+    }
+
+    #[test]
+    fn fitted_clothoid_matches_both_endpoints_and_tangents() {
+        // GIVEN an S-shaped configuration
+        let (from_point, to_point) = (PointF64::new(0.0, 0.0), PointF64::new(20.0, 5.0));
+        let (from_tangent, to_tangent) = (PointF64::new(1.0, 1.0), PointF64::new(1.0, 0.5));
+
+        // WHEN
+        let clothoid = Clothoid::fit(from_point, from_tangent, to_point, to_tangent).unwrap();
+        let spline = clothoid.to_spline(4);
+
+        // THEN
+        assert_point_approximately(clothoid.point_at(clothoid.length), to_point);
+        let end_angle = clothoid.angle_at(clothoid.length);
+        assert!(normalize_angle(end_angle - 0.5_f64.atan2(1.0)).abs() < 1e-9);
+        assert_eq!(spline.points.len(), 13);
+        assert_point_approximately(spline.points[0], from_point);
+        assert_point_approximately(spline.points[12], to_point);
+    }
+}
@@ -3,24 +3,163 @@ use std::collections::HashSet;
 use bit_vec::BitVec;
 use visioncortex::{
     clusters::Cluster, BinaryImage, BoundingRect, Color, ColorName, CompoundPath,
-    CompoundPathElement, PathI32, PointI32,
+    CompoundPathElement, PathI32, PointF64, PointI32,
 };
 
 use crate::{
     curve::{CurveIntrapolator, CurveIntrapolatorConfig},
     debugger::{Debugger, DummyDebugger},
-    filler::{FilledHoleMatrix, HoleFiller},
-    geo::bezier_curves_intersection,
-    matcher::Matcher,
-    matcher_helper::{MatchItem, MatchItemSet, Matching},
+    error::CompletionError,
+    filler::{FillRule, FilledHoleElement, FilledHoleMatrix, HoleFiller},
+    fragment::Fragment,
+    geo::{
+        bezier_curves_intersection, bounds_of_points, distance_to_segment, flatten_compound_path,
+        polylines_crossings, Bounds,
+    },
+    hole::Hole,
+    match_cost::MatchCost,
+    matcher::{Matcher, MatcherAlgorithm, MatcherConfig},
+    matcher_helper::{MatchItem, MatchItemSet, MatchSegment, Matching},
+    score::MatchingScore,
+    simplify::{simplify_segment, SimplifyConfig},
+    skeleton::{estimate_line_width, find_branches_from_hole, skeletonize, CenterlineConfig},
+    symmetry::{detect_symmetry_axis, fill_hole_by_reflection, SymmetryAxis, SymmetryConfig},
 };
 
+/// The number of line segments each cubic bezier curve is flattened into when finding crossings
+const NUM_CROSSING_SAMPLES_PER_BEZIER: usize = 16;
+/// Intrapolated curves may overlap fragments within this distance (in pixels) from their ends,
+/// where they join the fragments
+const FRAGMENT_JOIN_CLEARANCE: f64 = 1.5;
+/// Intrapolated curves may touch the visible contours within this distance (in pixels) from their
+/// ends, where they continue the contours
+const CONTOUR_JOIN_CLEARANCE: f64 = 1.5;
+/// The ends of the reflected contours in a hole filled by reflection continue the tails within this
+/// distance (in pixels), which the walked endpoints may be off the hole
+const REFLECTION_TAIL_TOLERANCE: f64 = 2.0;
+
 pub struct ShapeCompletor {
     image: BinaryImage,
     simplify_tolerance: f64,
+    simplify_config: SimplifyConfig,
     curve_intrapolator_config: CurveIntrapolatorConfig,
+    matcher_config: MatcherConfig,
+    /// None for the default match cost of the matcher algorithm
+    match_cost: Option<Box<dyn MatchCost>>,
     filler_blank_boundary_pixels_tolerance: usize,
+    fill_rule: FillRule,
     debugger: Box<dyn Debugger>,
+    symmetry_config: Option<SymmetryConfig>,
+    centerline_config: Option<CenterlineConfig>,
+}
+
+/// One plausible completion of a hole, derived from one matching or from reflective symmetry.
+#[derive(Debug)]
+pub struct CompletionHypothesis {
+    /// Empty if the hole is filled by reflection.
+    pub matching: Matching,
+    /// One intrapolated curve per pair in 'matching' (same order), followed by one stub per
+    /// unmatched endpoint in 'matching' (same order).
+    pub intrapolated_curves: Vec<CompoundPath>,
+    pub filled_hole: FilledHoleMatrix,
+    /// If the hole is filled by reflection, the score of the reflected contours inside it (see
+    /// ShapeCompletor::complete_shape_hypotheses()).
+    pub score: MatchingScore,
+    /// In (0.0, 1.0]. The confidences of all hypotheses of a hole sum up to 1.
+    pub confidence: f64,
+    /// The symmetry axis across which the hole is filled by reflection, if any.
+    pub symmetry_axis: Option<SymmetryAxis>,
+}
+
+/// The outputs of every stage of the shape completion pipeline for one hole.
+#[derive(Debug)]
+pub struct CompletionResult {
+    /// The completed hole.
+    pub hole: Hole,
+    /// The simplified path segments whose heads (index 0) are the endpoints on the hole boundary.
+    pub path_segments: Vec<PathI32>,
+    /// The match items constructed from 'path_segments', whose ids are indices of 'path_segments'.
+    pub match_item_set: MatchItemSet,
+    /// All candidate matchings in the order they were tried.
+    pub matchings: Vec<Matching>,
+    /// The matching whose curves were intrapolated. None iff there is no endpoint or the hole is
+    /// filled by reflection.
+    pub matching: Option<Matching>,
+    /// The scores of the successfully intrapolated matchings that were evaluated, in the order they
+    /// were tried. 'matching' is the one with the lowest total score.
+    pub matching_scores: Vec<MatchingScore>,
+    /// Whether tail tangents had to be corrected for the intrapolation to succeed.
+    pub tail_tangents_corrected: bool,
+    /// One intrapolated curve per pair in 'matching' (same order), followed by one stub per
+    /// unmatched endpoint in 'matching' (same order).
+    pub intrapolated_curves: Vec<CompoundPath>,
+    /// What is inside the hole after filling.
+    pub filled_hole: FilledHoleMatrix,
+    /// The symmetry axis across which the hole is filled by reflection, if any.
+    /// Matching and intrapolation are skipped in that case.
+    pub symmetry_axis: Option<SymmetryAxis>,
+}
+
+/// The path segments walked from the endpoints on the boundary of a hole, and from the ends of the
+/// fragments surviving inside it.
+#[derive(Default)]
+struct WalkedSegments {
+    /// The simplified segments to be matched, whose heads (index 0) are the endpoints.
+    match_segments: Vec<MatchSegment>,
+    /// The unsimplified points of each of 'match_segments' (same order).
+    raw_segments: Vec<PathI32>,
+    /// The width (in pixels) to draw the intrapolated centerlines at, in centerline mode.
+    line_width: Option<f64>,
+    /// The foreground surviving inside the hole.
+    fragments: Vec<Fragment>,
+    /// The number of the last of 'match_segments' which are walked from the ends of 'fragments'
+    /// instead of from the hole boundary.
+    num_fragment_segments: usize,
+}
+
+impl WalkedSegments {
+    /// The points of 'match_segments' (same order).
+    fn path_segments(&self) -> Vec<PathI32> {
+        self.match_segments
+            .iter()
+            .map(|segment| segment.points.clone())
+            .collect()
+    }
+}
+
+/// The candidate matchings of the endpoints of a hole, and the successfully intrapolated ones.
+struct IntrapolatedMatchings {
+    /// The match items constructed from the path segments.
+    match_item_set: MatchItemSet,
+    /// All candidate matchings in the order they were tried.
+    matchings: Vec<Matching>,
+    /// Whether tail tangents had to be corrected for the intrapolation to succeed.
+    tail_tangents_corrected: bool,
+    /// The intrapolated curves and scores of the successful matchings, in the order they were tried.
+    candidates: Vec<(Vec<CompoundPath>, MatchingScore)>,
+}
+
+/// Why a matching is rejected when its curves are intrapolated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Rejection {
+    /// Some curve cannot be intrapolated.
+    NotIntrapolated,
+    /// Some curves intersect each other where it is not allowed.
+    CurvesIntersect,
+    /// Some curve runs through a fragment surviving inside the hole.
+    CurvesThroughFragments,
+    /// Some curve crosses a visible contour outside the hole.
+    CurvesCrossContours,
+}
+
+/// The result of completing several holes of the same image jointly.
+pub struct MultiHoleCompletion {
+    /// The holes that are actually completed, after merging overlapping or touching holes.
+    pub holes: Vec<Hole>,
+    /// The completion result of each hole in 'holes' (same order).
+    pub results: Vec<Result<CompletionResult, CompletionError>>,
+    /// A copy of the image with all successfully filled holes composited into it.
+    pub image: BinaryImage,
 }
 
 // API
@@ -35,17 +174,72 @@ impl ShapeCompletor {
         Self {
             image,
             simplify_tolerance,
+            simplify_config: SimplifyConfig::default(),
             curve_intrapolator_config,
+            matcher_config: MatcherConfig::default(),
+            match_cost: None,
             filler_blank_boundary_pixels_tolerance,
+            fill_rule: FillRule::BoundarySampling,
             debugger: debugger.unwrap_or_else(|| Box::new(DummyDebugger)),
+            symmetry_config: None,
+            centerline_config: None,
         }
     }
 
-    pub fn complete_shape_and_draw(&self, hole_rect: BoundingRect) -> Result<(), String> {
-        let hole_origin = PointI32::new(hole_rect.left, hole_rect.top);
-        let filled_hole = self.complete_shape(hole_rect)?;
+    /// Find the candidate matchings of the endpoints by 'matcher_config' instead of by the default
+    /// MatcherConfig (see matcher::Matcher::find_all_possible_matchings()).
+    pub fn with_matcher_config(mut self, matcher_config: MatcherConfig) -> Self {
+        self.matcher_config = matcher_config;
+        self
+    }
 
-        self.debugger.draw_filled_hole(filled_hole, hole_origin);
+    /// Rank the matchings of the endpoints by 'match_cost' instead of by the default match cost of
+    /// the matcher algorithm (see matcher::MatcherAlgorithm::default_match_cost()).
+    pub fn with_match_cost(mut self, match_cost: Box<dyn MatchCost>) -> Self {
+        self.match_cost = Some(match_cost);
+        self
+    }
+
+    /// Simplify the walked path segments by the mode of 'simplify_config' within the simplify
+    /// tolerance, instead of by Ramer-Douglas-Peucker (see simplify::simplify_segment()).
+    pub fn with_simplify_config(mut self, simplify_config: SimplifyConfig) -> Self {
+        self.simplify_config = simplify_config;
+        self
+    }
+
+    /// Decide which regions bounded by the intrapolated curves are filled by 'fill_rule' instead of
+    /// by sampling the hole boundary between endpoints. FillRule::EvenOdd ignores the filler blank
+    /// boundary pixels tolerance (see filler::HoleFiller::fill_by_even_odd()).
+    pub fn with_fill_rule(mut self, fill_rule: FillRule) -> Self {
+        self.fill_rule = fill_rule;
+        self
+    }
+
+    /// Fill holes by reflecting the opposite side of the shape where the image has a dominant
+    /// reflective symmetry axis which covers the hole (see symmetry::detect_symmetry_axis() and
+    /// symmetry::fill_hole_by_reflection()), instead of intrapolating curves.
+    pub fn with_symmetry_config(mut self, symmetry_config: SymmetryConfig) -> Self {
+        self.symmetry_config = Some(symmetry_config);
+        self
+    }
+
+    /// Treat the image as line art: match and intrapolate the centerlines of the lines, walked along
+    /// the skeleton of the image from the hole boundary (see skeleton::find_branches_from_hole()),
+    /// instead of their contours, and draw the intrapolated centerlines at the line width.
+    pub fn with_centerline_config(mut self, centerline_config: CenterlineConfig) -> Self {
+        self.centerline_config = Some(centerline_config);
+        self
+    }
+
+    pub fn complete_shape_and_draw(&self, hole_rect: BoundingRect) -> Result<(), CompletionError> {
+        self.complete_shape_in_hole_and_draw(&Hole::from_rect(hole_rect))
+    }
+
+    pub fn complete_shape_in_hole_and_draw(&self, hole: &Hole) -> Result<(), CompletionError> {
+        let result = self.complete_shape_in_hole(hole)?;
+
+        self.debugger
+            .draw_filled_hole(result.filled_hole, hole.origin());
 
         Ok(())
     }
@@ -54,12 +248,11 @@ impl ShapeCompletor {
     pub fn complete_shape_and_draw_expandable(
         &self,
         hole_rect: BoundingRect,
-    ) -> Result<(), String> {
+    ) -> Result<(), CompletionError> {
         let hole_origin = PointI32::new(hole_rect.left, hole_rect.top);
         let filled_hole = match self.complete_shape(hole_rect) {
-            Ok(filled_hole) => filled_hole,
-            Err(mut error) => {
-                error += "\n";
+            Ok(result) => result.filled_hole,
+            Err(error) => {
                 let try_expand = || {
                     let (x, y, w, h) = (
                         hole_rect.left,
@@ -73,36 +266,29 @@ impl ShapeCompletor {
                         BoundingRect::new_x_y_w_h(x, y, w + 1, h),     // Expanded to the right
                         BoundingRect::new_x_y_w_h(x, y, w, h + 1),     // Expanded downward
                     ];
+                    let mut expansion_errors = vec![];
                     for (i, expanded_hole_rect) in
                         IntoIterator::into_iter(expanded_hole_rects).enumerate()
                     {
-                        if 0 <= hole_rect.left
-                            && hole_rect.right <= self.image.width as i32
-                            && 0 <= hole_rect.top
-                            && hole_rect.bottom <= self.image.height as i32
-                        {
-                            match self.complete_shape(expanded_hole_rect) {
-                                Ok(filled_hole) => {
-                                    return Ok(
-                                        // Remove the expanded column/row
-                                        match i {
-                                            0 => filled_hole.new_without_column(0),
-                                            1 => filled_hole.new_without_row(0),
-                                            2 => filled_hole.new_without_column(w as usize - 1),
-                                            3 => filled_hole.new_without_row(h as usize - 1),
-                                            _ => panic!("Impossible."),
-                                        },
-                                    );
-                                }
-                                Err(expanded_error) => {
-                                    error += &(expanded_error + "\n");
-                                }
+                        match self.complete_shape(expanded_hole_rect) {
+                            Ok(CompletionResult { filled_hole, .. }) => {
+                                return Ok(
+                                    // Remove the expanded column/row
+                                    match i {
+                                        0 => filled_hole.new_without_column(0),
+                                        1 => filled_hole.new_without_row(0),
+                                        2 => filled_hole.new_without_column(w as usize),
+                                        _ => filled_hole.new_without_row(h as usize),
+                                    },
+                                );
                             }
-                        } else {
-                            error += "Expansion out of range.\n";
+                            Err(expansion_error) => expansion_errors.push(expansion_error),
                         }
                     }
-                    Err(error)
+                    Err(CompletionError::ExpansionExhausted {
+                        cause: Box::new(error),
+                        expansion_errors,
+                    })
                 };
                 try_expand()?
             }
@@ -113,63 +299,800 @@ impl ShapeCompletor {
         Ok(())
     }
 
-    pub fn complete_shape(&self, hole_rect: BoundingRect) -> Result<FilledHoleMatrix, String> {
+    pub fn complete_shape(
+        &self,
+        hole_rect: BoundingRect,
+    ) -> Result<CompletionResult, CompletionError> {
+        self.complete_shape_in_hole(&Hole::from_rect(hole_rect))
+    }
+
+    /// Complete the shape in an arbitrarily shaped 'hole'.
+    /// The filled hole matrix covers the bounding rect of 'hole', where pixels not
+    /// belonging to 'hole' are FilledHoleElement::Outside.
+    /// Foreground pixels inside 'hole' survive the damage (see fragment::Fragment). They are kept as
+    /// they are, and the intrapolated curves either join their ends or pass around them.
+    pub fn complete_shape_in_hole(&self, hole: &Hole) -> Result<CompletionResult, CompletionError> {
+        self.validate_configs()?;
+        if !self.contains_hole(hole) {
+            return Err(CompletionError::HoleOutOfBounds);
+        }
+
+        //# Path walking
+        let paths = self.get_test_paths(&[hole]);
+
+        self.complete_shape_in_hole_among_holes(hole, &[], &paths)
+    }
+
+    /// Return the completions of 'hole' from the first 'k' successfully intrapolated matchings,
+    /// in ascending order of their scores, for a user to choose from.
+    /// If the hole can be filled by reflection (see ShapeCompletor::with_symmetry_config()), that
+    /// completion is also a hypothesis, and takes up one of the 'k'. It is scored like the
+    /// intrapolated curves, on the contours reflected into the hole.
+    /// The confidences are the softmax of the negated total scores, at a temperature of their
+    /// standard deviation.
+    /// If there is no endpoint, the only hypothesis is the blank hole with an empty matching.
+    /// Endpoints on the boundary of (or inside) any of 'other_holes' are discarded as in
+    /// ShapeCompletor::complete_shapes(), where 'other_holes' are expected not to overlap or touch
+    /// 'hole' (see Hole::merge_touching()).
+    /// Foreground pixels inside 'hole' are kept as in ShapeCompletor::complete_shape_in_hole().
+    pub fn complete_shape_hypotheses(
+        &self,
+        hole: &Hole,
+        other_holes: &[Hole],
+        k: usize,
+    ) -> Result<Vec<CompletionHypothesis>, CompletionError> {
+        self.validate_configs()?;
+        if !self.contains_hole(hole) {
+            return Err(CompletionError::HoleOutOfBounds);
+        }
+
+        //# Path walking
+        let mut holes = vec![hole];
+        holes.extend(other_holes.iter());
+        let paths = self.get_test_paths(&holes);
+
+        self.complete_shape_hypotheses_among_holes(hole, &holes[1..], &paths, k)
+    }
+
+    /// Complete the shapes in all 'holes' jointly.
+    /// Holes that overlap or touch each other are merged first. Path walking is done once for
+    /// all holes, and endpoints of a hole that lie on the boundary of (or inside) another hole
+    /// are discarded.
+    /// Foreground pixels inside 'holes' are kept as in ShapeCompletor::complete_shape_in_hole().
+    /// An error is returned if the configuration is invalid. The errors of the individual holes
+    /// are in the results instead.
+    pub fn complete_shapes(&self, holes: &[Hole]) -> Result<MultiHoleCompletion, CompletionError> {
+        self.validate_configs()?;
+        let holes = Hole::merge_touching(holes);
+
         //# Path walking
-        let paths = self.get_test_paths();
+        let hole_refs: Vec<&Hole> = holes.iter().collect();
+        let paths = self.get_test_paths(&hole_refs);
 
-        //# Path identification, segmentation, and simplification
-        let path_segments = self.find_simplified_segments_from_paths(&hole_rect, paths);
+        let results: Vec<Result<CompletionResult, CompletionError>> = holes
+            .iter()
+            .enumerate()
+            .map(|(i, hole)| {
+                let other_holes: Vec<&Hole> = holes
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(j, other_hole)| if i != j { Some(other_hole) } else { None })
+                    .collect();
+                if !self.contains_hole(hole) {
+                    return Err(CompletionError::HoleOutOfBounds);
+                }
+                self.complete_shape_in_hole_among_holes(hole, &other_holes, &paths)
+            })
+            .collect();
+
+        let mut image = self.image.clone();
+        holes.iter().zip(results.iter()).for_each(|(hole, result)| {
+            if let Ok(result) = result {
+                Self::composite_filled_hole(&mut image, &result.filled_hole, hole.origin());
+            }
+        });
+
+        Ok(MultiHoleCompletion {
+            holes,
+            results,
+            image,
+        })
+    }
+}
+
+// Helper functions
+impl ShapeCompletor {
+    fn complete_shape_in_hole_among_holes(
+        &self,
+        hole: &Hole,
+        other_holes: &[&Hole],
+        paths: &[PathI32],
+    ) -> Result<CompletionResult, CompletionError> {
+        //# Reflective symmetry, path identification, segmentation, and simplification
+        let (reflection, walked_segments) =
+            self.fill_hole_by_reflection_and_find_match_segments(hole, other_holes, paths)?;
+        let path_segments = walked_segments.path_segments();
+
+        if let Some((symmetry_axis, mut filled_hole)) = reflection {
+            filled_hole.keep_fragments(hole.origin(), &walked_segments.fragments);
+            return Ok(CompletionResult {
+                hole: hole.clone(),
+                path_segments,
+                match_item_set: MatchItemSet::new(),
+                matchings: vec![],
+                matching: None,
+                matching_scores: vec![],
+                tail_tangents_corrected: false,
+                intrapolated_curves: vec![],
+                filled_hole,
+                symmetry_axis: Some(symmetry_axis),
+            });
+        }
 
         if path_segments.is_empty() {
-            return Ok(FilledHoleMatrix::new(
-                hole_rect.width() as usize,
-                hole_rect.height() as usize,
-            ));
+            let mut filled_hole = FilledHoleMatrix::new_for_hole(hole);
+            filled_hole.keep_fragments(hole.origin(), &walked_segments.fragments);
+            return Ok(CompletionResult {
+                hole: hole.clone(),
+                path_segments,
+                match_item_set: MatchItemSet::new(),
+                matchings: vec![],
+                matching: None,
+                matching_scores: vec![],
+                tail_tangents_corrected: false,
+                intrapolated_curves: vec![],
+                filled_hole,
+                symmetry_axis: None,
+            });
         }
 
-        //# Matching paths
-        let match_item_set = self.construct_match_item_set(&path_segments)?;
-        let matchings = Matcher::find_all_possible_matchings(match_item_set)?;
+        //# Matching paths and intrapolation
+        let IntrapolatedMatchings {
+            match_item_set,
+            matchings,
+            tail_tangents_corrected,
+            candidates,
+        } = self.intrapolate_candidate_matchings(
+            hole,
+            &walked_segments,
+            paths,
+            self.matcher_config.num_evaluated_matchings,
+        )?;
+        let matching_scores: Vec<MatchingScore> =
+            candidates.iter().map(|(_, score)| *score).collect();
+        let (intrapolated_curves, score) = self.choose_best_candidate(candidates)?;
+        let matching_index = score.matching_index;
 
-        let intrapolated_curves = {
-            let try_intrapolation = |correct_tail_tangents| {
-                self.try_intrapolate_with_matchings(
-                    hole_rect,
-                    &matchings,
-                    &path_segments,
-                    correct_tail_tangents,
+        let filled_hole = self.fill_hole_with_curves(
+            hole,
+            &path_segments,
+            &walked_segments,
+            &matchings[matching_index],
+            &intrapolated_curves,
+        )?;
+
+        let matching = Some(matchings[matching_index].clone());
+        Ok(CompletionResult {
+            hole: hole.clone(),
+            path_segments,
+            match_item_set,
+            matchings,
+            matching,
+            matching_scores,
+            tail_tangents_corrected,
+            intrapolated_curves,
+            filled_hole,
+            symmetry_axis: None,
+        })
+    }
+
+    fn complete_shape_hypotheses_among_holes(
+        &self,
+        hole: &Hole,
+        other_holes: &[&Hole],
+        paths: &[PathI32],
+        k: usize,
+    ) -> Result<Vec<CompletionHypothesis>, CompletionError> {
+        let k = std::cmp::max(k, 1);
+
+        //# Reflective symmetry, path identification, segmentation, and simplification
+        let (reflection, walked_segments) =
+            self.fill_hole_by_reflection_and_find_match_segments(hole, other_holes, paths)?;
+        let path_segments = walked_segments.path_segments();
+
+        let mut hypotheses = vec![];
+        if let Some((symmetry_axis, mut filled_hole)) = reflection {
+            filled_hole.keep_fragments(hole.origin(), &walked_segments.fragments);
+            let score = self.score_reflection(hole, paths, &symmetry_axis, &path_segments);
+            hypotheses.push(CompletionHypothesis {
+                matching: Matching::new(),
+                intrapolated_curves: vec![],
+                filled_hole,
+                score,
+                confidence: 1.0,
+                symmetry_axis: Some(symmetry_axis),
+            });
+        }
+
+        if path_segments.is_empty() {
+            if hypotheses.is_empty() {
+                let mut filled_hole = FilledHoleMatrix::new_for_hole(hole);
+                filled_hole.keep_fragments(hole.origin(), &walked_segments.fragments);
+                hypotheses.push(CompletionHypothesis {
+                    matching: Matching::new(),
+                    intrapolated_curves: vec![],
+                    filled_hole,
+                    score: MatchingScore::default(),
+                    confidence: 1.0,
+                    symmetry_axis: None,
+                });
+            }
+            return Ok(hypotheses);
+        }
+
+        //# Matching paths and intrapolation
+        if hypotheses.len() < k {
+            match self.intrapolate_candidate_matchings(
+                hole,
+                &walked_segments,
+                paths,
+                k - hypotheses.len(),
+            ) {
+                Ok(IntrapolatedMatchings {
+                    matchings,
+                    candidates,
+                    ..
+                }) => {
+                    for (intrapolated_curves, score) in candidates.into_iter() {
+                        let matching = matchings[score.matching_index].clone();
+                        let filled_hole = self.fill_hole_with_curves(
+                            hole,
+                            &path_segments,
+                            &walked_segments,
+                            &matching,
+                            &intrapolated_curves,
+                        )?;
+                        hypotheses.push(CompletionHypothesis {
+                            matching,
+                            intrapolated_curves,
+                            filled_hole,
+                            score,
+                            confidence: 0.0,
+                            symmetry_axis: None,
+                        });
+                    }
+                }
+                // The hole is still completed by reflection
+                Err(_) if !hypotheses.is_empty() => {}
+                Err(error) => return Err(error),
+            }
+        }
+
+        //# Ranking
+        hypotheses.sort_by(|hypothesis1, hypothesis2| {
+            let (total1, total2) = (hypothesis1.score.total, hypothesis2.score.total);
+            total1
+                .partial_cmp(&total2)
+                .unwrap_or_else(|| total1.is_nan().cmp(&total2.is_nan()))
+        });
+        let totals: Vec<f64> = hypotheses.iter().map(|h| h.score.total).collect();
+        hypotheses
+            .iter_mut()
+            .zip(Self::calculate_confidences(&totals))
+            .for_each(|(hypothesis, confidence)| hypothesis.confidence = confidence);
+        Ok(hypotheses)
+    }
+
+    /// The softmax of the negated 'totals' (scores), at a temperature of their standard deviation,
+    /// so that the confidences depend on how the totals compare to each other rather than on
+    /// their absolute scale. The confidences are equal if the totals are (or are not finite).
+    fn calculate_confidences(totals: &[f64]) -> Vec<f64> {
+        let finite_totals = || totals.iter().copied().filter(|total| total.is_finite());
+        let num_finite_totals = finite_totals().count() as f64;
+        let min_total = finite_totals().fold(f64::INFINITY, f64::min);
+        let mean = finite_totals().sum::<f64>() / num_finite_totals;
+        let temperature = (finite_totals()
+            .map(|total| (total - mean).powi(2))
+            .sum::<f64>()
+            / num_finite_totals)
+            .sqrt();
+        if temperature <= 0.0 || !temperature.is_finite() {
+            return vec![1.0 / totals.len() as f64; totals.len()];
+        }
+        let weights: Vec<f64> = totals
+            .iter()
+            .map(|&total| {
+                if total.is_finite() {
+                    ((min_total - total) / temperature).exp()
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let sum_weights: f64 = weights.iter().sum();
+        weights.iter().map(|weight| weight / sum_weights).collect()
+    }
+
+    /// Score the fill of 'hole' by reflection across 'symmetry_axis' on the same scale as the
+    /// intrapolated curves (see MatchingScore::evaluate()). Its curves are the runs of the visible
+    /// contours in 'paths' whose reflections are in 'hole', reflected and simplified. Each end of
+    /// them continues the tail of the closest of 'path_segments' within REFLECTION_TAIL_TOLERANCE.
+    fn score_reflection(
+        &self,
+        hole: &Hole,
+        paths: &[PathI32],
+        symmetry_axis: &SymmetryAxis,
+        path_segments: &[PathI32],
+    ) -> MatchingScore {
+        let tails: Vec<(PointF64, PointF64)> = path_segments
+            .iter()
+            .filter(|segment| segment.len() >= 2)
+            .map(|segment| {
+                (
+                    segment[0].to_point_f64(),
+                    (segment[0] - segment[1]).to_point_f64().get_normalized(),
                 )
-            };
-            // First try intrapolation without correcting tail tangents
-            match try_intrapolation(false).or_else(|| try_intrapolation(true)) {
-                Some(curves) => curves,
-                None => return Err("Still not intrapolated.".into()),
+            })
+            .collect();
+        let tail_at = |end: PointI32| {
+            tails
+                .iter()
+                .map(|&tail| (tail.0.distance_to(end.to_point_f64()), tail))
+                .filter(|&(distance, _)| distance <= REFLECTION_TAIL_TOLERANCE)
+                .min_by(|(distance1, _), (distance2, _)| distance1.total_cmp(distance2))
+                .map(|(_, tail)| tail)
+        };
+        let reflect = |p: PointI32| {
+            let reflection = symmetry_axis.reflect(p.to_point_f64());
+            PointI32::new(reflection.x.round() as i32, reflection.y.round() as i32)
+        };
+
+        let mut runs: Vec<PathI32> = vec![];
+        for path in paths.iter() {
+            // The contour points are only where it turns, so walk it pixel by pixel
+            let path = path.to_open();
+            let path: Vec<PointI32> = (0..path.len())
+                .flat_map(|i| {
+                    let (from, to) = (path[i], path[(i + 1) % path.len()]);
+                    let num_steps = std::cmp::max((to.x - from.x).abs(), (to.y - from.y).abs());
+                    (0..std::cmp::max(num_steps, 1)).map(move |step| {
+                        let t = step as f64 / std::cmp::max(num_steps, 1) as f64;
+                        (from.to_point_f64() + (to - from).to_point_f64() * t).to_point_i32()
+                    })
+                })
+                .collect();
+            let len = path.len();
+            let is_in_run: Vec<bool> = path
+                .iter()
+                .map(|&p| {
+                    !hole.have_point_on_boundary_or_inside(p, 0)
+                        && hole.have_point_on_boundary_or_inside(reflect(p), 0)
+                })
+                .collect();
+            // The contours are closed, so start walking outside any run
+            let start = is_in_run.iter().position(|&in_run| !in_run).unwrap_or(0);
+            let mut run = PathI32::new();
+            for i in 0..len {
+                let index = (start + i) % len;
+                if is_in_run[index] {
+                    run.add(reflect(path[index]));
+                } else if !run.is_empty() {
+                    runs.push(std::mem::replace(&mut run, PathI32::new()));
+                }
             }
+            runs.push(run);
+        }
+
+        let (curves, curve_tails): (Vec<CompoundPath>, Vec<Vec<(PointF64, PointF64)>>) = runs
+            .iter()
+            .filter(|run| run.len() >= 2)
+            .map(|run| {
+                let mut curve = CompoundPath::new();
+                curve.add_path_f64(self.simplify_segment(run).to_path_f64());
+                let ends = [run[0], run[run.len() - 1]];
+                (curve, ends.iter().filter_map(|&end| tail_at(end)).collect())
+            })
+            .unzip();
+        MatchingScore::evaluate(0, &curves, &curve_tails, &self.matcher_config.score_weights)
+    }
+
+    /// Find the candidate matchings of the endpoints in 'walked_segments', and intrapolate them in
+    /// order until 'max_num_successes' of them are successful, correcting tail tangents if no
+    /// matching is successful otherwise.
+    /// See ShapeCompletor::intrapolate_matchings() for the errors returned.
+    fn intrapolate_candidate_matchings(
+        &self,
+        hole: &Hole,
+        walked_segments: &WalkedSegments,
+        paths: &[PathI32],
+        max_num_successes: usize,
+    ) -> Result<IntrapolatedMatchings, CompletionError> {
+        let path_segments = walked_segments.path_segments();
+        let (match_item_set, matchings) =
+            self.find_matchings(hole, &walked_segments.match_segments, &path_segments)?;
+
+        let intrapolate = |correct_tail_tangents| {
+            self.intrapolate_matchings(
+                hole,
+                &matchings,
+                walked_segments,
+                paths,
+                correct_tail_tangents,
+                max_num_successes,
+            )
         };
+        // First try intrapolation without correcting tail tangents
+        let (tail_tangents_corrected, candidates) = intrapolate(false)
+            .map(|candidates| (false, candidates))
+            .or_else(|_| intrapolate(true).map(|candidates| (true, candidates)))?;
 
-        let endpoints: Vec<PointI32> = path_segments
-            .into_iter()
-            .map(|segment| segment[0])
+        Ok(IntrapolatedMatchings {
+            match_item_set,
+            matchings,
+            tail_tangents_corrected,
+            candidates,
+        })
+    }
+
+    /// Fill 'hole' by reflection (see try_fill_hole_by_reflection()) and find the segments to be
+    /// matched (see find_match_segments()). Reflection is tried first, as it does not depend on the
+    /// segments, so that an error finding them is only returned if 'hole' cannot be filled by
+    /// reflection. Otherwise, no segments (nor fragments) are found in that case.
+    fn fill_hole_by_reflection_and_find_match_segments(
+        &self,
+        hole: &Hole,
+        other_holes: &[&Hole],
+        paths: &[PathI32],
+    ) -> Result<(Option<(SymmetryAxis, FilledHoleMatrix)>, WalkedSegments), CompletionError> {
+        let reflection = self.try_fill_hole_by_reflection(hole, other_holes, paths);
+        match self.find_match_segments(hole, other_holes, paths) {
+            Ok(walked_segments) => Ok((reflection, walked_segments)),
+            Err(_) if reflection.is_some() => Ok((reflection, WalkedSegments::default())),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Detect the symmetry axis of 'paths' and fill 'hole' by reflection across it, if symmetry is
+    /// enabled (see ShapeCompletor::with_symmetry_config()).
+    fn try_fill_hole_by_reflection(
+        &self,
+        hole: &Hole,
+        other_holes: &[&Hole],
+        paths: &[PathI32],
+    ) -> Option<(SymmetryAxis, FilledHoleMatrix)> {
+        let symmetry_config = self.symmetry_config.as_ref()?;
+        let mut holes = vec![hole];
+        holes.extend_from_slice(other_holes);
+        let symmetry_axis = detect_symmetry_axis(
+            paths,
+            &holes,
+            (self.image.width, self.image.height),
+            symmetry_config,
+        )?;
+        let filled_hole = fill_hole_by_reflection(
+            &self.image,
+            hole,
+            other_holes,
+            &symmetry_axis,
+            symmetry_config.min_support,
+        )?;
+        Some((symmetry_axis, filled_hole))
+    }
+
+    /// Intrapolate the edges of strokes crossing the hole together, if enabled (see
+    /// CurveIntrapolatorConfig::complete_strokes). 2 pairs in 'matching' are the edges of the same
+    /// stroke if their endpoints on each side of the hole are close and point in similar directions.
+    /// The edge intrapolated for each pair in 'matching' (same order) is returned, which is None if
+    /// the pair is not an edge of a stroke or the stroke cannot be intrapolated.
+    fn intrapolate_strokes(
+        &self,
+        curve_intrapolator: &CurveIntrapolator,
+        matching: &Matching,
+        path_segments: &[PathI32],
+        correct_tail_tangents: bool,
+    ) -> Vec<Option<CompoundPath>> {
+        let pairs = &matching.index_pairs;
+        let mut edges: Vec<Option<CompoundPath>> = pairs.iter().map(|_| None).collect();
+        let config = &self.curve_intrapolator_config;
+        if !config.complete_strokes {
+            return edges;
+        }
+
+        let is_stroke_end = |index1: usize, index2: usize| {
+            let (segment1, segment2) = (&path_segments[index1], &path_segments[index2]);
+            if segment1.len() < 2 || segment2.len() < 2 {
+                return false;
+            }
+            let direction =
+                |segment: &PathI32| (segment[0] - segment[1]).to_point_f64().get_normalized();
+            let distance = segment1[0]
+                .to_point_f64()
+                .distance_to(segment2[0].to_point_f64());
+            distance <= config.max_stroke_width
+                && direction(segment1).dot(direction(segment2))
+                    >= config.max_stroke_edge_angle.cos()
+        };
+
+        for i in 0..pairs.len() {
+            for j in (i + 1)..pairs.len() {
+                if edges[i].is_some() || edges[j].is_some() {
+                    continue;
+                }
+                let (a, b) = pairs[i];
+                let (c, d) = if is_stroke_end(pairs[i].0, pairs[j].0) {
+                    pairs[j]
+                } else {
+                    (pairs[j].1, pairs[j].0)
+                };
+                if !is_stroke_end(a, c) || !is_stroke_end(b, d) {
+                    continue;
+                }
+                let to_path_f64 = |index: usize| path_segments[index].to_path_f64();
+                if let Some((edge1, edge2)) = curve_intrapolator.intrapolate_stroke_between_curves(
+                    (&to_path_f64(a), &to_path_f64(c)),
+                    (&to_path_f64(b), &to_path_f64(d)),
+                    correct_tail_tangents,
+                ) {
+                    edges[i] = Some(edge1);
+                    edges[j] = Some(edge2);
+                }
+            }
+        }
+        edges
+    }
+
+    /// Whether any 2 of 'intrapolated_curves', whose first 'num_pairs' are intrapolated for pairs,
+    /// intersect each other where it is not allowed. If crossings are allowed (see
+    /// MatcherConfig::allow_crossings), the curves of 2 pairs may cross each other once at an angle
+    /// of at least 'min_crossing_angle'. The curves flagged in 'is_junction' (same order) may end
+    /// on another curve.
+    /// The behavior is undefined unless is_junction.len() == intrapolated_curves.len().
+    fn have_invalid_intersections(
+        &self,
+        intrapolated_curves: &[CompoundPath],
+        num_pairs: usize,
+        is_junction: &[bool],
+    ) -> bool {
+        let config = &self.matcher_config;
+        if !config.allow_crossings && !is_junction.contains(&true) {
+            return bezier_curves_intersection(intrapolated_curves);
+        }
+
+        let polylines: Vec<Vec<PointF64>> = intrapolated_curves
+            .iter()
+            .map(|curve| flatten_compound_path(curve, NUM_CROSSING_SAMPLES_PER_BEZIER))
+            .collect();
+        // A junction touches the curve it ends on at its last point
+        let is_junction_end = |index: usize, point: PointF64| {
+            is_junction[index]
+                && polylines[index]
+                    .last()
+                    .is_some_and(|&end| end.distance_to(point) < 1.0)
+        };
+        (0..polylines.len()).any(|i| {
+            ((i + 1)..polylines.len()).any(|j| {
+                // Enough to tell the ends of 2 junctions from 2 crossings
+                let crossings: Vec<(PointF64, f64)> =
+                    polylines_crossings(&polylines[i], &polylines[j], 4)
+                        .into_iter()
+                        .filter(|&(point, _)| {
+                            !is_junction_end(i, point) && !is_junction_end(j, point)
+                        })
+                        .collect();
+                match crossings[..] {
+                    [] => false,
+                    [(_, angle)] => {
+                        !config.allow_crossings
+                            || i >= num_pairs
+                            || j >= num_pairs
+                            || angle < config.min_crossing_angle
+                    }
+                    _ => true,
+                }
+            })
+        })
+    }
+
+    /// Whether any of 'intrapolated_curves' runs through 'fragment_pixels', except within
+    /// FRAGMENT_JOIN_CLEARANCE from its ends. A point of a curve is inside the fragments if all the
+    /// pixels around it are.
+    fn have_curves_through_fragments(
+        intrapolated_curves: &[CompoundPath],
+        fragment_pixels: &HashSet<PointI32>,
+    ) -> bool {
+        if fragment_pixels.is_empty() {
+            return false;
+        }
+        let is_inside = |p: PointF64| {
+            [(-0.25, -0.25), (0.25, -0.25), (-0.25, 0.25), (0.25, 0.25)]
+                .iter()
+                .all(|&(dx, dy)| {
+                    let pixel = PointI32::new((p.x + dx).floor() as i32, (p.y + dy).floor() as i32);
+                    fragment_pixels.contains(&pixel)
+                })
+        };
+        intrapolated_curves.iter().any(|curve| {
+            let polyline = flatten_compound_path(curve, NUM_CROSSING_SAMPLES_PER_BEZIER);
+            let (first, last) = match (polyline.first(), polyline.last()) {
+                (Some(&first), Some(&last)) => (first, last),
+                _ => return false,
+            };
+            polyline.windows(2).any(|edge| {
+                let num_steps = (edge[0].distance_to(edge[1]) / 0.25).ceil().max(1.0) as usize;
+                (0..=num_steps).any(|step| {
+                    let p = edge[0] + (edge[1] - edge[0]) * (step as f64 / num_steps as f64);
+                    p.distance_to(first) > FRAGMENT_JOIN_CLEARANCE
+                        && p.distance_to(last) > FRAGMENT_JOIN_CLEARANCE
+                        && is_inside(p)
+                })
+            })
+        })
+    }
+
+    /// Whether any of 'intrapolated_curves' crosses any of 'contours' (each with its bounds), except
+    /// within CONTOUR_JOIN_CLEARANCE from its ends.
+    fn have_curves_crossing_contours(
+        intrapolated_curves: &[CompoundPath],
+        contours: &[(Bounds, Vec<PointF64>)],
+    ) -> bool {
+        intrapolated_curves.iter().any(|curve| {
+            let polyline = flatten_compound_path(curve, NUM_CROSSING_SAMPLES_PER_BEZIER);
+            let (first, last) = match (polyline.first(), polyline.last()) {
+                (Some(&first), Some(&last)) => (first, last),
+                _ => return false,
+            };
+            let (left, top, right, bottom) = bounds_of_points(&polyline);
+            contours
+                .iter()
+                .filter(
+                    |((contour_left, contour_top, contour_right, contour_bottom), _)| {
+                        left <= *contour_right
+                            && *contour_left <= right
+                            && top <= *contour_bottom
+                            && *contour_top <= bottom
+                    },
+                )
+                .any(|(_, contour)| {
+                    polylines_crossings(&polyline, contour, usize::MAX)
+                        .into_iter()
+                        .any(|(point, _)| {
+                            point.distance_to(first) > CONTOUR_JOIN_CLEARANCE
+                                && point.distance_to(last) > CONTOUR_JOIN_CLEARANCE
+                        })
+                })
+        })
+    }
+
+    /// Construct the match items of 'path_segments' and find the candidate matchings of them.
+    fn find_matchings(
+        &self,
+        hole: &Hole,
+        match_segments: &[MatchSegment],
+        path_segments: &[PathI32],
+    ) -> Result<(MatchItemSet, Vec<Matching>), CompletionError> {
+        let match_item_set = self.construct_match_item_set(path_segments)?;
+        let match_item_set = if self.matcher_config.algorithm == MatcherAlgorithm::NonCrossing {
+            Self::sort_match_items_along_boundary(hole, match_item_set)
+        } else {
+            match_item_set
+        };
+        let default_match_cost = self.matcher_config.algorithm.default_match_cost();
+        let match_cost = self
+            .match_cost
+            .as_deref()
+            .unwrap_or(default_match_cost.as_ref());
+        let matchings = Matcher::find_all_possible_matchings(
+            match_item_set.clone(),
+            match_segments,
+            match_cost,
+            &self.matcher_config,
+        )?;
+        Ok((match_item_set, matchings))
+    }
+
+    /// Fill 'hole' bounded by 'intrapolated_curves', which are intrapolated for 'matching', and by
+    /// the fragments in 'walked_segments'.
+    /// In centerline mode, 'intrapolated_curves' are centerlines drawn at the line width instead.
+    fn fill_hole_with_curves(
+        &self,
+        hole: &Hole,
+        path_segments: &[PathI32],
+        walked_segments: &WalkedSegments,
+        matching: &Matching,
+        intrapolated_curves: &[CompoundPath],
+    ) -> Result<FilledHoleMatrix, CompletionError> {
+        let fragments = &walked_segments.fragments;
+        if let Some(line_width) = walked_segments.line_width {
+            let mut filled_hole = HoleFiller::fill_lines(hole, intrapolated_curves, line_width);
+            filled_hole.keep_fragments(hole.origin(), fragments);
+            return Ok(filled_hole);
+        }
+        if self.fill_rule == FillRule::EvenOdd {
+            // The curves of the pairs come before the stubs of the unmatched endpoints
+            let (contour_curves, stubs) = intrapolated_curves.split_at(matching.index_pairs.len());
+            return Ok(HoleFiller::fill_by_even_odd(
+                &self.image,
+                hole,
+                contour_curves,
+                stubs,
+                fragments,
+            ));
+        }
+
+        // Unmatched endpoints end inside the hole, so they do not bound any region to be filled.
+        // Neither do the ends of fragments, which are not on the hole boundary.
+        let num_boundary_segments = path_segments.len() - walked_segments.num_fragment_segments;
+        let endpoints: Vec<PointI32> = path_segments[..num_boundary_segments]
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !matching.unmatched.contains(i))
+            .map(|(_, segment)| segment[0])
             .collect();
 
         HoleFiller::fill(
             &self.image,
-            hole_rect,
+            hole,
             intrapolated_curves,
             endpoints,
+            fragments,
             self.filler_blank_boundary_pixels_tolerance,
         )
     }
-}
 
-// Helper functions
-impl ShapeCompletor {
-    fn get_test_paths(&self) -> Vec<PathI32> {
-        let clusters = self.image.to_clusters(false);
+    /// Return an error describing the first invalid configuration, if any.
+    fn validate_configs(&self) -> Result<(), CompletionError> {
+        self.curve_intrapolator_config.validate()?;
+        self.simplify_config.validate()
+    }
+
+    fn contains_hole(&self, hole: &Hole) -> bool {
+        let rect = hole.rect();
+        0 <= rect.left
+            && rect.right <= self.image.width as i32
+            && 0 <= rect.top
+            && rect.bottom <= self.image.height as i32
+    }
+
+    /// Paint the pixels of 'filled_hole' whose top-left corner is at 'origin' onto 'image'.
+    fn composite_filled_hole(
+        image: &mut BinaryImage,
+        filled_hole: &FilledHoleMatrix,
+        origin: PointI32,
+    ) {
+        for i in 0..filled_hole.height {
+            for j in 0..filled_hole.width {
+                let target = origin + PointI32::new(j as i32, i as i32);
+                match filled_hole[i][j] {
+                    FilledHoleElement::Blank => image.set_pixel_at_safe(target, false),
+                    FilledHoleElement::Structure | FilledHoleElement::Texture => {
+                        image.set_pixel_at_safe(target, true)
+                    }
+                    FilledHoleElement::Outside => {}
+                }
+            }
+        }
+    }
+
+    /// Walk the contours of the image with the pixels inside 'holes' cleared, so that the shapes
+    /// are cut off at the hole boundaries even where foreground survives inside the holes.
+    fn get_test_paths(&self, holes: &[&Hole]) -> Vec<PathI32> {
+        let mut image = self.image.clone();
+        for hole in holes.iter() {
+            let rect = hole.rect();
+            for y in rect.top..rect.bottom {
+                for x in rect.left..rect.right {
+                    let p = PointI32::new(x, y);
+                    if hole.have_pixel_inside(p) {
+                        image.set_pixel_at_safe(p, false);
+                    }
+                }
+            }
+        }
+        let clusters = image.to_clusters(false);
 
         clusters
             .into_iter()
-            .map(|cluster| {
+            .flat_map(|cluster| {
                 let origin = PointI32::new(cluster.rect.left, cluster.rect.top);
                 let mut paths = Cluster::image_to_paths(
                     &cluster.to_binary_image(),
@@ -178,43 +1101,172 @@ impl ShapeCompletor {
                 paths.iter_mut().for_each(|path| path.offset(&origin));
                 paths
             })
-            .flatten()
             .collect()
     }
 
-    fn find_simplified_segments_from_paths(
+    /// Find the segments to be matched, which are the skeleton branches from the boundary of 'hole'
+    /// if centerline mode is enabled (see ShapeCompletor::with_centerline_config()), together with
+    /// the line width to draw them at, or the contour segments of 'paths' otherwise.
+    /// In the latter case, the segments walked from the ends of the fragments inside 'hole' follow
+    /// those of 'paths', and the segments of 'paths' ending where a fragment is attached to the hole
+    /// boundary are dropped, as the shape continues into the fragment there.
+    fn find_match_segments(
         &self,
-        hole_rect: &BoundingRect,
-        paths: Vec<PathI32>,
-    ) -> Vec<PathI32> {
-        let mut endpoints = HashSet::new();
-        paths
+        hole: &Hole,
+        other_holes: &[&Hole],
+        paths: &[PathI32],
+    ) -> Result<WalkedSegments, CompletionError> {
+        let centerline_config = match self.centerline_config.as_ref() {
+            Some(centerline_config) => centerline_config,
+            None => {
+                let fragments = Fragment::find_in_hole(&self.image, hole);
+                let mut segments: Vec<(MatchSegment, PathI32)> = self
+                    .find_simplified_segments_from_paths(hole, other_holes, paths)?
+                    .into_iter()
+                    .filter(|(segment, _)| !Self::is_attached_to_fragment(segment, &fragments))
+                    .collect();
+                let num_path_segments = segments.len();
+                for (i, fragment) in fragments.iter().enumerate() {
+                    segments.extend(
+                        fragment
+                            .find_end_segments(hole)
+                            .into_iter()
+                            .map(|raw_points| {
+                                let segment = MatchSegment {
+                                    points: self.simplify_segment(&raw_points),
+                                    path_index: paths.len() + i,
+                                };
+                                (segment, raw_points)
+                            })
+                            .filter(|(segment, _)| segment.points.len() >= 2),
+                    );
+                }
+                let num_fragment_segments = segments.len() - num_path_segments;
+                let (match_segments, raw_segments) = segments.into_iter().unzip();
+                return Ok(WalkedSegments {
+                    match_segments,
+                    raw_segments,
+                    line_width: None,
+                    fragments,
+                    num_fragment_segments,
+                });
+            }
+        };
+
+        let mut holes = vec![hole];
+        holes.extend_from_slice(other_holes);
+        let skeleton = skeletonize(&self.image, &holes);
+        let branches = find_branches_from_hole(
+            &skeleton,
+            hole,
+            other_holes,
+            centerline_config.max_branch_length,
+        );
+        let (match_segments, raw_segments) = branches
             .into_iter()
-            .map(|path| {
-                self.find_segments_on_path_with_unique_endpoints(hole_rect, path, &mut endpoints)
+            .enumerate()
+            .map(|(path_index, branch)| {
+                let segment = MatchSegment {
+                    points: self.simplify_segment(&branch),
+                    path_index,
+                };
+                (segment, branch)
             })
-            .flatten()
-            .collect()
+            .filter(|(segment, _)| segment.points.len() >= 2)
+            .unzip();
+
+        let line_width = centerline_config.line_width.unwrap_or_else(|| {
+            let rect = hole.rect();
+            let margin = std::cmp::max(rect.width(), rect.height());
+            let window = BoundingRect::new_x_y_w_h(
+                rect.left - margin,
+                rect.top - margin,
+                rect.width() + 2 * margin,
+                rect.height() + 2 * margin,
+            );
+            estimate_line_width(&self.image, &skeleton, hole, window).unwrap_or(1.0)
+        });
+        Ok(WalkedSegments {
+            match_segments,
+            raw_segments,
+            line_width: Some(line_width),
+            fragments: Fragment::find_in_hole(&self.image, hole),
+            num_fragment_segments: 0,
+        })
+    }
+
+    /// Whether the head of 'segment' touches the contour of any of 'fragments'.
+    fn is_attached_to_fragment(segment: &MatchSegment, fragments: &[Fragment]) -> bool {
+        let head = segment.points[0].to_point_f64();
+        fragments.iter().any(|fragment| {
+            let contour = &fragment.contour.path;
+            contour.windows(2).any(|edge| {
+                distance_to_segment(head, edge[0].to_point_f64(), edge[1].to_point_f64()) <= 1.0
+            })
+        })
+    }
+
+    /// Return the simplified segments of 'paths' whose heads are endpoints on the boundary of 'hole',
+    /// each with its unsimplified points.
+    fn find_simplified_segments_from_paths(
+        &self,
+        hole: &Hole,
+        other_holes: &[&Hole],
+        paths: &[PathI32],
+    ) -> Result<Vec<(MatchSegment, PathI32)>, CompletionError> {
+        let mut endpoints = HashSet::new();
+        let mut match_segments = vec![];
+        for (path_index, path) in paths.iter().enumerate() {
+            match_segments.extend(
+                self.find_segments_on_path_with_unique_endpoints(
+                    hole,
+                    other_holes,
+                    path,
+                    &mut endpoints,
+                )?
+                .into_iter()
+                .map(|raw_points| {
+                    let points = self.simplify_segment(&raw_points);
+                    (MatchSegment { points, path_index }, raw_points)
+                }),
+            );
+        }
+        Ok(match_segments)
+    }
+
+    /// Simplify the walked 'segment' within the simplify tolerance (see ShapeCompletor::with_simplify_config()).
+    fn simplify_segment(&self, segment: &PathI32) -> PathI32 {
+        simplify_segment(segment, self.simplify_tolerance, &self.simplify_config)
     }
 
-    /// Return a vector of *simplified* path segments whose heads are endpoints, pointing outwards from hole_rect.
-    /// Segments are walked until 'max_num_points' is reached or another boundary point is reached, whichever happens first.
+    /// Return a vector of *unsimplified* path segments whose heads are endpoints, pointing outwards from hole.
+    /// Segments are walked until 'max_num_points' is reached or another boundary point (of any hole) is reached, whichever happens first.
+    /// Endpoints on the boundary of (or inside) any of 'other_holes' are discarded.
     fn find_segments_on_path_with_unique_endpoints(
         &self,
-        hole_rect: &BoundingRect,
-        path: PathI32,
+        hole: &Hole,
+        other_holes: &[&Hole],
+        path: &PathI32,
         current_endpoints: &mut HashSet<PointI32>,
-    ) -> Vec<PathI32> {
+    ) -> Result<Vec<PathI32>, CompletionError> {
         let path = path.to_open();
         let len = path.len();
-        let is_boundary_mask =
-            BitVec::from_fn(len, |i| hole_rect.have_point_on_boundary(path[i], 1));
+        if len == 0 {
+            return Ok(vec![]);
+        }
+        let is_boundary_mask = BitVec::from_fn(len, |i| hole.have_point_on_boundary(path[i], 1));
+        let is_other_boundary_mask = BitVec::from_fn(len, |i| {
+            other_holes
+                .iter()
+                .any(|other_hole| other_hole.have_point_on_boundary_or_inside(path[i], 0))
+        });
 
-        let endpoints_iter = (0..len).into_iter().filter(|&i| {
+        let endpoints_iter = (0..len).filter(|&i| {
             let prev = if i == 0 {len-1} else {i-1};
             let next = (i + 1) % len;
 
             is_boundary_mask[i] // itself is on boundary
+            && !is_other_boundary_mask[i] // not in another hole
             // If both neighbors are on boundary, it is a degenerate case (corner intersection) where there is no endpoints pair.
             && ((is_boundary_mask[prev] && !is_boundary_mask[next]) || (!is_boundary_mask[prev] && is_boundary_mask[next]))
 
@@ -224,10 +1276,12 @@ impl ShapeCompletor {
             .filter_map(|endpoint| {
                 let inserted = current_endpoints.insert(path[endpoint]);
                 if inserted {
-                    match self.walk_segment(&path, endpoint, &is_boundary_mask) {
-                        Ok(segment) => Some(segment),
-                        Err(error) => panic!("{}", error),
-                    }
+                    Some(self.walk_segment(
+                        &path,
+                        endpoint,
+                        &is_boundary_mask,
+                        &is_other_boundary_mask,
+                    ))
                 } else {
                     None
                 }
@@ -235,15 +1289,19 @@ impl ShapeCompletor {
             .collect()
     }
 
-    /// The behavior is undefined unless path.len() == is_boundary_mask.len().
+    /// Walking also stops at points where 'is_other_boundary_mask' is set.
+    /// The behavior is undefined unless path.len() == is_boundary_mask.len() == is_other_boundary_mask.len().
     fn walk_segment(
         &self,
         path: &PathI32,
         endpoint_index: usize,
         is_boundary_mask: &BitVec<u32>,
-    ) -> Result<PathI32, String> {
-        if path.len() != is_boundary_mask.len() {
-            return Err("Length of path must be equal to length of boundary mask.".into());
+        is_other_boundary_mask: &BitVec<u32>,
+    ) -> Result<PathI32, CompletionError> {
+        if path.len() != is_boundary_mask.len() || path.len() != is_other_boundary_mask.len() {
+            return Err(CompletionError::PathWalkingFailed(
+                "Length of path must be equal to length of boundary mask.",
+            ));
         }
 
         // Determine direction
@@ -256,7 +1314,9 @@ impl ShapeCompletor {
         let next = (endpoint_index + 1) % len;
         if is_boundary_mask[prev] == is_boundary_mask[next] {
             // Only one side can be boundary, not degenerate corner case
-            return Err("Only one neighbor can be boundary point.".into());
+            return Err(CompletionError::PathWalkingFailed(
+                "Only one neighbor can be boundary point.",
+            ));
         }
         let direction = if is_boundary_mask[prev] { 1 } else { -1 };
 
@@ -275,55 +1335,156 @@ impl ShapeCompletor {
                 len - 1
             };
 
-            if is_boundary_mask[endpoint as usize] {
+            if is_boundary_mask[endpoint as usize] || is_other_boundary_mask[endpoint as usize] {
                 path_segment.add(path[endpoint as usize]);
                 break;
             }
         }
 
-        // Simplify 'path_segment'
-        Ok(PathI32::from_points(visioncortex::reduce::reduce(
-            &path_segment.path,
-            self.simplify_tolerance,
-        )))
+        Ok(path_segment)
     }
 
-    /// The behavior is undefined unless 'path_segments' has an even number of elements.
-    /// The behavior is also undefined unless every segment has at least 2 points.
+    /// CompletionError::DegenerateSegment is returned if some segment has fewer than 2 distinct leading points.
     /// The behavior is also undefined unless all segments have their tails at index 0.
-    fn construct_match_item_set(&self, path_segments: &[PathI32]) -> Result<MatchItemSet, String> {
-        if path_segments.len() % 2 != 0 {
-            return Err("There must be an even number of path segments.".into());
-        }
-
-        let match_items_iter = path_segments.iter().map(|segment| {
-            assert!(segment.len() >= 2);
+    fn construct_match_item_set(
+        &self,
+        path_segments: &[PathI32],
+    ) -> Result<MatchItemSet, CompletionError> {
+        let mut match_item_set = MatchItemSet::new();
+        for segment in path_segments.iter() {
+            if segment.len() < 2 || segment[0] == segment[1] {
+                return Err(CompletionError::DegenerateSegment);
+            }
             // 0 is tail
             let direction = (segment[0] - segment[1]).to_point_f64().get_normalized();
-            MatchItem::new_with_default_id(segment[0].to_point_f64(), direction)
-        });
-        let mut match_item_set = MatchItemSet::new();
-        match_items_iter.for_each(|match_item| match_item_set.push_and_set_id(match_item));
+            match_item_set.push_and_set_id(MatchItem::new_with_default_id(
+                segment[0].to_point_f64(),
+                direction,
+            ));
+        }
         Ok(match_item_set)
     }
 
-    /// Return true iff one of the matchings is successfully intrapolated
-    fn try_intrapolate_with_matchings(
+    /// Reorder 'match_item_set' clockwise along the boundary of 'hole', keeping the ids of the items.
+    fn sort_match_items_along_boundary(hole: &Hole, match_item_set: MatchItemSet) -> MatchItemSet {
+        let points: Vec<PointI32> = match_item_set
+            .iter()
+            .map(|item| item.point.to_point_i32())
+            .collect();
+        let positions = hole.get_boundary_positions(&points);
+        let mut items_with_positions: Vec<(MatchItem, usize)> =
+            match_item_set.items.into_iter().zip(positions).collect();
+        items_with_positions.sort_by_key(|&(_, position)| position);
+
+        let mut sorted_match_item_set = MatchItemSet::new();
+        items_with_positions
+            .into_iter()
+            .for_each(|(item, _)| sorted_match_item_set.push_as_is(item));
+        sorted_match_item_set
+    }
+
+    /// Choose the candidate with the lowest total score among 'candidates', keeping the first one
+    /// among equally good ones.
+    /// CompletionError::NoValidMatching is returned if 'candidates' is empty.
+    fn choose_best_candidate(
         &self,
-        hole_rect: BoundingRect,
+        candidates: Vec<(Vec<CompoundPath>, MatchingScore)>,
+    ) -> Result<(Vec<CompoundPath>, MatchingScore), CompletionError> {
+        let mut best: Option<(Vec<CompoundPath>, MatchingScore)> = None;
+        for candidate in candidates.into_iter() {
+            if best
+                .as_ref()
+                .is_none_or(|(_, best_score)| candidate.1.total < best_score.total)
+            {
+                best = Some(candidate);
+            }
+        }
+        let (intrapolated_curves, score) = best.ok_or(CompletionError::NoValidMatching)?;
+
+        if self.debugger.should_draw_control_points() {
+            let color = Color::color(&ColorName::Black);
+            intrapolated_curves.iter().for_each(|curve| {
+                curve.iter().for_each(|part| {
+                    if let CompoundPathElement::Spline(part) = part {
+                        self.debugger
+                            .draw_cross_i32(&color, part.points[1].to_point_i32());
+                        self.debugger
+                            .draw_cross_i32(&color, part.points[2].to_point_i32());
+                    }
+                });
+            });
+        }
+
+        Ok((intrapolated_curves, score))
+    }
+
+    /// Intrapolate the matchings in order until 'max_num_successes' (at least 1) of them are successful,
+    /// and return the intrapolated curves and scores of the successful ones.
+    /// Matchings with curves running through the fragments in 'walked_segments', or crossing the
+    /// visible contours 'paths' (see ShapeCompletor::get_test_paths()), are rejected.
+    /// If no matching is successful, CompletionError::AllMatchingsIntersect is returned if every rejected
+    /// matching has intersecting curves, CompletionError::AllMatchingsCrossContours is returned if every
+    /// rejected matching has curves crossing 'paths', otherwise CompletionError::NoValidMatching is returned.
+    fn intrapolate_matchings(
+        &self,
+        hole: &Hole,
         matchings: &[Matching],
-        path_segments: &[PathI32],
-        correct_tail_tangents: bool, // Not a configuration, but a fail-safe feature
-    ) -> Option<Vec<CompoundPath>> {
-        let curve_intrapolator = CurveIntrapolator::new(
-            self.curve_intrapolator_config,
-            hole_rect,
-            self.debugger.as_ref(),
-        );
+        walked_segments: &WalkedSegments,
+        paths: &[PathI32],
+        correct_tail_tangents: bool,
+        max_num_successes: usize,
+    ) -> Result<Vec<(Vec<CompoundPath>, MatchingScore)>, CompletionError> {
+        let path_segments = &walked_segments.path_segments()[..];
+        let contours: Vec<(Bounds, Vec<PointF64>)> = paths
+            .iter()
+            .map(|path| {
+                let points = path.to_path_f64().path;
+                (bounds_of_points(&points), points)
+            })
+            .collect();
+        let curve_intrapolator =
+            CurveIntrapolator::new(self.curve_intrapolator_config, hole, self.debugger.as_ref())
+                .with_raw_segments(&walked_segments.raw_segments)
+                .with_pixel_centers(walked_segments.line_width.is_some());
+        let fragment_pixels: HashSet<PointI32> = walked_segments
+            .fragments
+            .iter()
+            .flat_map(|fragment| fragment.pixels.iter().copied())
+            .collect();
+        // The tail of each of 'path_segments' (same order), if it has a direction
+        let tails: Vec<Option<(PointF64, PointF64)>> = path_segments
+            .iter()
+            .map(|segment| {
+                if segment.len() < 2 {
+                    return None;
+                }
+                Some((
+                    segment[0].to_point_f64(),
+                    (segment[0] - segment[1]).to_point_f64().get_normalized(),
+                ))
+            })
+            .collect();
+        let tails_of = |indices: &[usize]| -> Vec<(PointF64, PointF64)> {
+            indices.iter().filter_map(|&index| tails[index]).collect()
+        };
+        let max_num_successes = std::cmp::max(max_num_successes, 1);
 
-        'matching_loop: for matching in matchings.iter() {
+        let mut candidates: Vec<(Vec<CompoundPath>, MatchingScore)> = vec![];
+
+        let mut rejections: Vec<Rejection> = vec![];
+        'matching_loop: for (matching_index, matching) in matchings.iter().enumerate() {
             let mut intrapolated_curves = vec![];
-            for &(index1, index2) in matching.iter() {
+            let mut stroke_edges = self.intrapolate_strokes(
+                &curve_intrapolator,
+                matching,
+                path_segments,
+                correct_tail_tangents,
+            );
+            for (pair_index, &(index1, index2)) in matching.iter().enumerate() {
+                if let Some(edge) = stroke_edges[pair_index].take() {
+                    intrapolated_curves.push(edge);
+                    continue;
+                }
                 let (curve1, curve2) = (
                     path_segments[index1].to_path_f64(),
                     path_segments[index2].to_path_f64(),
@@ -348,32 +1509,927 @@ impl ShapeCompletor {
                     intrapolated_curves.push(intrapolated_curve);
                 } else {
                     // A curve cannot be intrapolated, this matching is wrong
+                    rejections.push(Rejection::NotIntrapolated);
+                    continue 'matching_loop;
+                }
+            }
+            let num_pairs = intrapolated_curves.len();
+            let mut is_junction = vec![false; num_pairs];
+            for &index in matching.unmatched.iter() {
+                let segment = path_segments[index].to_path_f64();
+                let junction = if self.matcher_config.allow_t_junctions {
+                    curve_intrapolator.extend_curve_to_junction(
+                        segment.clone(),
+                        false,
+                        &intrapolated_curves[..num_pairs],
+                    )
+                } else {
+                    None
+                };
+                is_junction.push(junction.is_some());
+                if let Some(curve) =
+                    junction.or_else(|| curve_intrapolator.extend_curve_as_stub(segment, false))
+                {
+                    intrapolated_curves.push(curve);
+                } else {
+                    rejections.push(Rejection::NotIntrapolated);
                     continue 'matching_loop;
                 }
             }
             // Check if any curves intersect with each other
-            if bezier_curves_intersection(&intrapolated_curves) {
+            if self.have_invalid_intersections(&intrapolated_curves, num_pairs, &is_junction) {
+                rejections.push(Rejection::CurvesIntersect);
                 continue 'matching_loop;
             }
+            if Self::have_curves_through_fragments(&intrapolated_curves, &fragment_pixels) {
+                rejections.push(Rejection::CurvesThroughFragments);
+                continue 'matching_loop;
+            }
+            if Self::have_curves_crossing_contours(&intrapolated_curves, &contours) {
+                rejections.push(Rejection::CurvesCrossContours);
+                continue 'matching_loop;
+            }
+
+            let curve_tails: Vec<Vec<(PointF64, PointF64)>> = matching
+                .iter()
+                .map(|&(index1, index2)| tails_of(&[index1, index2]))
+                .chain(matching.unmatched.iter().map(|&index| tails_of(&[index])))
+                .collect();
+            let score = MatchingScore::evaluate(
+                matching_index,
+                &intrapolated_curves,
+                &curve_tails,
+                &self.matcher_config.score_weights,
+            );
+            candidates.push((intrapolated_curves, score));
+            if candidates.len() >= max_num_successes {
+                break;
+            }
+        }
+
+        if !candidates.is_empty() {
+            return Ok(candidates);
+        }
+
+        let all_rejected_for =
+            |reason| !rejections.is_empty() && rejections.iter().all(|&r| r == reason);
+        if all_rejected_for(Rejection::CurvesIntersect) {
+            Err(CompletionError::AllMatchingsIntersect)
+        } else if all_rejected_for(Rejection::CurvesCrossContours) {
+            Err(CompletionError::AllMatchingsCrossContours)
+        } else {
+            Err(CompletionError::NoValidMatching)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        curve::{CurveModel, TailTangentEstimator},
+        match_cost::{DefaultMatchCost, FacingMatchCost, GestaltMatchCost},
+        simplify::SimplifyMode,
+    };
+
+    /// A ShapeCompletor with the default configuration except for the given ones, to be
+    /// customized further by the builders.
+    fn make_shape_completor(
+        image: BinaryImage,
+        simplify_tolerance: f64,
+        curve_intrapolator_config: CurveIntrapolatorConfig,
+    ) -> ShapeCompletor {
+        ShapeCompletor::new(
+            image,
+            simplify_tolerance,
+            curve_intrapolator_config,
+            3,
+            None,
+        )
+    }
+
+    /// A horizontal bar with a rect hole cut across it.
+    fn bar_image_with_hole(hole_rect: BoundingRect) -> BinaryImage {
+        let mut image = BinaryImage::new_w_h(60, 40);
+        for y in 15..25 {
+            for x in 5..55 {
+                image.set_pixel(x, y, true);
+            }
+        }
+        for y in hole_rect.top..hole_rect.bottom {
+            for x in hole_rect.left..hole_rect.right {
+                image.set_pixel(x as usize, y as usize, false);
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn completion_result_exposes_pipeline_stages() {
+        // GIVEN
+        let hole_rect = BoundingRect::new_x_y_w_h(20, 10, 15, 20);
+        let image = bar_image_with_hole(hole_rect);
+        let shape_completor = make_shape_completor(image, 2.0, CurveIntrapolatorConfig::default());
+
+        // WHEN
+        let result = shape_completor.complete_shape(hole_rect).unwrap();
+
+        // THEN
+        assert_eq!(result.path_segments.len(), 4);
+        assert_eq!(result.match_item_set.len(), 4);
+        let matching = result.matching.unwrap();
+        assert_eq!(matching.index_pairs.len(), 2);
+        assert_eq!(result.intrapolated_curves.len(), 2);
+        assert_eq!(result.filled_hole.width, 15);
+        assert_eq!(result.filled_hole.height, 20);
+        // The middle of the bar is filled
+        assert_eq!(result.filled_hole[10][7], FilledHoleElement::Texture);
+    }
+
+    #[test]
+    fn matching_with_lowest_score_is_chosen() {
+        // GIVEN a bar of width 10 crossing a hole of width 15, whose edges are matched by plain
+        // distance, so that the U-turning matching is ranked before the straight one
+        let hole_rect = BoundingRect::new_x_y_w_h(20, 10, 15, 20);
+        let image = bar_image_with_hole(hole_rect);
+        let new_shape_completor = |num_evaluated_matchings| {
+            make_shape_completor(image.clone(), 2.0, CurveIntrapolatorConfig::default())
+                .with_matcher_config(MatcherConfig {
+                    algorithm: MatcherAlgorithm::Ranked,
+                    num_evaluated_matchings,
+                    ..Default::default()
+                })
+                .with_match_cost(Box::new(DefaultMatchCost))
+        };
+
+        // WHEN
+        let result = new_shape_completor(16).complete_shape(hole_rect).unwrap();
+        let first_result = new_shape_completor(1).complete_shape(hole_rect).unwrap();
+
+        // THEN
+        // Both the U-turning and the straight matchings are intrapolated without intersections
+        assert!(result.matching_scores.len() >= 2);
+        let best_score = result
+            .matching_scores
+            .iter()
+            .min_by(|score1, score2| score1.total.partial_cmp(&score2.total).unwrap())
+            .unwrap();
+        // The first successful matching is not the best one
+        assert_ne!(
+            best_score.matching_index,
+            result.matching_scores[0].matching_index
+        );
+        assert_ne!(best_score.matching_index, 0);
+        let matching = result.matching.unwrap();
+        assert_eq!(matching, result.matchings[best_score.matching_index]);
+        assert_eq!(first_result.matching_scores.len(), 1);
+        assert_ne!(first_result.matching.unwrap(), matching);
+        // The bar is continued straight through the middle of the hole
+        assert_eq!(result.filled_hole[10][7], FilledHoleElement::Texture);
+    }
+
+    #[test]
+    fn hypotheses_are_ranked_with_normalized_confidences() {
+        // GIVEN
+        let hole_rect = BoundingRect::new_x_y_w_h(20, 10, 15, 20);
+        let image = bar_image_with_hole(hole_rect);
+        let shape_completor = make_shape_completor(image, 2.0, CurveIntrapolatorConfig::default())
+            .with_matcher_config(MatcherConfig {
+                algorithm: MatcherAlgorithm::Ranked,
+                ..Default::default()
+            });
+
+        // WHEN
+        let hypotheses = shape_completor
+            .complete_shape_hypotheses(&Hole::from_rect(hole_rect), &[], 3)
+            .unwrap();
+
+        // THEN
+        assert!(hypotheses.len() >= 2 && hypotheses.len() <= 3);
+        let sum_confidences: f64 = hypotheses.iter().map(|h| h.confidence).sum();
+        assert!((sum_confidences - 1.0).abs() < 1e-9);
+        assert!(hypotheses
+            .windows(2)
+            .all(|pair| pair[0].confidence >= pair[1].confidence
+                && pair[0].matching != pair[1].matching));
+        // The most confident hypothesis continues the bar straight through the hole
+        assert_eq!(hypotheses[0].filled_hole[10][7], FilledHoleElement::Texture);
+    }
+
+    #[test]
+    fn hole_out_of_image_is_rejected() {
+        // GIVEN
+        let image = bar_image_with_hole(BoundingRect::new_x_y_w_h(20, 10, 15, 20));
+        let shape_completor = make_shape_completor(image, 2.0, CurveIntrapolatorConfig::default());
+
+        // WHEN
+        let result = shape_completor.complete_shape(BoundingRect::new_x_y_w_h(50, 10, 15, 20));
+
+        // THEN
+        assert!(matches!(result, Err(CompletionError::HoleOutOfBounds)));
+    }
+
+    #[test]
+    fn odd_number_of_endpoints_is_completed_if_unmatched_allowed() {
+        // GIVEN an L shape whose inner corner touches the hole boundary
+        let hole_rect = BoundingRect::new_x_y_w_h(9, 4, 11, 9);
+        let mut image = BinaryImage::new_w_h(40, 40);
+        for y in 9..13 {
+            image.set_pixel(7, y, true);
+            image.set_pixel(8, y, true);
+        }
+        for y in 13..15 {
+            for x in 9..13 {
+                image.set_pixel(x, y, true);
+            }
+        }
+        let new_shape_completor = |allow_unmatched| {
+            make_shape_completor(image.clone(), 2.0, CurveIntrapolatorConfig::default())
+                .with_matcher_config(MatcherConfig {
+                    allow_unmatched,
+                    ..Default::default()
+                })
+        };
+
+        // WHEN
+        let strict_result = new_shape_completor(false).complete_shape(hole_rect);
+        let result = new_shape_completor(true).complete_shape(hole_rect);
+
+        // THEN
+        assert!(matches!(
+            strict_result,
+            Err(CompletionError::OddNumberOfEndpoints(3))
+        ));
+        let result = result.unwrap();
+        let matching = result.matching.unwrap();
+        assert_eq!(matching.index_pairs.len(), 1);
+        assert_eq!(matching.unmatched.len(), 1);
+        assert_eq!(result.intrapolated_curves.len(), 2);
+    }
+
+    #[test]
+    fn corner_inside_hole_is_preserved() {
+        // GIVEN a square whose top right corner is covered by the hole
+        let hole_rect = BoundingRect::new_x_y_w_h(30, 4, 16, 16);
+        let mut image = BinaryImage::new_w_h(60, 60);
+        for y in 10..40 {
+            for x in 10..40 {
+                image.set_pixel(x, y, true);
+            }
+        }
+        for y in hole_rect.top..hole_rect.bottom {
+            for x in hole_rect.left..hole_rect.right {
+                image.set_pixel(x as usize, y as usize, false);
+            }
+        }
+        let new_shape_completor = |preserve_corners| {
+            make_shape_completor(
+                image.clone(),
+                2.0,
+                CurveIntrapolatorConfig {
+                    preserve_corners,
+                    ..Default::default()
+                },
+            )
+        };
+
+        // WHEN
+        let rounded_result = new_shape_completor(false).complete_shape(hole_rect);
+        let result = new_shape_completor(true).complete_shape(hole_rect);
+
+        // THEN the pixel at the corner (39, 10) is only filled if corners are preserved
+        let (row, column) = (10 - 4, 39 - 30);
+        let is_filled = |element| {
+            matches!(
+                element,
+                FilledHoleElement::Structure | FilledHoleElement::Texture
+            )
+        };
+        assert!(!is_filled(rounded_result.unwrap().filled_hole[row][column]));
+        let result = result.unwrap();
+        assert_eq!(result.intrapolated_curves.len(), 1);
+        assert!(matches!(
+            result.intrapolated_curves[0].iter().next(),
+            Some(CompoundPathElement::PathF64(_))
+        ));
+        assert!(is_filled(result.filled_hole[row][column]));
+    }
+
+    #[test]
+    fn missing_arc_of_circle_is_completed_by_conic_fitting() {
+        // GIVEN a disc of radius 15 whose top is covered by the hole
+        let (center, radius) = (PointF64::new(30.0, 30.0), 15.0);
+        let hole_rect = BoundingRect::new_x_y_w_h(22, 10, 16, 10);
+        let mut image = BinaryImage::new_w_h(60, 60);
+        for y in 0..60 {
+            for x in 0..60 {
+                let pixel_center = PointF64::new(x as f64 + 0.5, y as f64 + 0.5);
+                image.set_pixel(x, y, pixel_center.distance_to(center) <= radius);
+            }
+        }
+        for y in hole_rect.top..hole_rect.bottom {
+            for x in hole_rect.left..hole_rect.right {
+                image.set_pixel(x as usize, y as usize, false);
+            }
+        }
+        let shape_completor = make_shape_completor(
+            image,
+            0.5,
+            CurveIntrapolatorConfig {
+                fit_conics: true,
+                ..Default::default()
+            },
+        );
+
+        // WHEN
+        let result = shape_completor.complete_shape(hole_rect).unwrap();
+
+        // THEN the completed arc stays close to the circle
+        assert_eq!(result.intrapolated_curves.len(), 1);
+        let spline = match result.intrapolated_curves[0].iter().next() {
+            Some(CompoundPathElement::Spline(spline)) => spline,
+            _ => panic!("The arc should be a spline"),
+        };
+        for i in (0..spline.points.len()).step_by(3) {
+            let distance = spline.points[i].distance_to(center);
+            assert!((distance - radius).abs() < 1.5, "distance {}", distance);
+        }
+        assert_eq!(result.filled_hole[6][8], FilledHoleElement::Texture);
+    }
+
+    #[test]
+    fn symmetric_shape_is_completed_by_reflection() {
+        // GIVEN a T shape symmetric about x = 30, with the right end of its bar erased
+        let hole_rect = BoundingRect::new_x_y_w_h(42, 6, 12, 16);
+        let mut image = BinaryImage::new_w_h(60, 60);
+        for y in 10..18 {
+            for x in 10..50 {
+                image.set_pixel(x, y, true);
+            }
+        }
+        for y in 18..50 {
+            for x in 26..34 {
+                image.set_pixel(x, y, true);
+            }
+        }
+        for y in hole_rect.top..hole_rect.bottom {
+            for x in hole_rect.left..hole_rect.right {
+                image.set_pixel(x as usize, y as usize, false);
+            }
+        }
+        let shape_completor = make_shape_completor(image, 2.0, CurveIntrapolatorConfig::default())
+            .with_symmetry_config(SymmetryConfig::default());
+
+        // WHEN
+        let result = shape_completor.complete_shape(hole_rect).unwrap();
+        let hypotheses = shape_completor
+            .complete_shape_hypotheses(&Hole::from_rect(hole_rect), &[], 3)
+            .unwrap();
+
+        // THEN the bar ends at x = 50 like its left end at x = 10
+        assert!(result.symmetry_axis.is_some());
+        assert!(result.matching.is_none());
+        assert_eq!(result.filled_hole[4][7], FilledHoleElement::Texture);
+        assert_eq!(result.filled_hole[4][8], FilledHoleElement::Blank);
+        // The reflection scores best, as its contours continue the tails without bending until the
+        // corners of the bar end, and is the most confident hypothesis
+        assert!(hypotheses.len() >= 2 && hypotheses.len() <= 3);
+        assert!(hypotheses[0].symmetry_axis.is_some());
+        assert!(hypotheses[0].matching.index_pairs.is_empty());
+        assert_eq!(hypotheses[0].filled_hole[4][8], FilledHoleElement::Blank);
+        assert!(hypotheses[1..]
+            .iter()
+            .all(|hypothesis| hypothesis.symmetry_axis.is_none()
+                && hypothesis.confidence <= hypotheses[0].confidence));
+    }
+
+    #[test]
+    fn thin_line_is_completed_along_its_centerline() {
+        // GIVEN a diagonal line of 1 pixel wide crossing the hole
+        let mut image = BinaryImage::new_w_h(60, 60);
+        (5..55).for_each(|i| image.set_pixel(i, i, true));
+        let hole_rect = BoundingRect::new_x_y_w_h(20, 20, 12, 12);
+        (20..32).for_each(|i| image.set_pixel(i, i, false));
+        let shape_completor = make_shape_completor(image, 1.0, CurveIntrapolatorConfig::default())
+            .with_centerline_config(CenterlineConfig::default());
+
+        // WHEN
+        let result = shape_completor.complete_shape(hole_rect).unwrap();
+
+        // THEN the diagonal is drawn 1 pixel wide
+        assert_eq!(result.intrapolated_curves.len(), 1);
+        for i in 0..12 {
+            for j in 0..12 {
+                let expected = if i == j {
+                    FilledHoleElement::Structure
+                } else {
+                    FilledHoleElement::Blank
+                };
+                assert_eq!(result.filled_hole[i][j], expected, "at ({}, {})", j, i);
+            }
+        }
+    }
+
+    #[test]
+    fn crossing_lines_are_completed_through_the_crossing() {
+        // GIVEN 2 diagonal lines of 1 pixel wide crossing each other inside the hole
+        let mut image = BinaryImage::new_w_h(61, 61);
+        (5..56).for_each(|i| {
+            image.set_pixel(i, i, true);
+            image.set_pixel(i, 60 - i, true);
+        });
+        let hole_rect = BoundingRect::new_x_y_w_h(24, 24, 13, 13);
+        (24..37).for_each(|i| {
+            image.set_pixel(i, i, false);
+            image.set_pixel(i, 60 - i, false);
+        });
+        let new_shape_completor = |allow_crossings| {
+            make_shape_completor(image.clone(), 1.0, CurveIntrapolatorConfig::default())
+                .with_matcher_config(MatcherConfig {
+                    allow_crossings,
+                    ..Default::default()
+                })
+                .with_match_cost(Box::new(FacingMatchCost))
+                .with_centerline_config(CenterlineConfig::default())
+        };
 
-            if self.debugger.should_draw_control_points() {
-                let color = Color::color(&ColorName::Black);
-                intrapolated_curves.iter().for_each(|curve| {
-                    curve.iter().for_each(|part| {
-                        if let CompoundPathElement::Spline(part) = part {
-                            self.debugger
-                                .draw_cross_i32(&color, part.points[1].to_point_i32());
-                            self.debugger
-                                .draw_cross_i32(&color, part.points[2].to_point_i32());
+        // WHEN
+        let result = new_shape_completor(true).complete_shape(hole_rect).unwrap();
+        let non_crossing_result = new_shape_completor(false).complete_shape(hole_rect);
+
+        // THEN both diagonals are continued straight through the crossing
+        assert_eq!(result.intrapolated_curves.len(), 2);
+        for i in 0..13 {
+            assert_eq!(result.filled_hole[i][i], FilledHoleElement::Structure);
+            assert_eq!(result.filled_hole[i][12 - i], FilledHoleElement::Structure);
+        }
+        assert!(
+            non_crossing_result.map_or(true, |result| result.filled_hole[6][6]
+                == FilledHoleElement::Blank)
+        );
+    }
+
+    #[test]
+    fn crossing_lines_have_non_degenerate_hypothesis_confidences() {
+        // GIVEN 2 diagonal lines of 1 pixel wide crossing each other inside the hole, which may be
+        // completed as an X or as 2 touching arcs
+        let mut image = BinaryImage::new_w_h(61, 61);
+        (5..56).for_each(|i| {
+            image.set_pixel(i, i, true);
+            image.set_pixel(i, 60 - i, true);
+        });
+        let hole_rect = BoundingRect::new_x_y_w_h(24, 24, 13, 13);
+        (24..37).for_each(|i| {
+            image.set_pixel(i, i, false);
+            image.set_pixel(i, 60 - i, false);
+        });
+        let shape_completor = make_shape_completor(image, 1.0, CurveIntrapolatorConfig::default())
+            .with_matcher_config(MatcherConfig {
+                algorithm: MatcherAlgorithm::Ranked,
+                allow_crossings: true,
+                ..Default::default()
+            })
+            .with_centerline_config(CenterlineConfig::default());
+
+        // WHEN
+        let hypotheses = shape_completor
+            .complete_shape_hypotheses(&Hole::from_rect(hole_rect), &[], 3)
+            .unwrap();
+
+        // THEN every alternative keeps a share of the confidence
+        assert!(hypotheses.len() >= 2);
+        let sum_confidences: f64 = hypotheses.iter().map(|h| h.confidence).sum();
+        assert!((sum_confidences - 1.0).abs() < 1e-9);
+        assert!(hypotheses
+            .iter()
+            .all(|hypothesis| hypothesis.confidence > 0.05 && hypothesis.confidence < 0.95));
+        assert!(hypotheses
+            .windows(2)
+            .all(|pair| pair[0].confidence >= pair[1].confidence));
+    }
+
+    #[test]
+    fn line_ending_on_another_line_forms_a_t_junction() {
+        // GIVEN a T of lines of 1 pixel wide whose junction is inside the hole
+        let mut image = BinaryImage::new_w_h(60, 60);
+        (5..55).for_each(|i| image.set_pixel(i, 30, true));
+        (30..55).for_each(|i| image.set_pixel(30, i, true));
+        let hole_rect = BoundingRect::new_x_y_w_h(24, 24, 12, 12);
+        for y in 24..36 {
+            for x in 24..36 {
+                image.set_pixel(x, y, false);
+            }
+        }
+        let shape_completor = make_shape_completor(image, 1.0, CurveIntrapolatorConfig::default())
+            .with_matcher_config(MatcherConfig {
+                allow_unmatched: true,
+                allow_t_junctions: true,
+                ..Default::default()
+            })
+            .with_match_cost(Box::new(FacingMatchCost))
+            .with_centerline_config(CenterlineConfig::default());
+
+        // WHEN
+        let result = shape_completor.complete_shape(hole_rect).unwrap();
+
+        // THEN the stem is extended until it meets the bar
+        assert_eq!(result.intrapolated_curves.len(), 2);
+        for i in 0..12 {
+            assert_eq!(result.filled_hole[6][i], FilledHoleElement::Structure);
+        }
+        for i in 6..12 {
+            assert_eq!(result.filled_hole[i][6], FilledHoleElement::Structure);
+        }
+    }
+
+    #[test]
+    fn stroke_edges_are_completed_at_constant_width() {
+        // GIVEN a horizontal bar of width 10 crossing the hole
+        let hole_rect = BoundingRect::new_x_y_w_h(20, 10, 15, 20);
+        let image = bar_image_with_hole(hole_rect);
+        let shape_completor = make_shape_completor(
+            image,
+            2.0,
+            CurveIntrapolatorConfig {
+                complete_strokes: true,
+                ..Default::default()
+            },
+        );
+
+        // WHEN
+        let result = shape_completor.complete_shape(hole_rect).unwrap();
+
+        // THEN both edges are offset curves 10 pixels apart
+        assert_eq!(result.intrapolated_curves.len(), 2);
+        let edges: Vec<Vec<PointF64>> = result
+            .intrapolated_curves
+            .iter()
+            .map(|curve| match curve.iter().next() {
+                Some(CompoundPathElement::PathF64(path)) => path.path.clone(),
+                _ => panic!("The edges should be offset polylines"),
+            })
+            .collect();
+        for (p1, p2) in edges[0].iter().zip(edges[1].iter()) {
+            assert!((p1.distance_to(*p2) - 10.0).abs() < 1e-6);
+        }
+        assert_eq!(result.filled_hole[10][7], FilledHoleElement::Texture);
+    }
+
+    #[test]
+    fn fragment_surviving_in_hole_is_kept_and_joined() {
+        // GIVEN a horizontal bar crossing a wide hole, with a piece of it surviving in the middle
+        let hole_rect = BoundingRect::new_x_y_w_h(15, 8, 30, 24);
+        let mut image = bar_image_with_hole(hole_rect);
+        for y in 15..25 {
+            for x in 25..35 {
+                image.set_pixel(x, y, true);
+            }
+        }
+        let shape_completor = make_shape_completor(image, 2.0, CurveIntrapolatorConfig::default());
+
+        // WHEN
+        let result = shape_completor.complete_shape(hole_rect).unwrap();
+
+        // THEN the ends of the fragment are matched to the cut ends of the bar
+        assert_eq!(result.path_segments.len(), 8);
+        assert_eq!(result.intrapolated_curves.len(), 4);
+        let filled_hole = &result.filled_hole;
+        // (row, column) relative to the hole: the bar spans rows 7..17
+        for &(i, j) in [(12, 15), (8, 10), (16, 19)].iter() {
+            assert_eq!(filled_hole[i][j], FilledHoleElement::Structure);
+        }
+        for &(i, j) in [(12, 4), (12, 25)].iter() {
+            assert_ne!(filled_hole[i][j], FilledHoleElement::Blank);
+        }
+        for &(i, j) in [(2, 15), (21, 15), (3, 4), (20, 25)].iter() {
+            assert_eq!(filled_hole[i][j], FilledHoleElement::Blank);
+        }
+    }
+
+    #[test]
+    fn curves_crossing_visible_contours_are_rejected() {
+        // GIVEN a horizontal bar crossing a ring-shaped hole, around a visible square sticking out
+        // of the top edge of the bar, where matchings with crossing curves are not considered
+        let hole_rect = BoundingRect::new_x_y_w_h(20, 10, 15, 20);
+        let mut image = bar_image_with_hole(hole_rect);
+        let mut mask = BinaryImage::new_w_h(image.width, image.height);
+        for y in 10..30 {
+            for x in 20..35 {
+                let in_square = (25..30).contains(&x) && (12..18).contains(&y);
+                mask.set_pixel(x, y, !in_square);
+                image.set_pixel(x, y, in_square);
+            }
+        }
+        let hole = Hole::from_mask(&mask);
+        let shape_completor = make_shape_completor(image, 2.0, CurveIntrapolatorConfig::default())
+            .with_matcher_config(MatcherConfig {
+                algorithm: MatcherAlgorithm::NonCrossing,
+                ..Default::default()
+            });
+
+        // WHEN
+        let result = shape_completor.complete_shape_in_hole(&hole).unwrap();
+
+        // THEN the edges of the bar are not continued across the square, but the bar is capped on
+        // both sides instead
+        let matching = result.matching.unwrap();
+        assert_eq!(matching.index_pairs.len(), 2);
+        for &(index1, index2) in matching.index_pairs.iter() {
+            let (head1, head2) = (
+                result.path_segments[index1][0],
+                result.path_segments[index2][0],
+            );
+            assert_eq!(head1.x, head2.x);
+        }
+    }
+
+    /// A xorshift PRNG, so that the fuzz-style tests are deterministic.
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n.max(1) as u64) as usize
+        }
+
+        fn in_range(&mut self, low: i32, high: i32) -> i32 {
+            low + self.below((high - low) as usize) as i32
+        }
+    }
+
+    /// An image of random blobs of random density.
+    fn random_image(rng: &mut XorShift) -> BinaryImage {
+        let (width, height) = (rng.below(40) + 1, rng.below(40) + 1);
+        let mut image = BinaryImage::new_w_h(width, height);
+        for _ in 0..rng.below(8) {
+            let (x, y) = (rng.below(width) as i32, rng.below(height) as i32);
+            let (w, h) = (rng.below(width) as i32, rng.below(height) as i32);
+            let density = rng.below(4);
+            for i in y..(y + h) {
+                for j in x..(x + w) {
+                    if rng.below(4) <= density {
+                        image.set_pixel_at_safe(PointI32::new(j, i), true);
+                    }
+                }
+            }
+        }
+        image
+    }
+
+    /// A random rect that may be empty, partially or entirely outside of the image.
+    fn random_rect(rng: &mut XorShift, image: &BinaryImage) -> BoundingRect {
+        let (width, height) = (image.width as i32, image.height as i32);
+        BoundingRect::new_x_y_w_h(
+            rng.in_range(-3, width + 3),
+            rng.in_range(-3, height + 3),
+            rng.in_range(0, width + 3),
+            rng.in_range(0, height + 3),
+        )
+    }
+
+    fn clear_rect(image: &mut BinaryImage, rect: BoundingRect) {
+        for y in rect.top..rect.bottom {
+            for x in rect.left..rect.right {
+                image.set_pixel_at_safe(PointI32::new(x, y), false);
+            }
+        }
+    }
+
+    fn random_shape_completor(rng: &mut XorShift, image: BinaryImage) -> ShapeCompletor {
+        let simplify_tolerance = rng.below(4) as f64;
+        let curve_intrapolator_config = CurveIntrapolatorConfig {
+            curve_model: match rng.below(3) {
+                0 => CurveModel::Bezier,
+                1 => CurveModel::Clothoid,
+                _ => CurveModel::CurvatureContinuous,
+            },
+            tail_tangent_estimator: if rng.below(2) == 0 {
+                TailTangentEstimator::WeightedAverage
+            } else {
+                TailTangentEstimator::LeastSquares
+            },
+            preserve_corners: rng.below(2) == 0,
+            fit_conics: rng.below(2) == 0,
+            complete_strokes: rng.below(2) == 0,
+            ..Default::default()
+        };
+        let matcher_config = MatcherConfig {
+            allow_unmatched: rng.below(2) == 0,
+            algorithm: match rng.below(3) {
+                0 => MatcherAlgorithm::Exhaustive,
+                1 => MatcherAlgorithm::Ranked,
+                _ => MatcherAlgorithm::NonCrossing,
+            },
+            allow_crossings: rng.below(2) == 0,
+            allow_t_junctions: rng.below(2) == 0,
+            ..Default::default()
+        };
+        let match_cost: Box<dyn MatchCost> = if rng.below(2) == 0 {
+            matcher_config.algorithm.default_match_cost()
+        } else {
+            Box::new(GestaltMatchCost::default())
+        };
+        let shape_completor = ShapeCompletor::new(
+            image,
+            simplify_tolerance,
+            curve_intrapolator_config,
+            rng.below(4),
+            None,
+        )
+        .with_matcher_config(matcher_config)
+        .with_match_cost(match_cost)
+        .with_simplify_config(SimplifyConfig {
+            mode: match rng.below(4) {
+                0 => SimplifyMode::None,
+                1 => SimplifyMode::RamerDouglasPeucker,
+                2 => SimplifyMode::VisvalingamWhyatt,
+                _ => SimplifyMode::Spline,
+            },
+            ..Default::default()
+        });
+        if rng.below(4) == 0 {
+            shape_completor.with_centerline_config(CenterlineConfig::default())
+        } else {
+            shape_completor
+        }
+    }
+
+    #[test]
+    fn thin_bar_is_filled_by_even_odd_rule() {
+        // GIVEN a horizontal bar 3 pixels thick crossing a hole, too thin for the boundary between
+        // its endpoints to be sampled
+        let hole_rect = BoundingRect::new_x_y_w_h(20, 10, 15, 20);
+        let mut image = BinaryImage::new_w_h(60, 40);
+        for y in 18..21 {
+            for x in 5..55 {
+                if !(20..35).contains(&x) {
+                    image.set_pixel(x, y, true);
+                }
+            }
+        }
+        let shape_completor = |fill_rule| {
+            make_shape_completor(image.clone(), 2.0, CurveIntrapolatorConfig::default())
+                .with_fill_rule(fill_rule)
+        };
+
+        // WHEN
+        let sampled = shape_completor(FillRule::BoundarySampling)
+            .complete_shape(hole_rect)
+            .unwrap();
+        let even_odd = shape_completor(FillRule::EvenOdd)
+            .complete_shape(hole_rect)
+            .unwrap();
+
+        // THEN only the even-odd rule fills the bar between the curves along its edges (rows 8 and
+        // 11 relative to the hole)
+        assert_eq!(even_odd.intrapolated_curves.len(), 2);
+        assert!(sampled
+            .filled_hole
+            .elems
+            .iter()
+            .all(|&elem| elem != FilledHoleElement::Texture));
+        assert!(even_odd.filled_hole[9]
+            .iter()
+            .all(|&elem| elem == FilledHoleElement::Texture));
+        for i in (0..20).filter(|i| !(9..11).contains(i)) {
+            assert!(even_odd.filled_hole[i]
+                .iter()
+                .all(|&elem| elem != FilledHoleElement::Texture));
+        }
+    }
+
+    #[test]
+    fn separate_holes_on_one_stroke_are_completed_jointly() {
+        // GIVEN a horizontal bar with a notch cut into its top and another one into its bottom,
+        // by 2 holes touching only at a corner, which is on the boundaries of both
+        let holes = [
+            Hole::from_rect(BoundingRect::new_x_y_w_h(15, 10, 10, 10)),
+            Hole::from_rect(BoundingRect::new_x_y_w_h(25, 20, 10, 10)),
+        ];
+        let mut image = bar_image_with_hole(holes[0].rect());
+        for y in 20..25 {
+            for x in 25..35 {
+                image.set_pixel(x, y, false);
+            }
+        }
+        let shape_completor = make_shape_completor(image, 2.0, CurveIntrapolatorConfig::default());
+
+        // WHEN
+        let completion = shape_completor.complete_shapes(&holes).unwrap();
+
+        // THEN the holes are not merged, and the endpoints of each hole on the boundary of the
+        // other are discarded
+        assert_eq!(completion.holes.len(), 2);
+        let expected_heads = [
+            vec![PointI32::new(15, 15), PointI32::new(25, 15)],
+            vec![PointI32::new(25, 25), PointI32::new(35, 25)],
+        ];
+        for (i, result) in completion.results.iter().enumerate() {
+            let result = result.as_ref().unwrap();
+            let mut heads: Vec<PointI32> = result.path_segments.iter().map(|s| s[0]).collect();
+            heads.sort_by_key(|p| (p.x, p.y));
+            assert_eq!(heads, expected_heads[i]);
+            let other_hole = &completion.holes[1 - i];
+            assert!(heads
+                .iter()
+                .all(|&head| !other_hole.have_point_on_boundary_or_inside(head, 0)));
+        }
+        // Both notches are filled in the composited image
+        assert!(completion.image.get_pixel(20, 17));
+        assert!(completion.image.get_pixel(30, 22));
+        // The hypotheses of a hole among the other discard the same endpoints
+        let hypotheses = shape_completor
+            .complete_shape_hypotheses(&holes[0], &holes[1..], 1)
+            .unwrap();
+        let result = completion.results[0].as_ref().unwrap();
+        assert_eq!(hypotheses.len(), 1);
+        assert_eq!(
+            hypotheses[0].matching.index_pairs,
+            result.matching.as_ref().unwrap().index_pairs
+        );
+        assert_eq!(hypotheses[0].filled_hole[7][5], FilledHoleElement::Texture);
+    }
+
+    #[test]
+    fn random_rect_holes_never_panic() {
+        let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+        for _ in 0..500 {
+            // GIVEN
+            let mut image = random_image(&mut rng);
+            let hole_rect = random_rect(&mut rng, &image);
+            if rng.below(4) != 0 {
+                clear_rect(&mut image, hole_rect);
+            }
+            let shape_completor = random_shape_completor(&mut rng, image);
+
+            // WHEN / THEN (no panic)
+            let _ = shape_completor.complete_shape(hole_rect);
+            let _ = shape_completor.complete_shape_and_draw_expandable(hole_rect);
+            let _ = shape_completor.complete_shape_hypotheses(&Hole::from_rect(hole_rect), &[], 3);
+        }
+    }
+
+    #[test]
+    fn random_masked_holes_never_panic() {
+        let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..300 {
+            // GIVEN
+            let mut image = random_image(&mut rng);
+            let mut holes = vec![];
+            for _ in 0..(rng.below(3) + 1) {
+                let mut mask = BinaryImage::new_w_h(image.width, image.height);
+                let rect = random_rect(&mut rng, &image);
+                for y in rect.top..rect.bottom {
+                    for x in rect.left..rect.right {
+                        if rng.below(5) != 0 {
+                            mask.set_pixel_at_safe(PointI32::new(x, y), true);
                         }
-                    });
-                });
+                    }
+                }
+                let hole = Hole::from_mask(&mask);
+                for y in 0..image.height {
+                    for x in 0..image.width {
+                        if mask.get_pixel(x, y) {
+                            image.set_pixel(x, y, false);
+                        }
+                    }
+                }
+                holes.push(hole);
             }
+            let shape_completor = random_shape_completor(&mut rng, image);
 
-            // Trust it to be the correct solution
-            return Some(intrapolated_curves);
+            // WHEN / THEN (no panic)
+            holes.iter().for_each(|hole| {
+                let _ = shape_completor.complete_shape_in_hole(hole);
+            });
+            let _ = shape_completor.complete_shapes(&holes).unwrap();
         }
+    }
+
+    #[test]
+    fn invalid_configuration_is_rejected() {
+        // GIVEN
+        let hole_rect = BoundingRect::new_x_y_w_h(20, 10, 15, 20);
+        let image = bar_image_with_hole(hole_rect);
+        let config = CurveIntrapolatorConfig {
+            tail_tangent_num_points: 0,
+            ..Default::default()
+        };
+        let shape_completor = make_shape_completor(image, 2.0, config);
+
+        // WHEN
+        let result = shape_completor.complete_shape(hole_rect);
 
-        None
+        // THEN
+        assert!(matches!(
+            result,
+            Err(CompletionError::InvalidConfiguration(_))
+        ));
     }
 }
@@ -0,0 +1,405 @@
+use std::f64::consts::PI;
+
+use visioncortex::{PointF64, Spline};
+
+/// The largest change of the ellipse parameter covered by one cubic bezier curve
+const MAX_PART_PARAMETER_ANGLE: f64 = PI / 4.0;
+
+type Matrix3 = [[f64; 3]; 3];
+
+/// An ellipse, parameterized by angle as
+/// point(t) = 'center' + 'semi_axis_u' * cos(t) * u + 'semi_axis_v' * sin(t) * v,
+/// where u = (cos('rotation'), sin('rotation')) and v = (-sin('rotation'), cos('rotation')).
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Ellipse {
+    pub(super) center: PointF64,
+    pub(super) semi_axis_u: f64,
+    pub(super) semi_axis_v: f64,
+    pub(super) rotation: f64,
+}
+
+impl Ellipse {
+    /// Fit the ellipse to 'points' by direct least squares on the algebraic distance (Fitzgibbon et
+    /// al. 1999, in the numerically stable form of Halir & Flusser 1998). Circles are ellipses with
+    /// equal semi-axes.
+    /// None is returned if there are fewer than 6 points, or no ellipse can be found (e.g. the
+    /// points are collinear).
+    pub(super) fn fit(points: &[PointF64]) -> Option<Self> {
+        if points.len() < 6 {
+            return None;
+        }
+        // Normalize the points for numerical stability
+        let centroid =
+            points.iter().fold(PointF64::default(), |acc, &p| acc + p) / points.len() as f64;
+        let scale =
+            points.iter().map(|p| p.distance_to(centroid)).sum::<f64>() / points.len() as f64;
+        if scale == 0.0 || !scale.is_finite() {
+            return None;
+        }
+        let normalized: Vec<PointF64> = points.iter().map(|&p| (p - centroid) / scale).collect();
+
+        // Scatter matrices of the quadratic part [x^2, xy, y^2] and the linear part [x, y, 1]
+        let (mut s1, mut s2, mut s3) = ([[0.0; 3]; 3], [[0.0; 3]; 3], [[0.0; 3]; 3]);
+        for p in normalized.iter() {
+            let quadratic = [p.x * p.x, p.x * p.y, p.y * p.y];
+            let linear = [p.x, p.y, 1.0];
+            for i in 0..3 {
+                for j in 0..3 {
+                    s1[i][j] += quadratic[i] * quadratic[j];
+                    s2[i][j] += quadratic[i] * linear[j];
+                    s3[i][j] += linear[i] * linear[j];
+                }
+            }
+        }
+        let t = multiply(&invert(&s3)?, &transpose(&s2));
+        let t = t.map(|row| row.map(|value| -value));
+        let m = add(&s1, &multiply(&s2, &t));
+        // Premultiply by the inverse of the constraint matrix of 4ac - b^2 = 1
+        let m = [
+            m[2].map(|value| value / 2.0),
+            m[1].map(|value| -value),
+            m[0].map(|value| value / 2.0),
+        ];
+
+        let quadratic = real_eigenvalues(&m)
+            .into_iter()
+            .filter_map(|eigenvalue| eigenvector(&m, eigenvalue))
+            .find(|a| 4.0 * a[0] * a[2] - a[1] * a[1] > 0.0)?;
+        let linear = multiply_vector(&t, &quadratic);
+
+        let normalized_ellipse = Self::from_conic(
+            quadratic[0],
+            quadratic[1],
+            quadratic[2],
+            linear[0],
+            linear[1],
+            linear[2],
+        )?;
+        Some(Self {
+            center: normalized_ellipse.center * scale + centroid,
+            semi_axis_u: normalized_ellipse.semi_axis_u * scale,
+            semi_axis_v: normalized_ellipse.semi_axis_v * scale,
+            rotation: normalized_ellipse.rotation,
+        })
+    }
+
+    /// The root mean square of the approximate (first order) distances from 'points' to the ellipse.
+    pub(super) fn residual(&self, points: &[PointF64]) -> f64 {
+        if points.is_empty() {
+            return 0.0;
+        }
+        let sum_squares: f64 = points
+            .iter()
+            .map(|&p| {
+                let (x, y) = self.to_local(p);
+                let (a2, b2) = (
+                    self.semi_axis_u * self.semi_axis_u,
+                    self.semi_axis_v * self.semi_axis_v,
+                );
+                let value = x * x / a2 + y * y / b2 - 1.0;
+                let gradient = 2.0 * (x * x / (a2 * a2) + y * y / (b2 * b2)).sqrt();
+                if gradient == 0.0 {
+                    // The center, at a distance of roughly the semi-axes
+                    a2.min(b2)
+                } else {
+                    (value / gradient).powi(2)
+                }
+            })
+            .sum();
+        (sum_squares / points.len() as f64).sqrt()
+    }
+
+    /// The parameter of the point on the ellipse in the direction of 'p' from the center, after
+    /// scaling the ellipse into a circle.
+    pub(super) fn parameter_of(&self, p: PointF64) -> f64 {
+        let (x, y) = self.to_local(p);
+        (y / self.semi_axis_v).atan2(x / self.semi_axis_u)
+    }
+
+    /// The point at parameter 't'.
+    pub(super) fn point_at(&self, t: f64) -> PointF64 {
+        let (u, v) = self.axes();
+        self.center + u * (self.semi_axis_u * t.cos()) + v * (self.semi_axis_v * t.sin())
+    }
+
+    /// The derivative with respect to the parameter at 't'.
+    pub(super) fn derivative_at(&self, t: f64) -> PointF64 {
+        let (u, v) = self.axes();
+        u * (-self.semi_axis_u * t.sin()) + v * (self.semi_axis_v * t.cos())
+    }
+
+    /// The point at 'ratio' ([0.0, 1.0]) along the arc from parameter 'from' by (signed) 'sweep',
+    /// deformed so that the arc starts at 'from_point' and ends at 'to_point': the offsets of these
+    /// points from the ends of the arc are blended linearly along the parameter.
+    pub(super) fn deformed_arc_point_at(
+        &self,
+        (from, sweep): (f64, f64),
+        (from_point, to_point): (PointF64, PointF64),
+        ratio: f64,
+    ) -> PointF64 {
+        let offset = (from_point - self.point_at(from)) * (1.0 - ratio)
+            + (to_point - self.point_at(from + sweep)) * ratio;
+        self.point_at(from + sweep * ratio) + offset
+    }
+
+    /// Approximate the deformed arc (see deformed_arc_point_at()) with cubic bezier curves, each
+    /// covering at most MAX_PART_PARAMETER_ANGLE of the parameter.
+    pub(super) fn arc_to_spline(
+        &self,
+        (from, sweep): (f64, f64),
+        (from_point, to_point): (PointF64, PointF64),
+    ) -> Spline {
+        let num_parts = std::cmp::max((sweep.abs() / MAX_PART_PARAMETER_ANGLE).ceil() as usize, 1);
+        let step = sweep / num_parts as f64;
+        // The length of the tangent handles of the bezier approximation of a circular arc
+        let handle_ratio = 4.0 / 3.0 * (step / 4.0).tan();
+
+        let mut spline = Spline::new(from_point);
+        for i in 0..num_parts {
+            let (ratio0, ratio1) = (
+                i as f64 / num_parts as f64,
+                (i + 1) as f64 / num_parts as f64,
+            );
+            let (t0, t1) = (from + sweep * ratio0, from + sweep * ratio1);
+            let (p0, p1) = (
+                self.deformed_arc_point_at((from, sweep), (from_point, to_point), ratio0),
+                self.deformed_arc_point_at((from, sweep), (from_point, to_point), ratio1),
+            );
+            spline.add(
+                p0 + self.derivative_at(t0) * handle_ratio,
+                p1 - self.derivative_at(t1) * handle_ratio,
+                p1,
+            );
+        }
+        spline
+    }
+}
+
+// Helper functions
+impl Ellipse {
+    /// The ellipse of the conic a*x^2 + b*x*y + c*y^2 + d*x + e*y + f = 0.
+    /// None is returned unless the conic is a real, non-degenerate ellipse.
+    fn from_conic(a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Option<Self> {
+        let denominator = 4.0 * a * c - b * b;
+        if denominator <= 0.0 {
+            return None;
+        }
+        let center = PointF64::new(
+            (b * e - 2.0 * c * d) / denominator,
+            (b * d - 2.0 * a * e) / denominator,
+        );
+        let value_at_center = f + (d * center.x + e * center.y) / 2.0;
+
+        let rotation = b.atan2(a - c) / 2.0;
+        let (cos, sin) = (rotation.cos(), rotation.sin());
+        let a_rotated = a * cos * cos + b * cos * sin + c * sin * sin;
+        let c_rotated = a * sin * sin - b * cos * sin + c * cos * cos;
+        let (semi_axis_u_squared, semi_axis_v_squared) =
+            (-value_at_center / a_rotated, -value_at_center / c_rotated);
+        if !(semi_axis_u_squared > 0.0 && semi_axis_v_squared > 0.0) {
+            return None;
+        }
+        let ellipse = Self {
+            center,
+            semi_axis_u: semi_axis_u_squared.sqrt(),
+            semi_axis_v: semi_axis_v_squared.sqrt(),
+            rotation,
+        };
+        if [
+            ellipse.center.x,
+            ellipse.center.y,
+            ellipse.semi_axis_u,
+            ellipse.semi_axis_v,
+        ]
+        .iter()
+        .all(|value| value.is_finite())
+        {
+            Some(ellipse)
+        } else {
+            None
+        }
+    }
+
+    fn axes(&self) -> (PointF64, PointF64) {
+        let (cos, sin) = (self.rotation.cos(), self.rotation.sin());
+        (PointF64::new(cos, sin), PointF64::new(-sin, cos))
+    }
+
+    /// The coordinates of 'p' along the axes of the ellipse, relative to its center.
+    fn to_local(self, p: PointF64) -> (f64, f64) {
+        let (u, v) = self.axes();
+        let offset = p - self.center;
+        (offset.dot(u), offset.dot(v))
+    }
+}
+
+fn transpose(m: &Matrix3) -> Matrix3 {
+    let mut result = [[0.0; 3]; 3];
+    for (i, row) in m.iter().enumerate() {
+        for (j, &value) in row.iter().enumerate() {
+            result[j][i] = value;
+        }
+    }
+    result
+}
+
+fn add(m1: &Matrix3, m2: &Matrix3) -> Matrix3 {
+    let mut result = *m1;
+    for i in 0..3 {
+        for j in 0..3 {
+            result[i][j] += m2[i][j];
+        }
+    }
+    result
+}
+
+fn multiply(m1: &Matrix3, m2: &Matrix3) -> Matrix3 {
+    let mut result = [[0.0; 3]; 3];
+    for i in 0..3 {
+        for j in 0..3 {
+            result[i][j] = (0..3).map(|k| m1[i][k] * m2[k][j]).sum();
+        }
+    }
+    result
+}
+
+fn multiply_vector(m: &Matrix3, v: &[f64; 3]) -> [f64; 3] {
+    [0, 1, 2].map(|i| (0..3).map(|k| m[i][k] * v[k]).sum())
+}
+
+fn cross(v1: &[f64; 3], v2: &[f64; 3]) -> [f64; 3] {
+    [
+        v1[1] * v2[2] - v1[2] * v2[1],
+        v1[2] * v2[0] - v1[0] * v2[2],
+        v1[0] * v2[1] - v1[1] * v2[0],
+    ]
+}
+
+/// The inverse of 'm' by its adjugate. None is returned if 'm' is (nearly) singular.
+fn invert(m: &Matrix3) -> Option<Matrix3> {
+    let cofactor_rows = [
+        cross(&m[1], &m[2]),
+        cross(&m[2], &m[0]),
+        cross(&m[0], &m[1]),
+    ];
+    let determinant: f64 = (0..3).map(|k| m[0][k] * cofactor_rows[0][k]).sum();
+    let norm: f64 = m
+        .iter()
+        .flatten()
+        .map(|value| value.abs())
+        .fold(0.0, f64::max);
+    if determinant.abs() <= f64::EPSILON * norm * norm * norm {
+        return None;
+    }
+    // The adjugate is the transpose of the cofactor matrix
+    Some(transpose(&cofactor_rows).map(|row| row.map(|value| value / determinant)))
+}
+
+/// The real eigenvalues of 'm', as the real roots of its characteristic polynomial.
+fn real_eigenvalues(m: &Matrix3) -> Vec<f64> {
+    let trace = m[0][0] + m[1][1] + m[2][2];
+    let sum_principal_minors = m[0][0] * m[1][1] - m[0][1] * m[1][0] + m[0][0] * m[2][2]
+        - m[0][2] * m[2][0]
+        + m[1][1] * m[2][2]
+        - m[1][2] * m[2][1];
+    let determinant: f64 = (0..3).map(|k| m[0][k] * cross(&m[1], &m[2])[k]).sum();
+    solve_monic_cubic(-trace, sum_principal_minors, -determinant)
+}
+
+/// The real roots of x^3 + a*x^2 + b*x + c = 0.
+fn solve_monic_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    // Substitute x = y - a/3 for the depressed cubic y^3 + p*y + q = 0
+    let p = b - a * a / 3.0;
+    let q = 2.0 * a * a * a / 27.0 - a * b / 3.0 + c;
+    let shift = -a / 3.0;
+    let discriminant = q * q / 4.0 + p * p * p / 27.0;
+    if discriminant > 0.0 {
+        let sqrt_discriminant = discriminant.sqrt();
+        vec![(-q / 2.0 + sqrt_discriminant).cbrt() + (-q / 2.0 - sqrt_discriminant).cbrt() + shift]
+    } else if p == 0.0 {
+        vec![shift]
+    } else {
+        // 3 real roots (some may coincide), by the trigonometric method
+        let r = 2.0 * (-p / 3.0).sqrt();
+        let phi = ((3.0 * q / (p * r)).clamp(-1.0, 1.0)).acos() / 3.0;
+        (0..3)
+            .map(|k| r * (phi - 2.0 * PI * k as f64 / 3.0).cos() + shift)
+            .collect()
+    }
+}
+
+/// An eigenvector of 'm' for 'eigenvalue', as the largest cross product of 2 rows of
+/// m - eigenvalue * I. None is returned if all of them are zero.
+fn eigenvector(m: &Matrix3, eigenvalue: f64) -> Option<[f64; 3]> {
+    let mut shifted = *m;
+    (0..3).for_each(|i| shifted[i][i] -= eigenvalue);
+    let norm = |v: &[f64; 3]| v.iter().map(|value| value * value).sum::<f64>();
+    let candidate = [
+        cross(&shifted[0], &shifted[1]),
+        cross(&shifted[1], &shifted[2]),
+        cross(&shifted[2], &shifted[0]),
+    ]
+    .iter()
+    .copied()
+    .max_by(|v1, v2| {
+        norm(v1)
+            .partial_cmp(&norm(v2))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    })?;
+    if norm(&candidate) > 0.0 {
+        Some(candidate)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_on_a_rotated_ellipse_are_fitted_exactly() {
+        // GIVEN points on a third of an ellipse with semi-axes 30 and 10, rotated by 0.5 radians
+        let expected = Ellipse {
+            center: PointF64::new(50.0, 40.0),
+            semi_axis_u: 30.0,
+            semi_axis_v: 10.0,
+            rotation: 0.5,
+        };
+        let points: Vec<PointF64> = (0..12)
+            .map(|i| expected.point_at(i as f64 * 2.0 * PI / 36.0))
+            .collect();
+
+        // WHEN
+        let ellipse = Ellipse::fit(&points).unwrap();
+
+        // THEN
+        assert!(ellipse.residual(&points) < 1e-6);
+        assert!(ellipse.center.distance_to(expected.center) < 1e-6);
+        let mut semi_axes = [ellipse.semi_axis_u, ellipse.semi_axis_v];
+        semi_axes.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert!((semi_axes[0] - 10.0).abs() < 1e-6);
+        assert!((semi_axes[1] - 30.0).abs() < 1e-6);
+        let far_point = expected.point_at(PI);
+        assert!(
+            ellipse
+                .point_at(ellipse.parameter_of(far_point))
+                .distance_to(far_point)
+                < 1e-6
+        );
+    }
+
+    #[test]
+    fn collinear_points_are_not_fitted() {
+        // GIVEN
+        let points: Vec<PointF64> = (0..10)
+            .map(|i| PointF64::new(i as f64, 2.0 * i as f64))
+            .collect();
+
+        // WHEN
+        let ellipse = Ellipse::fit(&points);
+
+        // THEN
+        assert!(ellipse.is_none());
+    }
+}
@@ -1,14 +1,69 @@
-use visioncortex::{BoundingRect, Color, CompoundPath, PathF64, PointF64, Spline};
+use visioncortex::{Color, CompoundPath, PathF64, PathI32, PointF64, PointI32, Spline};
 
 use crate::{
+    clothoid::Clothoid,
+    conic::Ellipse,
     debugger::Debugger,
+    error::CompletionError,
     geo::{
-        calculate_intersection, calculate_midpoint, calculate_unit_normal_of_line,
-        find_corners_open_path, find_new_point_from_4_point_scheme, retract_point,
-        LineIntersectionResult,
+        bounds_of_points, calculate_in_between_point, calculate_intersection, calculate_midpoint,
+        calculate_unit_normal_of_line, find_corners_open_path, find_new_point_from_4_point_scheme,
+        flatten_compound_path, segments_intersection, split_bezier_in_half, LineIntersectionResult,
     },
+    hermite::QuinticHermite,
+    hole::Hole,
+    tangent::fit_tail_tangent,
 };
 
+/// The longest arc length of a clothoid or quintic curve approximated by one cubic bezier curve
+const MAX_PART_LENGTH: f64 = 8.0;
+/// The largest turning angle of a clothoid or quintic curve approximated by one cubic bezier curve
+const MAX_PART_TURNING_ANGLE: f64 = std::f64::consts::FRAC_PI_4;
+
+/// The number of points the midline between 2 edges of a stroke is sampled at
+const NUM_MIDLINE_POINTS: usize = 8;
+/// The number of line segments each cubic bezier curve of a stroke centerline is flattened into
+const NUM_CENTERLINE_SAMPLES_PER_BEZIER: usize = 8;
+/// The number of line segments each cubic bezier curve is flattened into when finding a T-junction
+const NUM_JUNCTION_SAMPLES_PER_BEZIER: usize = 16;
+
+/// The number of times a curve is split in half at most when proving that it is inside the hole
+const MAX_CONTAINMENT_SPLIT_DEPTH: usize = 8;
+/// The number of times a cubic bezier curve leaving the hole is split in half at most to re-fit
+/// only the parts that leave it
+const MAX_CURVE_SUBDIVISIONS: usize = 3;
+/// The number of times the control points of a cubic bezier curve are retracted towards its ends
+/// at most to keep it inside the hole, before the straight line between its ends is tried
+const MAX_HANDLE_RETRACTIONS: usize = 8;
+
+/// The kind of curves intrapolated between 2 tails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CurveModel {
+    /// One cubic bezier curve (or two for S-shapes) with control points derived from the
+    /// intersection of the tail tangents
+    Bezier,
+    /// The clothoid (Euler spiral) matching the positions and tangents at both tails, whose curvature
+    /// changes linearly along the curve. Falls back to CurveModel::Bezier where the clothoid cannot
+    /// be found or leaves the hole.
+    Clothoid,
+    /// The quintic bezier curve matching the positions, tangents and curvatures at both tails
+    /// (G2 continuity), where the curvature at a tail is estimated from its smoothed curve.
+    /// Falls back to CurveModel::Bezier where the curve leaves the hole.
+    CurvatureContinuous,
+}
+
+/// How the tangent and curvature at the tail of a curve are estimated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TailTangentEstimator {
+    /// The weighted average of the directions of the last segments of the (simplified) curve after
+    /// 4-point smoothing, with the curvature through the ends of those segments
+    WeightedAverage,
+    /// The straight line or circular arc fitted by least squares to the unsimplified boundary
+    /// pixels within 'tail_fit_length' of the endpoint (see CurveIntrapolator::with_raw_segments()).
+    /// Falls back to TailTangentEstimator::WeightedAverage where no fit is found.
+    LeastSquares,
+}
+
 #[derive(Clone, Copy)]
 pub struct CurveIntrapolatorConfig {
     // Smoothing
@@ -20,6 +75,83 @@ pub struct CurveIntrapolatorConfig {
     pub tail_tangent_num_points: usize, // [2, Inf]
     pub tail_weight_multiplier: f64,
     pub control_points_retract_ratio: f64,
+    pub tail_tangent_estimator: TailTangentEstimator,
+    /// The arc length (in pixels) of the boundary before an endpoint fitted by
+    /// TailTangentEstimator::LeastSquares
+    pub tail_fit_length: f64,
+    // Stubs of unmatched endpoints
+    pub stub_length_ratio: f64, // Relative to the shorter side of the hole
+    // Curve intrapolation
+    pub curve_model: CurveModel,
+    // Corner preservation
+    /// If true, 2 straight tails are completed by extending both of them to their intersection,
+    /// where the intersection is inside the hole, instead of by a curve of 'curve_model'
+    pub preserve_corners: bool,
+    /// The largest absolute curvature of a tail (over its last 'tail_tangent_num_points' smoothed
+    /// points) considered straight
+    pub straight_tail_max_curvature: f64,
+    // Conic fitting
+    /// If true, an ellipse (or circle) is fitted to both curves, and the missing arc of it is used
+    /// where the fit is good enough, instead of the tail tangents
+    pub fit_conics: bool,
+    /// The largest root mean square distance (in pixels) from the points of both curves to the fitted
+    /// ellipse for its arc to be used
+    pub conic_max_residual: f64,
+    // Stroke completion
+    /// If true, 2 pairs of matched endpoints which are the edges of the same stroke on both sides of
+    /// the hole are completed together, as offset curves of the intrapolated centerline of the stroke
+    pub complete_strokes: bool,
+    /// The largest distance (in pixels) between the endpoints of both edges of a stroke
+    pub max_stroke_width: f64,
+    /// The largest angle (in radians) between the directions of both edges of a stroke at an end
+    pub max_stroke_edge_angle: f64,
+}
+
+impl CurveIntrapolatorConfig {
+    /// Return an error describing the first invalid field, if any.
+    pub fn validate(&self) -> Result<(), CompletionError> {
+        if self.tail_tangent_num_points < 2 {
+            return Err(CompletionError::InvalidConfiguration(
+                "tail_tangent_num_points must be at least 2.",
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.control_points_retract_ratio) {
+            return Err(CompletionError::InvalidConfiguration(
+                "control_points_retract_ratio must be in [0.0, 1.0].",
+            ));
+        }
+        if self.tail_fit_length.is_nan() || self.tail_fit_length <= 0.0 {
+            return Err(CompletionError::InvalidConfiguration(
+                "tail_fit_length must be positive.",
+            ));
+        }
+        if self.stub_length_ratio.is_nan() || self.stub_length_ratio < 0.0 {
+            return Err(CompletionError::InvalidConfiguration(
+                "stub_length_ratio must be non-negative.",
+            ));
+        }
+        if self.outset_ratio.is_nan() || self.outset_ratio <= 0.0 {
+            return Err(CompletionError::InvalidConfiguration(
+                "outset_ratio must be positive.",
+            ));
+        }
+        if self.straight_tail_max_curvature.is_nan() || self.straight_tail_max_curvature < 0.0 {
+            return Err(CompletionError::InvalidConfiguration(
+                "straight_tail_max_curvature must be non-negative.",
+            ));
+        }
+        if self.conic_max_residual.is_nan() || self.conic_max_residual < 0.0 {
+            return Err(CompletionError::InvalidConfiguration(
+                "conic_max_residual must be non-negative.",
+            ));
+        }
+        if self.max_stroke_width.is_nan() || self.max_stroke_width < 0.0 {
+            return Err(CompletionError::InvalidConfiguration(
+                "max_stroke_width must be non-negative.",
+            ));
+        }
+        Ok(())
+    }
 }
 
 impl Default for CurveIntrapolatorConfig {
@@ -32,6 +164,17 @@ impl Default for CurveIntrapolatorConfig {
             tail_tangent_num_points: 5,
             tail_weight_multiplier: 1.5,
             control_points_retract_ratio: 0.4,
+            tail_tangent_estimator: TailTangentEstimator::WeightedAverage,
+            tail_fit_length: 12.0,
+            stub_length_ratio: 0.5,
+            curve_model: CurveModel::Bezier,
+            preserve_corners: false,
+            straight_tail_max_curvature: 0.01,
+            fit_conics: false,
+            conic_max_residual: 1.0,
+            complete_strokes: false,
+            max_stroke_width: 16.0,
+            max_stroke_edge_angle: std::f64::consts::FRAC_PI_6,
         }
     }
 }
@@ -39,28 +182,54 @@ impl Default for CurveIntrapolatorConfig {
 /// intrapolate in-between curve given 2 curves
 pub struct CurveIntrapolator<'a> {
     pub config: CurveIntrapolatorConfig,
-    pub hole_rect: BoundingRect,
+    pub hole: &'a Hole,
     pub debugger: &'a dyn Debugger,
+    /// The unsimplified path segments whose heads (index 0) are the endpoints
+    pub raw_segments: &'a [PathI32],
+    /// Whether the points at integer coordinates are the centers of pixels (as in centerline mode)
+    /// instead of their top-left corners
+    pub pixel_centers: bool,
 }
 
 // API
 impl<'a> CurveIntrapolator<'a> {
     pub fn new(
         config: CurveIntrapolatorConfig,
-        hole_rect: BoundingRect,
+        hole: &'a Hole,
         debugger: &'a dyn Debugger,
     ) -> Self {
         Self {
             config,
-            hole_rect,
+            hole,
             debugger,
+            raw_segments: &[],
+            pixel_centers: false,
         }
     }
 
+    /// Fit tail tangents to 'raw_segments', the unsimplified path segments whose heads (index 0) are
+    /// the endpoints, where TailTangentEstimator::LeastSquares is used. The tangent at the tail of a
+    /// curve is fitted to the raw segment whose head is the same point, or to the curve itself if
+    /// there is no such segment.
+    pub fn with_raw_segments(mut self, raw_segments: &'a [PathI32]) -> Self {
+        self.raw_segments = raw_segments;
+        self
+    }
+
+    /// Treat the points at integer coordinates as the centers of pixels instead of their top-left
+    /// corners when keeping the curves inside the hole, as in centerline mode (see
+    /// HoleFiller::fill_lines()).
+    pub fn with_pixel_centers(mut self, pixel_centers: bool) -> Self {
+        self.pixel_centers = pixel_centers;
+        self
+    }
+
     /// intrapolate the imaginary curve between two existing curves.
     /// The endpoints of the intrapolated curve are defined by 'at_tail_curve1' and 'at_tail_curve2'.
     /// If 'at_tail_curve1' is true, the last point of 'curve1' is used as one of the endpoints of the curve, otherwise the first
     /// point (head) of 'curve1' is used. The same goes for 'at_tail_curve2' and 'curve2'.
+    /// The curve is inside the hole (boundary included). Endpoints off the hole (within the boundary
+    /// tolerance of path walking) are snapped to the closest points of the hole first.
     pub fn intrapolate_curve_between_curves(
         &self,
         mut curve1: PathF64,
@@ -79,55 +248,25 @@ impl<'a> CurveIntrapolator<'a> {
         if !at_tail_curve2 {
             curve2.path.reverse();
         }
-        let (curve1, curve2) = (curve1, curve2);
+
+        if self.config.fit_conics {
+            if let Some(spline) = self.calculate_conic_curve(&curve1, &curve2) {
+                let mut compound_path = CompoundPath::new();
+                compound_path.add_spline(spline);
+                return Some(compound_path);
+            }
+        }
 
         let (endpoint1, endpoint2) = (curve1[curve1.len() - 1], curve2[curve2.len() - 1]);
         let base_length = endpoint1.distance_to(endpoint2);
 
-        //# Curve smoothing
-        let outset_ratio = self.config.outset_ratio;
-        let min_segment_length = self.config.min_segment_length;
-        let max_iterations = self.config.smooth_max_iterations;
-        let corner_threshold = self.config.corner_threshold;
-
-        let (smooth_curve1, corners1) = Self::smooth_open_curve_iterative(
-            curve1,
-            outset_ratio,
-            min_segment_length,
-            max_iterations,
-            corner_threshold,
-        );
-        let (smooth_curve2, corners2) = Self::smooth_open_curve_iterative(
-            curve2,
-            outset_ratio,
-            min_segment_length,
-            max_iterations,
-            corner_threshold,
-        );
-
-        if self.debugger.should_draw_smoothed() {
-            self.debugger.draw_path_f64(&color1, &smooth_curve1);
-            self.debugger.draw_path_f64(&color2, &smooth_curve2);
-        }
-
-        //# Tail tangent approximation
-        let tail_tangent_n_points = self.config.tail_tangent_num_points;
-        let tail_weight_multiplier = self.config.tail_weight_multiplier;
-        let (smooth_curve1_len, smooth_curve2_len) = (smooth_curve1.len(), smooth_curve2.len());
-        let tail_tangent1 = Self::calculate_weighted_average_tangent_at_tail(
-            smooth_curve1,
-            &corners1,
-            std::cmp::min(tail_tangent_n_points, smooth_curve1_len),
-            base_length,
-            tail_weight_multiplier,
-        );
-        let tail_tangent2 = Self::calculate_weighted_average_tangent_at_tail(
-            smooth_curve2,
-            &corners2,
-            std::cmp::min(tail_tangent_n_points, smooth_curve2_len),
-            base_length,
-            tail_weight_multiplier,
-        );
+        let (tail_tangent1, tail_curvature1) =
+            self.calculate_tail_tangent(curve1.clone(), base_length, &color1);
+        let (tail_tangent2, tail_curvature2) =
+            self.calculate_tail_tangent(curve2.clone(), base_length, &color2);
+        let straight_tails = self.config.preserve_corners
+            && self.is_tail_straight(&curve1, tail_curvature1, base_length)
+            && self.is_tail_straight(&curve2, tail_curvature2, base_length);
 
         let (tail_tangent1, tail_tangent2) = if correct_tail_tangents {
             Self::correct_tail_tangents(endpoint1, tail_tangent1, endpoint2, tail_tangent2)
@@ -136,8 +275,8 @@ impl<'a> CurveIntrapolator<'a> {
         };
 
         if self.debugger.should_draw_tail_tangents() {
-            let tangent_visual_length =
-                (self.hole_rect.width() + self.hole_rect.height()) as f64 / 3.5;
+            let hole_rect = self.hole.rect();
+            let tangent_visual_length = (hole_rect.width() + hole_rect.height()) as f64 / 3.5;
             let to_point1 = endpoint1 + tail_tangent1.get_normalized() * tangent_visual_length;
             let to_point2 = endpoint2 + tail_tangent2.get_normalized() * tangent_visual_length;
             self.debugger.draw_line_f64(&color1, endpoint1, to_point1);
@@ -145,12 +284,384 @@ impl<'a> CurveIntrapolator<'a> {
         }
 
         //# Curve intrapolation
-        self.calculate_whole_curve(endpoint1, tail_tangent1, endpoint2, tail_tangent2)
+        let (endpoint1, endpoint2) = (
+            self.snap_into_hole(endpoint1),
+            self.snap_into_hole(endpoint2),
+        );
+        self.calculate_whole_curve(
+            (endpoint1, tail_tangent1, tail_curvature1),
+            (endpoint2, tail_tangent2, tail_curvature2),
+            straight_tails,
+        )
+    }
+
+    /// Intrapolate both edges of a stroke crossing the hole, keeping its width (roughly) constant.
+    /// 'edges1' are the 2 edges of the stroke on one side of the hole and 'edges2' those on the
+    /// other side, where 'edges1.0' continues into 'edges2.0' and 'edges1.1' into 'edges2.1'.
+    /// The first point (head) of each edge is its endpoint on the hole boundary.
+    /// The centerline is intrapolated between the midlines of the edges on both sides, and the edges
+    /// are its offset curves, whose width changes linearly along the centerline from that on one
+    /// side to that on the other. The edges are returned in the order of 'edges1'.
+    /// None is returned if the centerline cannot be intrapolated, the edges swap sides along the
+    /// centerline, or an edge is not inside the hole.
+    pub fn intrapolate_stroke_between_curves(
+        &self,
+        edges1: (&PathF64, &PathF64),
+        edges2: (&PathF64, &PathF64),
+        correct_tail_tangents: bool,
+    ) -> Option<(CompoundPath, CompoundPath)> {
+        if [edges1.0, edges1.1, edges2.0, edges2.1]
+            .iter()
+            .any(|edge| edge.len() < 2)
+        {
+            return None;
+        }
+        let ends = [edges1.0[0], edges1.1[0], edges2.0[0], edges2.1[0]];
+        let midline1 = Self::calculate_midline(edges1.0, edges1.1)?;
+        let midline2 = Self::calculate_midline(edges2.0, edges2.1)?;
+        let centerline = self.intrapolate_curve_between_curves(
+            midline1,
+            midline2,
+            true,
+            true,
+            correct_tail_tangents,
+        )?;
+        let centerline = flatten_compound_path(&centerline, NUM_CENTERLINE_SAMPLES_PER_BEZIER);
+        let len = centerline.len();
+        if len < 2 {
+            return None;
+        }
+
+        // Cumulative arc lengths and unit normals (rotated by +90 degrees) along the centerline
+        let mut arc_lengths = vec![0.0];
+        for pair in centerline.windows(2) {
+            arc_lengths.push(arc_lengths[arc_lengths.len() - 1] + pair[0].distance_to(pair[1]));
+        }
+        let total_length = arc_lengths[len - 1];
+        if total_length == 0.0 {
+            return None;
+        }
+        let normals: Vec<PointF64> = (0..len)
+            .map(|i| {
+                let tangent = (centerline[std::cmp::min(i + 1, len - 1)]
+                    - centerline[i.saturating_sub(1)])
+                .get_normalized();
+                PointF64::new(-tangent.y, tangent.x)
+            })
+            .collect();
+
+        // The signed offsets of the edges from the centerline at both ends
+        let side_offset = |end: PointF64, i: usize| (end - centerline[i]).dot(normals[i]);
+        let (from_offsets, to_offsets) = (
+            [side_offset(ends[0], 0), side_offset(ends[1], 0)],
+            [side_offset(ends[2], len - 1), side_offset(ends[3], len - 1)],
+        );
+        // Each edge must stay on the same side of the centerline
+        if (0..2).any(|k| from_offsets[k] * to_offsets[k] <= 0.0) {
+            return None;
+        }
+
+        let offset_edge = |k: usize| {
+            let (from_end, to_end) = (ends[k], ends[k + 2]);
+            let nominal_point = |i: usize| {
+                let ratio = arc_lengths[i] / total_length;
+                centerline[i]
+                    + normals[i] * (from_offsets[k] * (1.0 - ratio) + to_offsets[k] * ratio)
+            };
+            // Blend in the (small) errors at both ends so that the edge ends exactly at its endpoints
+            let (from_error, to_error) =
+                (from_end - nominal_point(0), to_end - nominal_point(len - 1));
+            let points: Vec<PointF64> = (0..len)
+                .map(|i| {
+                    let ratio = arc_lengths[i] / total_length;
+                    nominal_point(i) + from_error * (1.0 - ratio) + to_error * ratio
+                })
+                .collect();
+            if !self.is_polyline_inside_hole(&points) {
+                return None;
+            }
+            let mut compound_path = CompoundPath::new();
+            compound_path.add_path_f64(PathF64::from_points(points));
+            Some(compound_path)
+        };
+        Some((offset_edge(0)?, offset_edge(1)?))
+    }
+
+    /// Extend 'curve' into the hole as a straight stub that ends inside the hole.
+    /// This is used for endpoints that are left unmatched, e.g. where a line ends inside the hole.
+    /// If 'at_tail' is true, the stub starts from the last point of 'curve', otherwise the first point (head).
+    /// The stub follows the tail tangent and is sampled at unit intervals. It is 'stub_length_ratio' times
+    /// the shorter side of the hole long, but stops at the last sample the stub is inside the hole up
+    /// to (see is_curve_inside_hole()). An endpoint off the hole is snapped into it first.
+    /// None is returned if the stub is not inside the hole up to any sample other than the endpoint.
+    pub fn extend_curve_as_stub(&self, mut curve: PathF64, at_tail: bool) -> Option<CompoundPath> {
+        let color = Color::get_palette_color(1);
+
+        if curve.len() < 2 {
+            return None;
+        }
+        // The rest of the algorithm assumes at_tail = true
+        if !at_tail {
+            curve.path.reverse();
+        }
+        let endpoint = curve[curve.len() - 1];
+
+        let hole_rect = self.hole.rect();
+        let stub_length = self.config.stub_length_ratio
+            * std::cmp::min(hole_rect.width(), hole_rect.height()) as f64;
+
+        let tail_tangent = self
+            .calculate_tail_tangent(curve, stub_length, &color)
+            .0
+            .get_normalized();
+        if !tail_tangent.x.is_finite() || !tail_tangent.y.is_finite() {
+            return None;
+        }
+
+        let endpoint = self.snap_into_hole(endpoint);
+        let mut stub = PathF64::new();
+        stub.add(endpoint);
+        let mut step = 1.0;
+        while step <= stub_length {
+            let point = endpoint + tail_tangent * step;
+            if !self.is_curve_inside_hole(&[endpoint, point], MAX_CONTAINMENT_SPLIT_DEPTH) {
+                break;
+            }
+            stub.add(point);
+            step += 1.0;
+        }
+
+        if stub.len() < 2 {
+            return None;
+        }
+        let mut compound_path = CompoundPath::new();
+        compound_path.add_path_f64(stub);
+        Some(compound_path)
+    }
+
+    /// Extend 'curve' into the hole straight along its tail tangent until it meets the nearest of
+    /// 'curves', forming a T-junction there.
+    /// This is used for endpoints that are left unmatched where a line ends on another line.
+    /// If 'at_tail' is true, the extension starts from the last point of 'curve', otherwise the first point (head).
+    /// An endpoint off the hole is snapped into it first.
+    /// None is returned if the extension is not inside the hole (see is_curve_inside_hole()) up to
+    /// where it meets the nearest of 'curves'.
+    pub fn extend_curve_to_junction(
+        &self,
+        mut curve: PathF64,
+        at_tail: bool,
+        curves: &[CompoundPath],
+    ) -> Option<CompoundPath> {
+        let color = Color::get_palette_color(1);
+
+        if curve.len() < 2 {
+            return None;
+        }
+        // The rest of the algorithm assumes at_tail = true
+        if !at_tail {
+            curve.path.reverse();
+        }
+        let endpoint = curve[curve.len() - 1];
+
+        let hole_rect = self.hole.rect();
+        let base_length = self.config.stub_length_ratio
+            * std::cmp::min(hole_rect.width(), hole_rect.height()) as f64;
+        let tail_tangent = self
+            .calculate_tail_tangent(curve, base_length, &color)
+            .0
+            .get_normalized();
+        if !tail_tangent.x.is_finite() || !tail_tangent.y.is_finite() {
+            return None;
+        }
+
+        // The ray is long enough to cross the whole hole
+        let endpoint = self.snap_into_hole(endpoint);
+        let ray_length = (hole_rect.width() + hole_rect.height()) as f64;
+        let ray = (endpoint, endpoint + tail_tangent * ray_length);
+        let junction = curves
+            .iter()
+            .flat_map(|curve| {
+                let points = flatten_compound_path(curve, NUM_JUNCTION_SAMPLES_PER_BEZIER);
+                points
+                    .windows(2)
+                    .filter_map(|segment| segments_intersection(ray, (segment[0], segment[1])))
+                    .collect::<Vec<(PointF64, f64)>>()
+            })
+            .filter(|&(_, ratio)| ratio * ray_length >= 1.0)
+            .min_by(|(_, ratio1), (_, ratio2)| ratio1.total_cmp(ratio2))
+            .map(|(point, _)| point)?;
+
+        if !self.is_curve_inside_hole(&[endpoint, junction], MAX_CONTAINMENT_SPLIT_DEPTH) {
+            return None;
+        }
+        let mut compound_path = CompoundPath::new();
+        compound_path.add_path_f64(PathF64::from_points(vec![endpoint, junction]));
+        Some(compound_path)
     }
 }
 
 // Helper functions
 impl<'a> CurveIntrapolator<'a> {
+    /// Fit the ellipse to the points of 'curve1' and 'curve2', and approximate its arc from the tail of
+    /// 'curve1' to the tail of 'curve2', continuing both tails, with cubic bezier curves.
+    /// The arc is deformed slightly so that it ends exactly at the tails.
+    /// None is returned if there is no such ellipse, the fit residual is above 'conic_max_residual',
+    /// the arc does not continue both tails, or it is not inside the hole.
+    fn calculate_conic_curve(&self, curve1: &PathF64, curve2: &PathF64) -> Option<Spline> {
+        let (len1, len2) = (curve1.len(), curve2.len());
+        if len1 < 2 || len2 < 2 {
+            return None;
+        }
+        let points: Vec<PointF64> = curve1.iter().chain(curve2.iter()).copied().collect();
+        let ellipse = Ellipse::fit(&points)?;
+        let residual = ellipse.residual(&points);
+        if residual.is_nan() || residual > self.config.conic_max_residual {
+            return None;
+        }
+
+        let (endpoint1, endpoint2) = (curve1[len1 - 1], curve2[len2 - 1]);
+        let (direction1, direction2) = (endpoint1 - curve1[len1 - 2], endpoint2 - curve2[len2 - 2]);
+        let from = ellipse.parameter_of(endpoint1);
+        let to = ellipse.parameter_of(endpoint2);
+        // Go around the ellipse in the direction of the first tail
+        let full_turn = 2.0 * std::f64::consts::PI;
+        let sweep = if ellipse.derivative_at(from).dot(direction1) > 0.0 {
+            (to - from).rem_euclid(full_turn)
+        } else {
+            (to - from).rem_euclid(full_turn) - full_turn
+        };
+        if sweep.abs() <= f64::EPSILON || sweep.abs() >= full_turn - f64::EPSILON {
+            return None;
+        }
+        // The arc must arrive at the second tail against its direction
+        let arrival = ellipse.derivative_at(from + sweep) * sweep.signum();
+        if arrival.dot(direction2) >= 0.0 {
+            return None;
+        }
+
+        let spline = ellipse.arc_to_spline((from, sweep), (endpoint1, endpoint2));
+        if !self.is_spline_inside_hole(&spline) {
+            return None;
+        }
+        Some(spline)
+    }
+
+    /// The midline between 'edge1' and 'edge2', whose first points (heads) are their endpoints:
+    /// NUM_MIDLINE_POINTS midpoints of the points at the same arc lengths from the heads, up to half
+    /// the length of the shorter edge, so that the midline does not fold back where both edges are
+    /// walked around the same end of the stroke. The midline is returned with its tail (last point)
+    /// between the heads.
+    /// None is returned if any of the edges has no length, or the midline is closed.
+    fn calculate_midline(edge1: &PathF64, edge2: &PathF64) -> Option<PathF64> {
+        let length = |edge: &PathF64| {
+            edge.path
+                .windows(2)
+                .map(|pair| pair[0].distance_to(pair[1]))
+                .sum::<f64>()
+        };
+        let max_length = length(edge1).min(length(edge2)) / 2.0;
+        if max_length == 0.0 || !max_length.is_finite() {
+            return None;
+        }
+        // The point at 'distance' from the head of 'edge' along it
+        let point_at_distance = |edge: &PathF64, distance: f64| {
+            let mut remaining = distance;
+            for pair in edge.path.windows(2) {
+                let segment_length = pair[0].distance_to(pair[1]);
+                if remaining <= segment_length && segment_length > 0.0 {
+                    return pair[0] + (pair[1] - pair[0]) * (remaining / segment_length);
+                }
+                remaining -= segment_length;
+            }
+            edge[edge.len() - 1]
+        };
+        let points: Vec<PointF64> = (0..NUM_MIDLINE_POINTS)
+            .rev()
+            .map(|i| {
+                let distance = max_length * i as f64 / (NUM_MIDLINE_POINTS - 1) as f64;
+                calculate_midpoint(
+                    point_at_distance(edge1, distance),
+                    point_at_distance(edge2, distance),
+                )
+            })
+            .collect();
+        // A closed midline has no tail
+        if points[0] == points[NUM_MIDLINE_POINTS - 1] {
+            return None;
+        }
+        Some(PathF64::from_points(points))
+    }
+
+    /// Smooth 'curve' and approximate the tangent and signed curvature at its tail (last point).
+    /// See calculate_weighted_average_tangent_at_tail() for the definition of 'base_length'.
+    fn calculate_tail_tangent(
+        &self,
+        curve: PathF64,
+        base_length: f64,
+        color: &Color,
+    ) -> (PointF64, f64) {
+        if self.config.tail_tangent_estimator == TailTangentEstimator::LeastSquares {
+            if let Some(fit) = fit_tail_tangent(&self.find_points_near_tail(&curve)) {
+                return fit;
+            }
+        }
+
+        //# Curve smoothing
+        let (smooth_curve, corners) = Self::smooth_open_curve_iterative(
+            curve,
+            self.config.outset_ratio,
+            self.config.min_segment_length,
+            self.config.smooth_max_iterations,
+            self.config.corner_threshold,
+        );
+
+        if self.debugger.should_draw_smoothed() {
+            self.debugger.draw_path_f64(color, &smooth_curve);
+        }
+
+        //# Tail tangent and curvature approximation
+        let n = std::cmp::min(self.config.tail_tangent_num_points, smooth_curve.len());
+        let curvature = Self::calculate_curvature_at_tail(&smooth_curve, &corners, n, base_length);
+        let tangent = Self::calculate_weighted_average_tangent_at_tail(
+            smooth_curve,
+            &corners,
+            n,
+            base_length,
+            self.config.tail_weight_multiplier,
+        );
+        (tangent, curvature)
+    }
+
+    /// The points within 'tail_fit_length' (see CurveIntrapolatorConfig) of arc length before the
+    /// tail of 'curve', ending at the tail, taken from the raw segment whose head is the tail if any,
+    /// or from 'curve' otherwise.
+    fn find_points_near_tail(&self, curve: &PathF64) -> Vec<PointF64> {
+        let tail = match curve.path.last() {
+            Some(&tail) => tail,
+            None => return vec![],
+        };
+        let reversed_curve: Vec<PointF64> = match self
+            .raw_segments
+            .iter()
+            .find(|segment| segment.path.first().map(|head| head.to_point_f64()) == Some(tail))
+        {
+            Some(segment) => segment.iter().map(|p| p.to_point_f64()).collect(),
+            None => curve.iter().rev().copied().collect(),
+        };
+
+        let mut points = vec![tail];
+        let mut length = 0.0;
+        for pair in reversed_curve.windows(2) {
+            if length >= self.config.tail_fit_length {
+                break;
+            }
+            length += pair[0].distance_to(pair[1]);
+            points.push(pair[1]);
+        }
+        points.reverse();
+        points
+    }
+
     /// Apply the 4-point scheme subdivision on 'path' in a convolutional manner iteratively, preserving corners.
     /// The corners of the smoothed path are returned as a bool mask.
     /// Segments (at any point during iteration) shorter than 'min_segment_length' are not further subdivided.
@@ -244,7 +755,8 @@ impl<'a> CurveIntrapolator<'a> {
     /// lengths is at most base_length, or the last points until a corner is seen, whichever is the smallest,
     /// are taken into account.
     /// The weights are stronger towards the tail, this is specified by 'tail_weight_multiplier'.
-    /// The behavior is undefined unless path is open and 1 < n <= path.len().
+    /// The zero vector is returned unless 1 < n and 1 < path.len() (n is capped at path.len()).
+    /// The behavior is undefined unless path is open.
     fn calculate_weighted_average_tangent_at_tail(
         path: PathF64,
         corners: &[bool],
@@ -252,9 +764,10 @@ impl<'a> CurveIntrapolator<'a> {
         base_length: f64,
         tail_weight_multiplier: f64,
     ) -> PointF64 {
-        let len = path.len();
-        assert!(1 < n);
-        assert!(n <= len);
+        let n = std::cmp::min(n, std::cmp::min(path.len(), corners.len()));
+        if n < 2 {
+            return PointF64::default();
+        }
 
         let mut tangent_acc = PointF64::default();
         let mut length_acc = 0.0;
@@ -280,6 +793,52 @@ impl<'a> CurveIntrapolator<'a> {
         tangent_acc.get_normalized()
     }
 
+    /// Calculate the signed curvature at the tail of 'path', when walked towards the tail, as the
+    /// curvature of the circle through the tail, the farthest point taken into account and the point
+    /// halfway between them along 'path'.
+    /// The points taken into account are the same as in calculate_weighted_average_tangent_at_tail().
+    /// A positive curvature turns as the angle given by atan2() increases.
+    /// 0.0 is returned if fewer than 3 points are taken into account, or they are collinear.
+    fn calculate_curvature_at_tail(
+        path: &PathF64,
+        corners: &[bool],
+        n: usize,
+        base_length: f64,
+    ) -> f64 {
+        let n = std::cmp::min(n, std::cmp::min(path.len(), corners.len()));
+        let rev_points: Vec<PointF64> = path.path.iter().rev().take(n).copied().collect();
+        let rev_corners: Vec<&bool> = corners.iter().rev().take(n).collect();
+
+        let mut num_points = 1;
+        let mut length_acc = 0.0;
+        for (i, point_pair) in rev_points.windows(2).enumerate() {
+            // Stop at first corner from tail
+            if *rev_corners[i] {
+                break;
+            }
+            num_points += 1;
+            length_acc += point_pair[0].distance_to(point_pair[1]);
+            if length_acc >= base_length {
+                break;
+            }
+        }
+        if num_points < 3 {
+            return 0.0;
+        }
+
+        let (far, middle, tail) = (
+            rev_points[num_points - 1],
+            rev_points[(num_points - 1) / 2],
+            rev_points[0],
+        );
+        let (v1, v2) = (middle - far, tail - middle);
+        let denominator = v1.norm() * v2.norm() * far.distance_to(tail);
+        if denominator == 0.0 {
+            return 0.0;
+        }
+        2.0 * (v1.x * v2.y - v1.y * v2.x) / denominator
+    }
+
     /// Make sure the two tangents do not bend outwards
     fn correct_tail_tangents(
         point1: PointF64,
@@ -308,13 +867,42 @@ impl<'a> CurveIntrapolator<'a> {
         (tangent1, tangent2)
     }
 
+    /// Calculate the curve between 2 tails, each given as its point, tangent pointing into the hole
+    /// and signed curvature when walked towards the tail.
+    /// If 'straight_tails' is true, both tails are extended to their intersection where possible
+    /// (see calculate_corner_curve()).
     fn calculate_whole_curve(
         &self,
-        from_point: PointF64,
-        from_tangent: PointF64,
-        to_point: PointF64,
-        to_tangent: PointF64,
+        (from_point, from_tangent, from_curvature): (PointF64, PointF64, f64),
+        (to_point, to_tangent, to_curvature): (PointF64, PointF64, f64),
+        straight_tails: bool,
     ) -> Option<CompoundPath> {
+        if straight_tails {
+            if let Some(polyline) =
+                self.calculate_corner_curve(from_point, from_tangent, to_point, to_tangent)
+            {
+                let mut compound_path = CompoundPath::new();
+                compound_path.add_path_f64(polyline);
+                return Some(compound_path);
+            }
+        }
+
+        let spline = match self.config.curve_model {
+            CurveModel::Bezier => None,
+            CurveModel::Clothoid => {
+                self.calculate_clothoid_curve(from_point, from_tangent, to_point, to_tangent)
+            }
+            CurveModel::CurvatureContinuous => self.calculate_curvature_continuous_curve(
+                (from_point, from_tangent, from_curvature),
+                (to_point, to_tangent, to_curvature),
+            ),
+        };
+        if let Some(spline) = spline {
+            let mut compound_path = CompoundPath::new();
+            compound_path.add_spline(spline);
+            return Some(compound_path);
+        }
+
         let intersection_result = calculate_intersection(
             from_point,
             from_point + from_tangent,
@@ -383,6 +971,129 @@ impl<'a> CurveIntrapolator<'a> {
         Some(compound_path)
     }
 
+    /// Fit the clothoid from 'from_point' to 'to_point', where both tangents point into the hole,
+    /// and approximate it with cubic bezier curves.
+    /// None is returned if there is no such clothoid or it is not inside the hole.
+    fn calculate_clothoid_curve(
+        &self,
+        from_point: PointF64,
+        from_tangent: PointF64,
+        to_point: PointF64,
+        to_tangent: PointF64,
+    ) -> Option<Spline> {
+        // The curve arrives at 'to_point' against the direction of its tail tangent
+        let clothoid = Clothoid::fit(from_point, from_tangent, to_point, -to_tangent)?;
+
+        // An upper bound of the total turning angle
+        let turning_angle = clothoid.curvature.abs() * clothoid.length
+            + clothoid.curvature_rate.abs() * clothoid.length * clothoid.length / 2.0;
+        let num_parts = std::cmp::max(
+            (clothoid.length / MAX_PART_LENGTH).ceil() as usize,
+            (turning_angle / MAX_PART_TURNING_ANGLE).ceil() as usize,
+        );
+        let spline = clothoid.to_spline(num_parts);
+        if !self.is_spline_inside_hole(&spline) {
+            return None;
+        }
+        Some(spline)
+    }
+
+    /// Whether the tail (last point) of 'curve' is straight: there is no corner (see
+    /// find_corners_open_path()) among its points taken into account, and its signed 'curvature'
+    /// is at most 'straight_tail_max_curvature' in magnitude (see CurveIntrapolatorConfig).
+    /// The points taken into account are the last 'tail_tangent_num_points' ones, up to the first
+    /// one 'base_length' of arc length away from the tail, as in calculate_curvature_at_tail().
+    /// The corners are checked as well because the curvature is only estimated up to the first
+    /// corner from the tail.
+    fn is_tail_straight(&self, curve: &PathF64, curvature: f64, base_length: f64) -> bool {
+        if curvature.abs() > self.config.straight_tail_max_curvature {
+            return false;
+        }
+        let n = std::cmp::min(self.config.tail_tangent_num_points, curve.len());
+        let rev_points: Vec<PointF64> = curve.path.iter().rev().take(n).copied().collect();
+
+        let mut num_points = 1;
+        let mut length_acc = 0.0;
+        for point_pair in rev_points.windows(2) {
+            num_points += 1;
+            length_acc += point_pair[0].distance_to(point_pair[1]);
+            if length_acc >= base_length {
+                break;
+            }
+        }
+        let tail = PathF64::from_points(rev_points[..num_points].to_vec());
+        !find_corners_open_path(&tail, self.config.corner_threshold)
+            .into_iter()
+            .any(|is_corner| is_corner)
+    }
+
+    /// Extend the straight tails at 'from_point' and 'to_point' along their tangents, which point
+    /// into the hole, to their intersection, forming a sharp corner.
+    /// None is returned if the tangent lines do not intersect ahead of both tails, or the polyline
+    /// is not inside the hole.
+    fn calculate_corner_curve(
+        &self,
+        from_point: PointF64,
+        from_tangent: PointF64,
+        to_point: PointF64,
+        to_tangent: PointF64,
+    ) -> Option<PathF64> {
+        let corner = match calculate_intersection(
+            from_point,
+            from_point + from_tangent,
+            to_point,
+            to_point + to_tangent,
+        ) {
+            LineIntersectionResult::Intersect(corner) => corner,
+            _ => return None,
+        };
+        if !corner.x.is_finite() || !corner.y.is_finite() {
+            return None;
+        }
+        if (corner - from_point).dot(from_tangent) <= 0.0
+            || (corner - to_point).dot(to_tangent) <= 0.0
+        {
+            return None;
+        }
+
+        let points = vec![from_point, corner, to_point];
+        if !self.is_polyline_inside_hole(&points) {
+            return None;
+        }
+        Some(PathF64::from_points(points))
+    }
+
+    /// Fit the quintic curve matching the tangents and curvatures of both tails (see
+    /// calculate_whole_curve()) and approximate it with cubic bezier curves.
+    /// None is returned if there is no such curve or it is not inside the hole.
+    fn calculate_curvature_continuous_curve(
+        &self,
+        (from_point, from_tangent, from_curvature): (PointF64, PointF64, f64),
+        (to_point, to_tangent, to_curvature): (PointF64, PointF64, f64),
+    ) -> Option<Spline> {
+        // The curve arrives at 'to_point' against the direction of its tail tangent, which also
+        // negates the curvature
+        let curve = QuinticHermite::fit(
+            from_point,
+            from_tangent,
+            from_curvature,
+            to_point,
+            -to_tangent,
+            -to_curvature,
+        )?;
+
+        let (length, turning_angle) = curve.measure();
+        let num_parts = std::cmp::max(
+            (length / MAX_PART_LENGTH).ceil() as usize,
+            (turning_angle / MAX_PART_TURNING_ANGLE).ceil() as usize,
+        );
+        let spline = curve.to_spline(num_parts);
+        if !self.is_spline_inside_hole(&spline) {
+            return None;
+        }
+        Some(spline)
+    }
+
     fn cut_two_curves_and_insert(
         &self,
         compound_path: &mut CompoundPath,
@@ -426,6 +1137,8 @@ impl<'a> CurveIntrapolator<'a> {
 
     /// Calculate the cubic bezier curve from 'from_point' to 'to_point' with the provided tangents.
     /// 'intersection_result' is only to avoid unnecessary recalculation.
+    /// The curve is kept inside the hole by construct_curve_inside_hole().
+    /// None is returned if no such curve is inside the hole, e.g. where an end is not.
     fn calculate_part_curve(
         &self,
         from_point: PointF64,
@@ -434,13 +1147,6 @@ impl<'a> CurveIntrapolator<'a> {
         to_tangent: PointF64,
         whole_intersection_result: LineIntersectionResult,
     ) -> Option<Spline> {
-        let retract_ratio = self.config.control_points_retract_ratio;
-        let retract_predicate = |cp: PointF64| {
-            self.hole_rect
-                .have_point_on_boundary_or_inside(cp.to_point_i32(), 1)
-        };
-        let retract_max_n = Some(1000);
-
         let (control_point1, control_point2) = match whole_intersection_result {
             LineIntersectionResult::Intersect(intersection) => self
                 .evaluate_control_points_with_intersection(
@@ -451,7 +1157,16 @@ impl<'a> CurveIntrapolator<'a> {
                     intersection,
                 ),
             LineIntersectionResult::Parallel => (from_point + from_tangent, to_point + to_tangent),
-            LineIntersectionResult::Coincidence => panic!("Part curves do not handle coincidence."),
+            LineIntersectionResult::Coincidence => {
+                // Just a straight line
+                self.evaluate_control_points_with_intersection(
+                    from_point,
+                    from_tangent,
+                    to_point,
+                    to_tangent,
+                    calculate_midpoint(from_point, to_point),
+                )
+            }
             LineIntersectionResult::None => {
                 // Whole curve has been divided -> recalculate intersection
                 let intersection_result = calculate_intersection(
@@ -472,33 +1187,161 @@ impl<'a> CurveIntrapolator<'a> {
                     LineIntersectionResult::Parallel => {
                         (from_point + from_tangent, to_point + to_tangent)
                     }
-                    LineIntersectionResult::Coincidence => {
-                        panic!("Part curves do not handle coincidence.")
-                    }
+                    LineIntersectionResult::Coincidence => self
+                        .evaluate_control_points_with_intersection(
+                            from_point,
+                            from_tangent,
+                            to_point,
+                            to_tangent,
+                            calculate_midpoint(from_point, to_point),
+                        ),
                     LineIntersectionResult::None => return None,
                 }
             }
         };
 
-        let (control_point1, control_point2) = (
-            retract_point(
-                control_point1,
-                from_point,
-                retract_ratio,
-                retract_predicate,
-                retract_max_n,
-            ),
-            retract_point(
-                control_point2,
-                to_point,
-                retract_ratio,
-                retract_predicate,
-                retract_max_n,
-            ),
-        );
-        let mut spline = Spline::new(from_point);
-        spline.add(control_point1, control_point2, to_point);
-        Some(spline)
+        // Degenerate tangents (e.g. from coinciding points) cannot produce a curve
+        if [from_point, control_point1, control_point2, to_point]
+            .iter()
+            .any(|p| !p.x.is_finite() || !p.y.is_finite())
+        {
+            return None;
+        }
+        self.construct_curve_inside_hole(
+            [from_point, control_point1, control_point2, to_point],
+            MAX_CURVE_SUBDIVISIONS,
+        )
+    }
+
+    /// The cubic bezier curve with 'control_points' if it is proven to be inside the hole (see
+    /// is_curve_inside_hole()). Otherwise, it is split in half (up to 'depth' times) where its
+    /// midpoint is inside the hole, and each half is constructed the same way, so that only the
+    /// parts leaving the hole are re-fitted. These parts are re-fitted by retracting their control
+    /// points towards their ends along the tangents, by 'control_points_retract_ratio' each time,
+    /// which keeps the tangents (hence the continuity at the splits). The straight line between the
+    /// ends is the last resort. None is returned if even that is not inside the hole.
+    fn construct_curve_inside_hole(
+        &self,
+        control_points: [PointF64; 4],
+        depth: usize,
+    ) -> Option<Spline> {
+        let [from_point, control_point1, control_point2, to_point] = control_points;
+        if self.is_curve_inside_hole(&control_points, MAX_CONTAINMENT_SPLIT_DEPTH) {
+            let mut spline = Spline::new(from_point);
+            spline.add(control_point1, control_point2, to_point);
+            return Some(spline);
+        }
+
+        let (first, second) = split_bezier_in_half(&control_points);
+        if depth > 0 && self.is_curve_inside_hole(&first[3..], 0) {
+            let halves = [first, second].map(|half| [half[0], half[1], half[2], half[3]]);
+            let mut spline = self.construct_curve_inside_hole(halves[0], depth - 1)?;
+            let second_spline = self.construct_curve_inside_hole(halves[1], depth - 1)?;
+            second_spline
+                .get_control_points()
+                .into_iter()
+                .for_each(|points| spline.add(points[1], points[2], points[3]));
+            return Some(spline);
+        }
+
+        let retract_ratio = self.config.control_points_retract_ratio;
+        let mut handles = (control_point1, control_point2);
+        for retraction in 1..=MAX_HANDLE_RETRACTIONS {
+            handles = if retraction == MAX_HANDLE_RETRACTIONS {
+                (from_point, to_point)
+            } else {
+                (
+                    calculate_in_between_point(handles.0, from_point, retract_ratio),
+                    calculate_in_between_point(handles.1, to_point, retract_ratio),
+                )
+            };
+            if self.is_curve_inside_hole(
+                &[from_point, handles.0, handles.1, to_point],
+                MAX_CONTAINMENT_SPLIT_DEPTH,
+            ) {
+                let mut spline = Spline::new(from_point);
+                spline.add(handles.0, handles.1, to_point);
+                return Some(spline);
+            }
+        }
+        None
+    }
+
+    /// Whether the curve with 'control_points' (the ends of a line segment, or the control points
+    /// of a bezier curve) is inside the hole (boundary included). By the convex hull property, it is
+    /// if the bounding box of its control points is covered by the hole. Otherwise, it is split in
+    /// half up to 'depth' times to find out. The result is conservative: false may be returned for
+    /// curves running (nearly) along the hole boundary. False is returned for non-finite points.
+    fn is_curve_inside_hole(&self, control_points: &[PointF64], depth: usize) -> bool {
+        let offset = self.corner_offset();
+        let covers = |points: &[PointF64]| {
+            let (left, top, right, bottom) = bounds_of_points(points);
+            self.hole.covers_box(
+                left + offset.x,
+                top + offset.y,
+                right + offset.x,
+                bottom + offset.y,
+            )
+        };
+        if covers(control_points) {
+            return true;
+        }
+        // The ends are on the curve
+        let ends = [control_points[0], control_points[control_points.len() - 1]];
+        if depth == 0 || !ends.iter().all(|&end| covers(&[end])) {
+            return false;
+        }
+        let (first, second) = split_bezier_in_half(control_points);
+        self.is_curve_inside_hole(&first, depth - 1)
+            && self.is_curve_inside_hole(&second, depth - 1)
+    }
+
+    /// Whether every cubic bezier curve of 'spline' is inside the hole (see is_curve_inside_hole()).
+    fn is_spline_inside_hole(&self, spline: &Spline) -> bool {
+        spline
+            .get_control_points()
+            .into_iter()
+            .all(|points| self.is_curve_inside_hole(points, MAX_CONTAINMENT_SPLIT_DEPTH))
+    }
+
+    /// Whether every segment of the polyline 'points' is inside the hole (see is_curve_inside_hole()).
+    fn is_polyline_inside_hole(&self, points: &[PointF64]) -> bool {
+        points
+            .windows(2)
+            .all(|segment| self.is_curve_inside_hole(segment, MAX_CONTAINMENT_SPLIT_DEPTH))
+    }
+
+    /// The offset from the points of the curves to image coordinates, where the points at integer
+    /// coordinates are the top-left corners of pixels.
+    fn corner_offset(&self) -> PointF64 {
+        if self.pixel_centers {
+            PointF64::new(0.5, 0.5)
+        } else {
+            PointF64::default()
+        }
+    }
+
+    /// The closest point to 'p' in the hole (boundary included), searched among the hole pixels
+    /// within 2 pixels of 'p', as the endpoints of walked segments are at most that far off the hole.
+    /// 'p' is returned as it is if no such pixel is found.
+    fn snap_into_hole(&self, p: PointF64) -> PointF64 {
+        if !p.x.is_finite() || !p.y.is_finite() {
+            return p;
+        }
+        let offset = self.corner_offset();
+        let corner = p + offset;
+        let (x0, y0) = (corner.x.floor() as i32, corner.y.floor() as i32);
+        (y0 - 2..=y0 + 2)
+            .flat_map(|y| (x0 - 2..=x0 + 2).map(move |x| PointI32::new(x, y)))
+            .filter(|&pixel| self.hole.have_pixel_inside(pixel))
+            .map(|pixel| {
+                PointF64::new(
+                    corner.x.clamp(pixel.x as f64, (pixel.x + 1) as f64),
+                    corner.y.clamp(pixel.y as f64, (pixel.y + 1) as f64),
+                )
+            })
+            .min_by(|q1, q2| corner.distance_to(*q1).total_cmp(&corner.distance_to(*q2)))
+            .map_or(p, |closest| closest - offset)
     }
 
     fn evaluate_control_points_with_intersection(
@@ -529,3 +1372,144 @@ impl<'a> CurveIntrapolator<'a> {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debugger::DummyDebugger;
+    use visioncortex::{BinaryImage, BoundingRect, CompoundPathElement};
+
+    #[test]
+    fn tail_curvature_of_circular_arcs() {
+        // GIVEN arcs of a circle of radius 20, walked counterclockwise and clockwise (by atan2())
+        let point_at = |degrees: f64| {
+            let radians = degrees.to_radians();
+            PointF64::new(radians.cos(), radians.sin()) * 20.0
+        };
+        let counterclockwise =
+            PathF64::from_points((0..=12).map(|i| point_at(i as f64 * 5.0)).collect());
+        let mut clockwise = counterclockwise.clone();
+        clockwise.path.reverse();
+        let corners = vec![false; counterclockwise.len()];
+
+        // WHEN
+        let curvature1 =
+            CurveIntrapolator::calculate_curvature_at_tail(&counterclockwise, &corners, 9, 100.0);
+        let curvature2 =
+            CurveIntrapolator::calculate_curvature_at_tail(&clockwise, &corners, 9, 100.0);
+        let straight = PathF64::from_points((0..5).map(|i| PointF64::new(i as f64, 0.0)).collect());
+        let curvature3 =
+            CurveIntrapolator::calculate_curvature_at_tail(&straight, &corners[..5], 5, 100.0);
+
+        // THEN
+        assert!((curvature1 - 0.05).abs() < 1e-9);
+        assert!((curvature2 + 0.05).abs() < 1e-9);
+        assert!(curvature3.abs() < 1e-9);
+    }
+
+    #[test]
+    fn tail_with_corner_before_endpoint_is_not_straight() {
+        // GIVEN a straight tail, and a tail turning by 90 degrees right before its endpoint, whose
+        // curvature is estimated as 0 since the estimation stops at the corner
+        let hole = Hole::from_rect(BoundingRect::new_x_y_w_h(10, 10, 20, 20));
+        let debugger = DummyDebugger;
+        let intrapolator =
+            CurveIntrapolator::new(CurveIntrapolatorConfig::default(), &hole, &debugger);
+        let straight = PathF64::from_points((0..5).map(|i| PointF64::new(0.0, i as f64)).collect());
+        let mut turning = straight.clone();
+        turning.add(PointF64::new(4.0, 4.0));
+
+        // WHEN
+        let is_straight1 = intrapolator.is_tail_straight(&straight, 0.0, 100.0);
+        let is_straight2 = intrapolator.is_tail_straight(&turning, 0.0, 100.0);
+
+        // THEN
+        assert!(is_straight1);
+        assert!(!is_straight2);
+    }
+
+    #[test]
+    fn corner_behind_the_tails_is_rejected() {
+        // GIVEN tails whose tangent lines intersect inside the hole, ahead of both tails or behind
+        let hole = Hole::from_rect(BoundingRect::new_x_y_w_h(10, 10, 20, 20));
+        let debugger = DummyDebugger;
+        let intrapolator =
+            CurveIntrapolator::new(CurveIntrapolatorConfig::default(), &hole, &debugger);
+        let (from_point, to_point) = (PointF64::new(15.0, 20.0), PointF64::new(25.0, 20.0));
+
+        // WHEN
+        let ahead = intrapolator.calculate_corner_curve(
+            from_point,
+            PointF64::new(1.0, 1.0),
+            to_point,
+            PointF64::new(-1.0, 1.0),
+        );
+        let behind = intrapolator.calculate_corner_curve(
+            from_point,
+            PointF64::new(-1.0, 1.0),
+            to_point,
+            PointF64::new(1.0, 1.0),
+        );
+
+        // THEN
+        assert_eq!(ahead.unwrap()[1], PointF64::new(20.0, 25.0));
+        assert!(behind.is_none());
+    }
+
+    #[test]
+    fn curve_bulging_out_of_hole_is_kept_inside() {
+        // GIVEN 2 curves both leaving the top of a hole upwards, which a smooth cap between them
+        // would bulge out of
+        let hole = Hole::from_rect(BoundingRect::new_x_y_w_h(10, 10, 20, 20));
+        let debugger = DummyDebugger;
+        let intrapolator =
+            CurveIntrapolator::new(CurveIntrapolatorConfig::default(), &hole, &debugger);
+        let vertical = |x: f64| {
+            PathF64::from_points((0..=6).map(|i| PointF64::new(x, 16.0 - i as f64)).collect())
+        };
+
+        // WHEN
+        let curve = intrapolator
+            .intrapolate_curve_between_curves(vertical(12.0), vertical(28.0), true, true, false)
+            .unwrap();
+
+        // THEN every control point, hence the whole curve, is inside the hole
+        for path in curve.iter() {
+            let spline = match path {
+                CompoundPathElement::Spline(spline) => spline,
+                _ => panic!("expected a spline"),
+            };
+            assert!(spline
+                .points
+                .iter()
+                .all(|p| { (10.0..=30.0).contains(&p.x) && (10.0..=30.0).contains(&p.y) }));
+        }
+    }
+
+    #[test]
+    fn stub_stops_before_a_pixel_missing_between_its_samples() {
+        // GIVEN a hole missing the pixel (11, 12), which a diagonal stub from (10, 10.2) crosses
+        // between its samples at unit intervals
+        let mut mask = BinaryImage::new_w_h(40, 40);
+        (5..30).for_each(|y| (5..30).for_each(|x| mask.set_pixel(x, y, true)));
+        mask.set_pixel(11, 12, false);
+        let hole = Hole::from_mask(&mask);
+        let debugger = DummyDebugger;
+        let intrapolator =
+            CurveIntrapolator::new(CurveIntrapolatorConfig::default(), &hole, &debugger);
+        let diagonal = PathF64::from_points(
+            (0..=6)
+                .rev()
+                .map(|i| PointF64::new(10.0 - i as f64, 10.2 - i as f64))
+                .collect(),
+        );
+
+        // WHEN
+        let stub = intrapolator.extend_curve_as_stub(diagonal, true).unwrap();
+
+        // THEN the stub stops before the missing pixel
+        let points = flatten_compound_path(&stub, 1);
+        assert!(points.len() >= 2);
+        assert!(points.iter().all(|p| p.x < 11.8));
+    }
+}
@@ -0,0 +1,86 @@
+use std::fmt;
+
+/// The reasons shape completion can fail.
+#[derive(Debug)]
+pub enum CompletionError {
+    /// Endpoints must be paired up, but an odd number of them is found on the hole boundary.
+    OddNumberOfEndpoints(usize),
+    /// Matching is attempted without any endpoints.
+    NoEndpoints,
+    /// A walked path segment is too short to have a direction at its endpoint.
+    DegenerateSegment,
+    /// A path segment cannot be walked from an endpoint.
+    PathWalkingFailed(&'static str),
+    /// No matching can have all of its curves intrapolated.
+    NoValidMatching,
+    /// Every matching whose curves are intrapolated has some curves intersecting each other.
+    AllMatchingsIntersect,
+    /// Every matching whose curves are intrapolated has some curves crossing the visible contours
+    /// outside the hole.
+    AllMatchingsCrossContours,
+    /// The hole is not (entirely) inside the image.
+    HoleOutOfBounds,
+    /// A configuration value is out of its valid range.
+    InvalidConfiguration(&'static str),
+    /// Completion fails in the original hole as well as in all of its expansions.
+    ExpansionExhausted {
+        /// Why completion fails in the original hole.
+        cause: Box<CompletionError>,
+        /// Why completion fails in each expansion, in the order they were tried.
+        expansion_errors: Vec<CompletionError>,
+    },
+}
+
+impl fmt::Display for CompletionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OddNumberOfEndpoints(n) => write!(
+                f,
+                "There must be an even number of path segments, but {} are found.",
+                n
+            ),
+            Self::NoEndpoints => write!(f, "There must be some match items."),
+            Self::DegenerateSegment => {
+                write!(
+                    f,
+                    "Every path segment must have at least 2 distinct points."
+                )
+            }
+            Self::PathWalkingFailed(reason) => write!(f, "Path walking failed: {}", reason),
+            Self::NoValidMatching => write!(f, "Still not intrapolated."),
+            Self::AllMatchingsIntersect => {
+                write!(
+                    f,
+                    "Still not intrapolated: all matchings have intersecting curves."
+                )
+            }
+            Self::AllMatchingsCrossContours => {
+                write!(
+                    f,
+                    "Still not intrapolated: all matchings have curves crossing the visible contours."
+                )
+            }
+            Self::HoleOutOfBounds => write!(f, "Hole out of range."),
+            Self::InvalidConfiguration(reason) => write!(f, "Invalid configuration: {}", reason),
+            Self::ExpansionExhausted {
+                cause,
+                expansion_errors,
+            } => {
+                writeln!(f, "{}", cause)?;
+                for error in expansion_errors.iter() {
+                    writeln!(f, "{}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompletionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::ExpansionExhausted { cause, .. } => Some(cause.as_ref()),
+            _ => None,
+        }
+    }
+}
@@ -5,15 +5,28 @@ use std::{
 };
 
 use flo_curves::{bezier::Curve, BezierCurve, Coord2, Coordinate2D};
-use visioncortex::{BinaryImage, BoundingRect, CompoundPath, PointF64, PointI32, PointUsize};
+use visioncortex::{BinaryImage, CompoundPath, PointF64, PointI32, PointUsize};
 
-#[derive(Clone, Copy, PartialEq)]
+use crate::{
+    error::CompletionError,
+    fragment::Fragment,
+    geo::{distance_to_segment, flatten_compound_path},
+    hole::Hole,
+};
+
+/// The number of points each bezier curve is flattened into when measuring distances to curves.
+const NUM_SAMPLES_PER_BEZIER: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum FilledHoleElement {
     Blank,
     Structure,
     Texture,
+    /// Within the bounding rect but not part of the hole
+    Outside,
 }
 
+#[derive(Clone, Debug)]
 pub struct FilledHoleMatrix {
     pub width: usize,
     pub height: usize,
@@ -48,6 +61,34 @@ impl FilledHoleMatrix {
         }
         matrix
     }
+
+    /// Create a matrix of the size of the bounding rect of 'hole', where pixels
+    /// not belonging to 'hole' are FilledHoleElement::Outside.
+    pub fn new_for_hole(hole: &Hole) -> Self {
+        let mask = hole.to_mask();
+        let mut matrix = Self::new(mask.width, mask.height);
+        for i in 0..matrix.height {
+            for j in 0..matrix.width {
+                if !mask.get_pixel(j, i) {
+                    matrix[i][j] = FilledHoleElement::Outside;
+                }
+            }
+        }
+        matrix
+    }
+
+    /// Mark the pixels of 'fragments' as FilledHoleElement::Structure, where 'origin' is the
+    /// top-left corner of the matrix in image coordinates. Pixels out of range are ignored.
+    pub fn keep_fragments(&mut self, origin: PointI32, fragments: &[Fragment]) {
+        for fragment in fragments.iter() {
+            for &pixel in fragment.pixels.iter() {
+                let p = pixel - origin;
+                if 0 <= p.x && p.x < self.width as i32 && 0 <= p.y && p.y < self.height as i32 {
+                    self[p.y as usize][p.x as usize] = FilledHoleElement::Structure;
+                }
+            }
+        }
+    }
 }
 
 impl Index<usize> for FilledHoleMatrix {
@@ -78,80 +119,244 @@ impl IndexMut<PointUsize> for FilledHoleMatrix {
     }
 }
 
+/// How the regions inside a hole bounded by the intrapolated curves are decided to be filled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FillRule {
+    /// The subregion between each pair of consecutive endpoints along the hole boundary is filled
+    /// if the pixels outside the hole along it are (nearly) all foreground (see HoleFiller::fill())
+    BoundarySampling,
+    /// The contours of the shape are closed by the intrapolated curves, and the pixels inside them
+    /// by the even-odd rule are filled along scanlines (see HoleFiller::fill_by_even_odd())
+    EvenOdd,
+}
+
 /// A class to fill colors into image whose structural information has been recovered.
 pub struct HoleFiller;
 
 // API
 impl HoleFiller {
     /// Return a FilledHoleMatrix representing what is inside the hole after filling.
+    /// The pixels of 'fragments' are kept as FilledHoleElement::Structure, and bound the filled
+    /// regions like 'intrapolated_curves' do.
     /// The behavior is undefined unless the size of 'image' is at least the size
-    /// of 'hole_rect'.
+    /// of the bounding rect of 'hole'.
     pub fn fill(
         image: &BinaryImage,
-        hole_rect: BoundingRect,
-        intrapolated_curves: Vec<CompoundPath>,
+        hole: &Hole,
+        intrapolated_curves: &[CompoundPath],
         endpoints: Vec<PointI32>,
+        fragments: &[Fragment],
         blank_broundary_pixels_threshold: usize,
-    ) -> Result<FilledHoleMatrix, String> {
-        let matrix = FilledHoleMatrix::new(hole_rect.width() as usize, hole_rect.height() as usize);
-        let origin = PointI32::new(hole_rect.left, hole_rect.top);
+    ) -> Result<FilledHoleMatrix, CompletionError> {
+        let mut matrix = FilledHoleMatrix::new_for_hole(hole);
+        let origin = hole.origin();
+        matrix.keep_fragments(origin, fragments);
 
-        let matrix = Self::rasterize_intrapolated_curves(matrix, intrapolated_curves, origin);
+        let matrix = Self::rasterize_intrapolated_curves(
+            matrix,
+            intrapolated_curves,
+            -origin.to_point_f64(),
+        );
 
         Self::fill_holes(
             matrix,
             image,
-            hole_rect,
+            hole,
             origin,
             endpoints,
             blank_broundary_pixels_threshold,
         )
     }
+
+    /// Return a FilledHoleMatrix representing what is inside the hole after filling by the even-odd
+    /// rule, which has no parameter to tune.
+    /// The contours of the shape entering the hole are closed by 'contour_curves', each of which
+    /// joins 2 of their ends, and by the sides of 'fragments' (see Fragment::find_sides()), whose
+    /// pixels are kept as FilledHoleElement::Structure. A pixel is filled iff the ray from its
+    /// center to the left crosses the closed contours an odd number of times. Where that ray leaves
+    /// the hole, it crosses the contours outside the hole an odd number of times iff the pixel it
+    /// leaves into is foreground in 'image', so only the crossings inside the hole are counted.
+    /// 'stubs' are drawn but do not bound any region, so the filling is exact only if every
+    /// contour entering the hole is closed, i.e. no endpoint is left unmatched.
+    /// The behavior is undefined unless the curves are inside the hole (boundary included).
+    pub fn fill_by_even_odd(
+        image: &BinaryImage,
+        hole: &Hole,
+        contour_curves: &[CompoundPath],
+        stubs: &[CompoundPath],
+        fragments: &[Fragment],
+    ) -> FilledHoleMatrix {
+        let mut matrix = FilledHoleMatrix::new_for_hole(hole);
+        let origin = hole.origin();
+        matrix.keep_fragments(origin, fragments);
+
+        let offset = -origin.to_point_f64();
+        let matrix = Self::rasterize_intrapolated_curves(matrix, contour_curves, offset);
+        let mut matrix = Self::rasterize_intrapolated_curves(matrix, stubs, offset);
+
+        let mut contours: Vec<Vec<PointF64>> = contour_curves
+            .iter()
+            .map(|curve| {
+                flatten_compound_path(curve, NUM_SAMPLES_PER_BEZIER)
+                    .into_iter()
+                    .map(|p| p + offset)
+                    .collect()
+            })
+            .collect();
+        for fragment in fragments.iter() {
+            contours.extend(fragment.find_sides().into_iter().map(|side| {
+                side.iter()
+                    .map(|&p| p.to_point_f64() + offset)
+                    .collect::<Vec<PointF64>>()
+            }));
+        }
+
+        for i in 0..matrix.height {
+            // Scan along the centers of the pixels in the row
+            let y = i as f64 + 0.5;
+            let crossings = Self::find_scanline_crossings(&contours, y);
+            let mut inside = false;
+            let mut num_crossed = 0;
+            for j in 0..matrix.width {
+                if matrix[i][j] == FilledHoleElement::Outside {
+                    continue;
+                }
+                if j == 0 || matrix[i][j - 1] == FilledHoleElement::Outside {
+                    // The row enters the hole from the pixel on the left
+                    let left = origin + PointI32::new(j as i32 - 1, i as i32);
+                    inside = image.get_pixel_at_safe(left);
+                    num_crossed = crossings.partition_point(|&x| x < j as f64);
+                }
+                let center = j as f64 + 0.5;
+                while num_crossed < crossings.len() && crossings[num_crossed] < center {
+                    inside = !inside;
+                    num_crossed += 1;
+                }
+                if inside && matrix[i][j] == FilledHoleElement::Blank {
+                    matrix[i][j] = FilledHoleElement::Texture;
+                }
+            }
+        }
+        matrix
+    }
+
+    /// Return a FilledHoleMatrix representing what is inside the hole after drawing
+    /// 'intrapolated_curves' as the centerlines of lines 'line_width' pixels wide.
+    /// Unlike in fill(), the points of 'intrapolated_curves' at integer coordinates are the
+    /// centers of pixels. Pixels whose centers are within 'line_width' / 2 of a curve are
+    /// FilledHoleElement::Texture, unless the curve passes through them.
+    pub fn fill_lines(
+        hole: &Hole,
+        intrapolated_curves: &[CompoundPath],
+        line_width: f64,
+    ) -> FilledHoleMatrix {
+        let matrix = FilledHoleMatrix::new_for_hole(hole);
+        let origin = hole.origin();
+
+        let offset = -origin.to_point_f64();
+        let mut matrix = Self::rasterize_intrapolated_curves(
+            matrix,
+            intrapolated_curves,
+            offset + PointF64::new(0.5, 0.5),
+        );
+
+        let polylines: Vec<Vec<PointF64>> = intrapolated_curves
+            .iter()
+            .map(|curve| {
+                flatten_compound_path(curve, NUM_SAMPLES_PER_BEZIER)
+                    .into_iter()
+                    .map(|p| p + offset)
+                    .collect()
+            })
+            .collect();
+        let half_width = line_width / 2.0;
+        for i in 0..matrix.height {
+            for j in 0..matrix.width {
+                if matrix[i][j] != FilledHoleElement::Blank {
+                    continue;
+                }
+                let p = PointF64::new(j as f64, i as f64);
+                let covered = polylines.iter().any(|polyline| {
+                    polyline
+                        .windows(2)
+                        .any(|segment| distance_to_segment(p, segment[0], segment[1]) <= half_width)
+                        || (polyline.len() == 1 && polyline[0].distance_to(p) <= half_width)
+                });
+                if covered {
+                    matrix[i][j] = FilledHoleElement::Texture;
+                }
+            }
+        }
+        matrix
+    }
 }
 
 // Helper functions
 impl HoleFiller {
+    /// Mark the pixels along 'curves', translated by 'offset', as FilledHoleElement::Structure.
     fn rasterize_intrapolated_curves(
         mut matrix: FilledHoleMatrix,
-        curves: Vec<CompoundPath>,
-        origin: PointI32,
+        curves: &[CompoundPath],
+        offset: PointF64,
     ) -> FilledHoleMatrix {
-        let offset = -origin;
-        curves.into_iter().for_each(|mut compound_path| {
-            compound_path
-                .iter_mut()
-                .for_each(|path_elem| match path_elem {
-                    visioncortex::CompoundPathElement::PathI32(path) => {
-                        path.offset(&offset);
-                        path.iter().for_each(|point| {
-                            let point = PointUsize::new(point.x as usize, point.y as usize);
-                            matrix[point] = FilledHoleElement::Structure;
-                        })
-                    }
-                    visioncortex::CompoundPathElement::PathF64(path) => {
-                        path.offset(&offset.to_point_f64());
-                        path.iter().for_each(|point| {
-                            let point = PointUsize::new(point.x as usize, point.y as usize);
-                            matrix[point] = FilledHoleElement::Structure;
-                        })
-                    }
-                    visioncortex::CompoundPathElement::Spline(spline) => {
-                        spline.offset(&offset.to_point_f64());
-                        spline.get_control_points().into_iter().for_each(|points| {
-                            Self::rasterize_bezier_curve(
-                                &mut matrix,
-                                points
-                                    .try_into()
-                                    .expect("Control points must have 4 elements"),
-                            );
-                        });
-                    }
-                });
+        curves.iter().for_each(|compound_path| {
+            compound_path.iter().for_each(|path_elem| match path_elem {
+                visioncortex::CompoundPathElement::PathI32(path) => {
+                    let points: Vec<PointF64> = path
+                        .iter()
+                        .map(|&point| point.to_point_f64() + offset)
+                        .collect();
+                    Self::rasterize_polyline(&mut matrix, &points);
+                }
+                visioncortex::CompoundPathElement::PathF64(path) => {
+                    let points: Vec<PointF64> = path.iter().map(|&point| point + offset).collect();
+                    Self::rasterize_polyline(&mut matrix, &points);
+                }
+                visioncortex::CompoundPathElement::Spline(spline) => {
+                    spline.get_control_points().into_iter().for_each(|points| {
+                        let points: Vec<PointF64> =
+                            points.iter().map(|&point| point + offset).collect();
+                        if let Ok(control_points) = points.try_into() {
+                            Self::rasterize_bezier_curve(&mut matrix, control_points);
+                        }
+                    });
+                }
+            });
         });
 
         matrix
     }
 
+    /// Mark the pixel containing 'p' as FilledHoleElement::Structure, unless it is out of range or
+    /// not part of the hole.
+    fn set_structure_at(matrix: &mut FilledHoleMatrix, p: PointF64) {
+        if 0.0 <= p.x && p.x < matrix.width as f64 && 0.0 <= p.y && p.y < matrix.height as f64 {
+            let elem = &mut matrix[PointUsize::new(p.x as usize, p.y as usize)];
+            if *elem != FilledHoleElement::Outside {
+                *elem = FilledHoleElement::Structure;
+            }
+        }
+    }
+
+    /// Mark the pixels along the segments between consecutive 'points' as
+    /// FilledHoleElement::Structure, sampling each segment at every quarter of a pixel.
+    fn rasterize_polyline(matrix: &mut FilledHoleMatrix, points: &[PointF64]) {
+        if let Some(&first) = points.first() {
+            Self::set_structure_at(matrix, first);
+        }
+        points.windows(2).for_each(|segment| {
+            let (from, to) = (segment[0], segment[1]);
+            let quantization_levels =
+                std::cmp::max((from.distance_to(to) * 4.0).ceil() as usize, 1);
+            for i in 1..=quantization_levels {
+                let t = i as f64 / quantization_levels as f64;
+                Self::set_structure_at(matrix, from * (1.0 - t) + to * t);
+            }
+        });
+    }
+
+    /// Mark the pixels along the cubic bezier curve with 'control_points' as
+    /// FilledHoleElement::Structure (see set_structure_at()).
     fn rasterize_bezier_curve(matrix: &mut FilledHoleMatrix, control_points: [PointF64; 4]) {
         let points: Vec<Coord2> = control_points.iter().map(|p| Coord2(p.x, p.y)).collect();
 
@@ -165,27 +370,52 @@ impl HoleFiller {
         for i in 0..quantization_levels {
             let t = i as f64 / quantization_levels as f64;
             let p = curve.point_at_pos(t);
-            let clipped_p = PointUsize::new(
-                std::cmp::min(p.x() as usize, matrix.width - 1),
-                std::cmp::min(p.y() as usize, matrix.height - 1),
-            );
-            matrix[clipped_p] = FilledHoleElement::Structure;
+            Self::set_structure_at(matrix, PointF64::new(p.x(), p.y()));
         }
     }
 
-    /// The behavior is undefined unless 'offset' is the top-left corner of 'hole_rect' (exactly on its boundary).
+    /// The x coordinates (sorted) where the polylines 'contours' cross the horizontal line at 'y'.
+    /// A segment crosses the line iff its ends are on different sides of it, where points on the
+    /// line are above it, so that a polyline passing through the line at a vertex crosses it once.
+    fn find_scanline_crossings(contours: &[Vec<PointF64>], y: f64) -> Vec<f64> {
+        let mut crossings: Vec<f64> = contours
+            .iter()
+            .flat_map(|contour| contour.windows(2))
+            .filter_map(|segment| {
+                let (from, to) = (segment[0], segment[1]);
+                if (from.y <= y) == (to.y <= y) {
+                    return None;
+                }
+                Some(from.x + (to.x - from.x) * (y - from.y) / (to.y - from.y))
+            })
+            .collect();
+        crossings.sort_by(f64::total_cmp);
+        crossings
+    }
+
+    /// The behavior is undefined unless 'offset' is the top-left corner of the bounding rect of 'hole'.
     fn fill_holes(
         mut matrix: FilledHoleMatrix,
         image: &BinaryImage,
-        hole_rect: BoundingRect,
+        hole: &Hole,
         offset: PointI32,
         endpoints: Vec<PointI32>,
         blank_boundary_pixels_threshold: usize,
-    ) -> Result<FilledHoleMatrix, String> {
-        let endpoints = Self::adjust_endpoints(&hole_rect, endpoints);
+    ) -> Result<FilledHoleMatrix, CompletionError> {
+        // Correction for endpoints off boundary
+        let endpoints: Vec<PointI32> = endpoints
+            .into_iter()
+            .map(|endpoint| hole.snap_to_boundary(endpoint))
+            .collect();
+        if endpoints.is_empty() {
+            return Ok(matrix);
+        }
 
-        let bounding_points = hole_rect.get_boundary_points_from(endpoints[0], true);
+        let bounding_points = hole.get_boundary_points_from(endpoints[0], true);
         let num_points = bounding_points.len();
+        if num_points == 0 {
+            return Ok(matrix);
+        }
         let mut current_point = 0;
         // The middle point between from and to in a cyclic manner.
         // Used to sample the middle point between endpoints.
@@ -201,23 +431,11 @@ impl HoleFiller {
         let endpoints_set = endpoints.iter().copied().collect::<HashSet<PointI32>>();
         let is_endpoint = |p| endpoints_set.contains(&p);
 
-        let eval_outside_point = |point_idx| {
-            let point_val: PointI32 = bounding_points[point_idx];
-            if point_val.x == hole_rect.right || point_val.y == hole_rect.bottom {
-                point_val
-            } else {
-                hole_rect.get_closest_point_outside(point_val)
-            }
-        };
+        let eval_outside_point =
+            |point_idx| hole.get_closest_pixel_outside(bounding_points[point_idx]);
 
-        let eval_inside_point = |point_idx| {
-            let point_val: PointI32 = bounding_points[point_idx];
-            if point_val.x == hole_rect.left || point_val.y == hole_rect.top {
-                point_val
-            } else {
-                hole_rect.get_closest_point_inside(point_val)
-            }
-        };
+        let eval_inside_point =
+            |point_idx| hole.get_closest_pixel_inside(bounding_points[point_idx]);
 
         // Go to next segment. Fill it if it should be filled.
         // Repeat this until the first endpoint is seen again.
@@ -233,11 +451,14 @@ impl HoleFiller {
                 if !image.get_pixel_at_safe(outside_point) {
                     blank_outside_pixels += 1;
                 }
-                if is_endpoint(bounding_points[current_point]) {
+                // Also stop after a full cycle in case the first endpoint is not on the boundary
+                if is_endpoint(bounding_points[current_point]) || current_point == 0 {
                     break;
                 }
             }
-            if total_outside_pixels > blank_boundary_pixels_threshold && blank_outside_pixels <= blank_boundary_pixels_threshold {
+            if total_outside_pixels > blank_boundary_pixels_threshold
+                && blank_outside_pixels <= blank_boundary_pixels_threshold
+            {
                 let sampled_mid_point = sample_point(prev_endpoint, current_point);
                 let sampled_points = [
                     sample_point(prev_endpoint, sampled_mid_point),
@@ -251,73 +472,21 @@ impl HoleFiller {
                 });
             }
 
-            if current_point == 0 {
+            // Stop once the first endpoint is seen again or passed
+            if current_point == 0 || current_point + 1 == num_points {
                 break;
             }
 
-            current_point = (current_point + 1) % num_points;
+            current_point += 1;
         }
 
         Ok(matrix)
     }
 
-    /// Correction for endpoints off boundary
-    fn adjust_endpoints(hole_rect: &BoundingRect, endpoints: Vec<PointI32>) -> Vec<PointI32> {
-        endpoints
-            .into_iter()
-            .map(|endpoint| {
-                if hole_rect.have_point_on_boundary(endpoint, 0) {
-                    endpoint
-                } else {
-                    // Determine if endpoint is vertically or horizontally aligned with the rect
-                    if hole_rect.left <= endpoint.x && endpoint.x <= hole_rect.right {
-                        // Should be adjusted to either top or bottom side
-                        PointI32::new(
-                            endpoint.x,
-                            if (hole_rect.top - endpoint.y).abs()
-                                < (hole_rect.bottom - endpoint.y).abs()
-                            {
-                                hole_rect.top
-                            } else {
-                                hole_rect.bottom
-                            },
-                        )
-                    } else if hole_rect.top <= endpoint.y && endpoint.y <= hole_rect.bottom {
-                        // Should be adjusted to either left and right side
-                        PointI32::new(
-                            if (hole_rect.left - endpoint.x).abs()
-                                < (hole_rect.right - endpoint.x).abs()
-                            {
-                                hole_rect.left
-                            } else {
-                                hole_rect.right
-                            },
-                            endpoint.y,
-                        )
-                    } else {
-                        // Should be adjusted to one of the corners
-                        IntoIterator::into_iter([
-                            hole_rect.top_left(),
-                            hole_rect.top_right(),
-                            hole_rect.bottom_left(),
-                            hole_rect.bottom_right(),
-                        ])
-                        .min_by_key(|&corner| {
-                            endpoint.to_point_f64().distance_to(corner.to_point_f64()) as i32
-                        })
-                        .unwrap()
-                    }
-                }
-            })
-            .collect()
-    }
-
     /// Flood fill a region of FilledHoleElement::Blank starting at 'seed' in an iterative manner.
     fn fill_hole_iterative(matrix: &mut FilledHoleMatrix, seed: PointI32) {
         let mut stack = vec![seed];
-        while !stack.is_empty() {
-            let point = stack.pop().unwrap();
-
+        while let Some(point) = stack.pop() {
             // Out of range
             if point.x < 0
                 || point.x >= matrix.width as i32
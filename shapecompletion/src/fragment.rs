@@ -0,0 +1,186 @@
+use visioncortex::{clusters::Cluster, BinaryImage, PathI32, PointF64, PointI32};
+
+use crate::{hole::Hole, tangent::fit_line};
+
+/// Fragments shorter than this (in pixels) along their principal axes have no ends to be matched
+const MIN_FRAGMENT_LENGTH: f64 = 2.0;
+/// The contour points within this distance (in pixels) of the extremes of a fragment along its
+/// principal axis are on the faces where it was cut off by the damage
+const END_FACE_DEPTH: f64 = 0.5;
+
+/// A connected piece (4-connectivity) of foreground surviving inside a partially damaged hole.
+#[derive(Clone, Debug)]
+pub struct Fragment {
+    /// The pixels of the fragment (image coordinates).
+    pub pixels: Vec<PointI32>,
+    /// The outer contour of the fragment as a closed path of pixel corners (image coordinates).
+    pub contour: PathI32,
+}
+
+// API
+impl Fragment {
+    /// Find the fragments formed by the foreground pixels of 'image' inside 'hole'.
+    pub fn find_in_hole(image: &BinaryImage, hole: &Hole) -> Vec<Fragment> {
+        let rect = hole.rect();
+        let origin = hole.origin();
+        let mut surviving = BinaryImage::new_w_h(rect.width() as usize, rect.height() as usize);
+        for y in rect.top..rect.bottom {
+            for x in rect.left..rect.right {
+                let p = PointI32::new(x, y);
+                if hole.have_pixel_inside(p) && image.get_pixel_at_safe(p) {
+                    surviving.set_pixel_at(p - origin, true);
+                }
+            }
+        }
+        if surviving.pixels.none() {
+            return vec![];
+        }
+
+        surviving
+            .to_clusters(false)
+            .into_iter()
+            .filter_map(|cluster| {
+                let cluster_origin = origin + PointI32::new(cluster.rect.left, cluster.rect.top);
+                let mut contour = Cluster::image_to_paths(
+                    &cluster.to_binary_image(),
+                    visioncortex::PathSimplifyMode::None,
+                )
+                .into_iter()
+                .next()?;
+                contour.offset(&cluster_origin);
+                Some(Fragment {
+                    pixels: cluster.points.iter().map(|&p| p + origin).collect(),
+                    contour,
+                })
+            })
+            .collect()
+    }
+
+    /// The segments walked along the sides of the fragment from their ends, whose heads (index 0)
+    /// are the ends, like the segments walked from the hole boundary.
+    /// Each side (see find_sides()) gives one segment from each of its ends, walked to its other
+    /// end. Sides ending on the boundary of 'hole' continue the shape outside the hole there, so no
+    /// segment is walked from such ends.
+    pub fn find_end_segments(&self, hole: &Hole) -> Vec<PathI32> {
+        let mut segments = vec![];
+        for side in self.find_sides().into_iter() {
+            if !hole.have_point_on_boundary(side[0], 1) {
+                segments.push(side.clone());
+            }
+            if !hole.have_point_on_boundary(side[side.len() - 1], 1) {
+                let mut reversed = side;
+                reversed.path.reverse();
+                segments.push(reversed);
+            }
+        }
+        segments
+    }
+
+    /// The sides of the fragment, which are the parts of its contour along which the shape is
+    /// intact. The fragment is assumed to be cut off by the damage across its principal axis, so its
+    /// contour is split into sides at the edges at both extremes along the axis (the cut faces).
+    /// No side is found if the fragment is too short along its principal axis to have cut faces.
+    pub fn find_sides(&self) -> Vec<PathI32> {
+        let centers: Vec<PointF64> = self
+            .pixels
+            .iter()
+            .map(|p| p.to_point_f64() + PointF64::new(0.5, 0.5))
+            .collect();
+        let axis = match fit_line(&centers) {
+            Some((axis, _)) => axis,
+            None => return vec![],
+        };
+
+        let contour = self.contour.to_open();
+        let len = contour.len();
+        let projections: Vec<f64> = contour.iter().map(|p| p.to_point_f64().dot(axis)).collect();
+        let min = projections.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = projections
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if max - min < MIN_FRAGMENT_LENGTH {
+            return vec![];
+        }
+        let is_at_min = |i: usize| projections[i] <= min + END_FACE_DEPTH;
+        let is_at_max = |i: usize| projections[i] >= max - END_FACE_DEPTH;
+        // Edge i joins contour[i] and contour[i + 1]
+        let is_end_face_edge: Vec<bool> = (0..len)
+            .map(|i| {
+                let j = (i + 1) % len;
+                (is_at_min(i) && is_at_min(j)) || (is_at_max(i) && is_at_max(j))
+            })
+            .collect();
+        let first_face_edge = match is_end_face_edge.iter().position(|&face| face) {
+            Some(first_face_edge) => first_face_edge,
+            None => return vec![],
+        };
+
+        // Collect the sides as the runs of edges between end faces, starting from an end face so
+        // that no side wraps around the start of the contour
+        let mut sides: Vec<PathI32> = vec![];
+        let mut side = PathI32::new();
+        for offset in 1..=len {
+            let i = (first_face_edge + offset) % len;
+            if is_end_face_edge[i] {
+                if !side.is_empty() {
+                    sides.push(std::mem::take(&mut side));
+                }
+            } else {
+                if side.is_empty() {
+                    side.add(contour[i]);
+                }
+                side.add(contour[(i + 1) % len]);
+            }
+        }
+        sides
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use visioncortex::BoundingRect;
+
+    #[test]
+    fn fragment_of_bar_is_split_at_its_cut_faces() {
+        // GIVEN a horizontal bar crossing a hole, with a piece of it surviving in the middle
+        let mut image = BinaryImage::new_w_h(40, 30);
+        for y in 10..16 {
+            for x in 0..40 {
+                image.set_pixel(x, y, true);
+            }
+        }
+        let hole = Hole::from_rect(BoundingRect::new_x_y_w_h(10, 5, 20, 20));
+        for y in 5..25 {
+            for x in 10..30 {
+                if !(16..24).contains(&x) {
+                    image.set_pixel(x, y, false);
+                }
+            }
+        }
+
+        // WHEN
+        let fragments = Fragment::find_in_hole(&image, &hole);
+        let segments = fragments[0].find_end_segments(&hole);
+
+        // THEN
+        assert_eq!(fragments.len(), 1);
+        assert_eq!(fragments[0].pixels.len(), 8 * 6);
+        let mut heads: Vec<PointI32> = segments.iter().map(|segment| segment[0]).collect();
+        heads.sort_by_key(|p| (p.x, p.y));
+        assert_eq!(
+            heads,
+            vec![
+                PointI32::new(16, 10),
+                PointI32::new(16, 16),
+                PointI32::new(24, 10),
+                PointI32::new(24, 16)
+            ]
+        );
+        // Each segment runs along the top or bottom edge to the other end
+        assert!(segments
+            .iter()
+            .all(|segment| segment.iter().all(|p| p.y == segment[0].y)));
+    }
+}
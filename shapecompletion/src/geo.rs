@@ -2,12 +2,15 @@ use std::f64::consts::PI;
 
 use flo_curves::{
     bezier::{curve_intersects_curve_clip, Curve},
-    Coord2, Coordinate,
+    Coord2, Coordinate, Coordinate2D,
 };
-use visioncortex::{CompoundPath, CompoundPathElement, PathF64, PointF64, Spline};
+use visioncortex::{CompoundPath, CompoundPathElement, PathF64, PointF64};
 
 // Geometry helper functions
 
+/// An axis-aligned bounding box as (left, top, right, bottom)
+pub(super) type Bounds = (f64, f64, f64, f64);
+
 fn f64_approximately(a: f64, b: f64) -> bool {
     (a - b).abs() <= f64::EPSILON
 }
@@ -162,55 +165,249 @@ pub(super) fn find_new_point_from_4_point_scheme(
     mid_out + vector_out.get_normalized() * new_magnitude
 }
 
-/// Determine if any curves in one of the compound paths intersect with another curve in another compound path.
-/// Assume that no curves within any single compound path intersect with each other.
-/// The behavior is undefined unless all elements in all compound paths are Spline and contain exactly 1 curve.
-pub(super) fn bezier_curves_intersection(compound_curves: &[CompoundPath]) -> bool {
-    // Assertion
-    compound_curves.iter().for_each(|compound_curve| {
-        compound_curve.iter().for_each(|curve| {
-            if let CompoundPathElement::Spline(curve) = curve {
-                assert_eq!(curve.num_curves(), 1);
-            } else {
-                panic!("Not all compound paths are Spline.");
+/// Convert every element of 'compound_path' into cubic bezier curves.
+/// Each segment of a polyline (PathI32 or PathF64) is converted into a straight cubic bezier curve.
+pub(super) fn compound_path_to_bezier_curves(compound_path: &CompoundPath) -> Vec<Curve<Coord2>> {
+    let to_coord = |p: &PointF64| Coord2::from_components(&[p.x, p.y]);
+    let straight_curve = |from: PointF64, to: PointF64| Curve {
+        start_point: to_coord(&from),
+        end_point: to_coord(&to),
+        control_points: (
+            to_coord(&calculate_in_between_point(from, to, 1.0 / 3.0)),
+            to_coord(&calculate_in_between_point(from, to, 2.0 / 3.0)),
+        ),
+    };
+    let polyline_curves = |points: Vec<PointF64>| -> Vec<Curve<Coord2>> {
+        points
+            .windows(2)
+            .map(|pair| straight_curve(pair[0], pair[1]))
+            .collect()
+    };
+
+    compound_path
+        .iter()
+        .flat_map(|element| match element {
+            CompoundPathElement::PathI32(path) => {
+                polyline_curves(path.iter().map(|p| p.to_point_f64()).collect())
             }
+            CompoundPathElement::PathF64(path) => polyline_curves(path.path.clone()),
+            CompoundPathElement::Spline(spline) => spline
+                .get_control_points()
+                .into_iter()
+                .map(|points| Curve {
+                    start_point: to_coord(&points[0]),
+                    end_point: to_coord(&points[3]),
+                    control_points: (to_coord(&points[1]), to_coord(&points[2])),
+                })
+                .collect(),
         })
-    });
+        .collect()
+}
+
+/// The distance from 'p' to the line segment between 'from' and 'to'.
+pub(super) fn distance_to_segment(p: PointF64, from: PointF64, to: PointF64) -> f64 {
+    let direction = to - from;
+    let squared_length = direction.dot(direction);
+    if squared_length == 0.0 {
+        return p.distance_to(from);
+    }
+    let t = ((p - from).dot(direction) / squared_length).clamp(0.0, 1.0);
+    p.distance_to(from + direction * t)
+}
 
-    let single_spline_to_curve = |spline: &Spline| {
-        let coords: Vec<Coord2> = spline
-            .points
-            .iter()
-            .map(|p| Coord2::from_components(&[p.x, p.y]))
+/// The intersection of the line segments from 'from1' to 'to1' and from 'from2' to 'to2', and the
+/// ratio along the first segment at which it lies.
+/// None is returned if they do not intersect or are parallel.
+pub(super) fn segments_intersection(
+    (from1, to1): (PointF64, PointF64),
+    (from2, to2): (PointF64, PointF64),
+) -> Option<(PointF64, f64)> {
+    let (direction1, direction2) = (to1 - from1, to2 - from2);
+    let denominator = direction1.x * direction2.y - direction1.y * direction2.x;
+    if denominator.abs() < f64::EPSILON {
+        return None;
+    }
+    let difference = from2 - from1;
+    let ratio1 = (difference.x * direction2.y - difference.y * direction2.x) / denominator;
+    let ratio2 = (difference.x * direction1.y - difference.y * direction1.x) / denominator;
+    if (0.0..=1.0).contains(&ratio1) && (0.0..=1.0).contains(&ratio2) {
+        Some((from1 + direction1 * ratio1, ratio1))
+    } else {
+        None
+    }
+}
+
+/// The bounding box of 'points'. It is empty (left > right) if 'points' is empty.
+pub(super) fn bounds_of_points(points: &[PointF64]) -> Bounds {
+    points.iter().fold(
+        (
+            f64::INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NEG_INFINITY,
+        ),
+        |(left, top, right, bottom), p| {
+            (left.min(p.x), top.min(p.y), right.max(p.x), bottom.max(p.y))
+        },
+    )
+}
+
+/// Split the bezier curve of any degree with 'control_points' at its middle (t = 0.5) by de
+/// Casteljau's algorithm, and return the control points of both halves (same degree).
+pub(super) fn split_bezier_in_half(control_points: &[PointF64]) -> (Vec<PointF64>, Vec<PointF64>) {
+    let mut first = vec![];
+    let mut second = vec![];
+    let mut points = control_points.to_vec();
+    while let (Some(&head), Some(&tail)) = (points.first(), points.last()) {
+        first.push(head);
+        second.push(tail);
+        points = points
+            .windows(2)
+            .map(|pair| calculate_midpoint(pair[0], pair[1]))
             .collect();
-        Curve {
-            start_point: coords[0],
-            end_point: coords[3],
-            control_points: (coords[1], coords[2]),
+    }
+    second.reverse();
+    (first, second)
+}
+
+/// The number of consecutive segments of a polyline sharing a bounding box when finding crossings
+const NUM_SEGMENTS_PER_CROSSING_CHUNK: usize = 16;
+
+/// The points where the polylines 'points1' and 'points2' cross each other, together with the
+/// (unsigned) angles in [0, PI/2] between them there. Crossings less than 1 pixel apart are merged.
+/// At most 'max_num_crossings' crossings are returned.
+pub(super) fn polylines_crossings(
+    points1: &[PointF64],
+    points2: &[PointF64],
+    max_num_crossings: usize,
+) -> Vec<(PointF64, f64)> {
+    // The bounding boxes of chunks of consecutive segments, each with the range of its points
+    let chunks = |points: &[PointF64]| -> Vec<(Bounds, std::ops::Range<usize>)> {
+        (0..points.len().saturating_sub(1))
+            .step_by(NUM_SEGMENTS_PER_CROSSING_CHUNK)
+            .map(|from| {
+                let to = std::cmp::min(from + NUM_SEGMENTS_PER_CROSSING_CHUNK + 1, points.len());
+                (bounds_of_points(&points[from..to]), from..to)
+            })
+            .collect()
+    };
+    let (chunks1, chunks2) = (chunks(points1), chunks(points2));
+
+    let mut crossings: Vec<(PointF64, f64)> = vec![];
+    for ((left1, top1, right1, bottom1), range1) in chunks1.iter() {
+        for ((left2, top2, right2, bottom2), range2) in chunks2.iter() {
+            if left1 > right2 || left2 > right1 || top1 > bottom2 || top2 > bottom1 {
+                continue;
+            }
+            for segment1 in points1[range1.clone()].windows(2) {
+                for segment2 in points2[range2.clone()].windows(2) {
+                    let intersection = segments_intersection(
+                        (segment1[0], segment1[1]),
+                        (segment2[0], segment2[1]),
+                    );
+                    if let Some((point, _)) = intersection {
+                        if crossings.iter().any(|(p, _)| p.distance_to(point) < 1.0) {
+                            continue;
+                        }
+                        if crossings.len() >= max_num_crossings {
+                            return crossings;
+                        }
+                        let (direction1, direction2) = (
+                            (segment1[1] - segment1[0]).get_normalized(),
+                            (segment2[1] - segment2[0]).get_normalized(),
+                        );
+                        let angle = direction1.dot(direction2).abs().clamp(0.0, 1.0).acos();
+                        crossings.push((point, angle));
+                    }
+                }
+            }
+        }
+    }
+    crossings
+}
+
+/// The points of 'compound_path' with each of its cubic bezier curves flattened into
+/// 'num_samples_per_bezier' line segments. Consecutive duplicate points are removed.
+pub(super) fn flatten_compound_path(
+    compound_path: &CompoundPath,
+    num_samples_per_bezier: usize,
+) -> Vec<PointF64> {
+    let mut points: Vec<PointF64> = vec![];
+    let mut push = |p: PointF64| {
+        if points.last() != Some(&p) {
+            points.push(p);
         }
     };
+    for element in compound_path.iter() {
+        match element {
+            CompoundPathElement::PathI32(path) => {
+                path.iter().for_each(|p| push(p.to_point_f64()));
+            }
+            CompoundPathElement::PathF64(path) => path.iter().for_each(|&p| push(p)),
+            CompoundPathElement::Spline(spline) => {
+                for points in spline.get_control_points() {
+                    let (p0, p1, p2, p3) = (points[0], points[1], points[2], points[3]);
+                    for s in 0..=num_samples_per_bezier {
+                        let t = s as f64 / num_samples_per_bezier as f64;
+                        let u = 1.0 - t;
+                        push(
+                            p0 * (u * u * u)
+                                + p1 * (3.0 * u * u * t)
+                                + p2 * (3.0 * u * t * t)
+                                + p3 * (t * t * t),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    points
+}
 
+/// Determine if any curves in one of the compound paths intersect with another curve in another compound path.
+/// Assume that no curves within any single compound path intersect with each other.
+pub(super) fn bezier_curves_intersection(compound_curves: &[CompoundPath]) -> bool {
     // Convert to a type that is easier to work with
     let curves_vec: Vec<Vec<Curve<Coord2>>> = compound_curves
         .iter()
-        .map(|compound_curve| {
-            compound_curve
-                .iter()
-                .filter_map(|curve| {
-                    if let CompoundPathElement::Spline(curve) = curve {
-                        Some(single_spline_to_curve(curve))
-                    } else {
-                        None
-                    }
-                })
-                .collect()
-        })
+        .map(compound_path_to_bezier_curves)
         .collect();
 
+    // The bounding box of the control points, which contains the curve
+    let bounds = |curve: &Curve<Coord2>| -> Bounds {
+        let (c1, c2) = curve.control_points;
+        [curve.start_point, c1, c2, curve.end_point].iter().fold(
+            (
+                f64::INFINITY,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+            ),
+            |(left, top, right, bottom), p| {
+                (
+                    left.min(p.x()),
+                    top.min(p.y()),
+                    right.max(p.x()),
+                    bottom.max(p.y()),
+                )
+            },
+        )
+    };
     let two_curves_intersect = |curve1: &Curve<Coord2>, curve2: &Curve<Coord2>| {
         let base_length = |curve: &Curve<Coord2>| curve.start_point.distance_to(&curve.end_point);
         let accuracy = (base_length(curve1) + base_length(curve2)) * 0.25;
 
+        // Curves farther apart than the accuracy cannot be reported as intersecting
+        let ((left1, top1, right1, bottom1), (left2, top2, right2, bottom2)) =
+            (bounds(curve1), bounds(curve2));
+        if left1 > right2 + accuracy
+            || left2 > right1 + accuracy
+            || top1 > bottom2 + accuracy
+            || top2 > bottom1 + accuracy
+        {
+            return false;
+        }
+
         !curve_intersects_curve_clip(curve1, curve2, accuracy).is_empty()
     };
 
@@ -226,27 +423,3 @@ pub(super) fn bezier_curves_intersection(compound_curves: &[CompoundPath]) -> bo
         })
     })
 }
-
-/// Retract a point towards another point until the supplied predicate returns true or n retractions have been done.
-/// The direction is (0: from) -> (1: to).
-/// The behavior is undefined unless 0.0 <= retract_ratio <= 1.0
-pub(super) fn retract_point<P>(
-    mut from: PointF64,
-    to: PointF64,
-    retract_ratio: f64,
-    predicate: P,
-    n: Option<usize>,
-) -> PointF64
-where
-    P: Fn(PointF64) -> bool,
-{
-    assert!(0.0 <= retract_ratio);
-    assert!(retract_ratio <= 1.0);
-
-    let mut i = n.unwrap_or_default();
-    while !predicate(from) && (n.is_none() || i > 0) {
-        from = calculate_in_between_point(from, to, retract_ratio);
-        i -= 1;
-    }
-    from
-}
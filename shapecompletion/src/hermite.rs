@@ -0,0 +1,198 @@
+use visioncortex::{PointF64, Spline};
+
+/// The number of samples used to estimate the arc length and total turning angle of a curve.
+const NUM_MEASURE_SAMPLES: usize = 64;
+
+/// A quintic bezier curve, parameterized over [0, 1], matching the positions, tangent directions
+/// and signed curvatures at both of its ends (G2 Hermite interpolation).
+#[derive(Clone, Copy, Debug)]
+pub(super) struct QuinticHermite {
+    pub(super) control_points: [PointF64; 6],
+}
+
+impl QuinticHermite {
+    /// Find the curve from 'from_point' to 'to_point', leaving 'from_point' in the direction of
+    /// 'from_tangent' with signed curvature 'from_curvature', and arriving at 'to_point' in the
+    /// direction of 'to_tangent' with signed curvature 'to_curvature'.
+    /// A positive curvature turns from the tangent towards its normal rotated by +90 degrees, as
+    /// the angle given by atan2() increases.
+    /// The speed at both ends is the length of the circular arc between the points that turns by
+    /// the angle between the tangents, and the acceleration there is perpendicular to the tangent.
+    /// None is returned if the points coincide or a tangent is zero.
+    pub(super) fn fit(
+        from_point: PointF64,
+        from_tangent: PointF64,
+        from_curvature: f64,
+        to_point: PointF64,
+        to_tangent: PointF64,
+        to_curvature: f64,
+    ) -> Option<Self> {
+        let distance = from_point.distance_to(to_point);
+        if distance == 0.0 || from_tangent.norm() == 0.0 || to_tangent.norm() == 0.0 {
+            return None;
+        }
+        let (from_unit, to_unit) = (from_tangent.get_normalized(), to_tangent.get_normalized());
+        let left_normal = |unit: PointF64| PointF64::new(-unit.y, unit.x);
+
+        let half_turning_angle = from_unit.dot(to_unit).clamp(-1.0, 1.0).acos() / 2.0;
+        let speed = if half_turning_angle > f64::EPSILON {
+            distance * half_turning_angle / half_turning_angle.sin()
+        } else {
+            distance
+        };
+        let (from_velocity, to_velocity) = (from_unit * speed, to_unit * speed);
+        let from_acceleration = left_normal(from_unit) * (from_curvature * speed * speed);
+        let to_acceleration = left_normal(to_unit) * (to_curvature * speed * speed);
+
+        let control_points = [
+            from_point,
+            from_point + from_velocity / 5.0,
+            from_point + from_velocity * (2.0 / 5.0) + from_acceleration / 20.0,
+            to_point - to_velocity * (2.0 / 5.0) + to_acceleration / 20.0,
+            to_point - to_velocity / 5.0,
+            to_point,
+        ];
+        if control_points
+            .iter()
+            .any(|p| !p.x.is_finite() || !p.y.is_finite())
+        {
+            return None;
+        }
+        Some(Self { control_points })
+    }
+
+    /// The point at parameter 't'.
+    pub(super) fn point_at(&self, t: f64) -> PointF64 {
+        Self::evaluate(&self.control_points, t)
+    }
+
+    /// The first derivative at parameter 't'.
+    pub(super) fn derivative_at(&self, t: f64) -> PointF64 {
+        let p = &self.control_points;
+        let differences: Vec<PointF64> = (0..5).map(|i| (p[i + 1] - p[i]) * 5.0).collect();
+        Self::evaluate(&differences, t)
+    }
+
+    /// The signed curvature at parameter 't'. 0.0 is returned where the derivative is zero.
+    pub(super) fn curvature_at(&self, t: f64) -> f64 {
+        let p = &self.control_points;
+        let second_differences: Vec<PointF64> = (0..4)
+            .map(|i| (p[i + 2] - p[i + 1] * 2.0 + p[i]) * 20.0)
+            .collect();
+        let (d1, d2) = (
+            self.derivative_at(t),
+            Self::evaluate(&second_differences, t),
+        );
+        let speed = d1.norm();
+        if speed == 0.0 {
+            return 0.0;
+        }
+        (d1.x * d2.y - d1.y * d2.x) / (speed * speed * speed)
+    }
+
+    /// The approximate arc length and total (unsigned) turning angle of the curve.
+    pub(super) fn measure(&self) -> (f64, f64) {
+        let points: Vec<PointF64> = (0..=NUM_MEASURE_SAMPLES)
+            .map(|i| self.point_at(i as f64 / NUM_MEASURE_SAMPLES as f64))
+            .collect();
+        let (mut length, mut turning_angle) = (0.0, 0.0);
+        for (i, pair) in points.windows(2).enumerate() {
+            let part_length = pair[0].distance_to(pair[1]);
+            let t = (i as f64 + 0.5) / NUM_MEASURE_SAMPLES as f64;
+            length += part_length;
+            turning_angle += self.curvature_at(t).abs() * part_length;
+        }
+        (length, turning_angle)
+    }
+
+    /// Approximate the curve with 'num_parts' (at least 1) cubic bezier curves over equal parameter
+    /// intervals, each matching the position and derivative of the curve at both of its ends.
+    /// The curvatures at the ends of the spline converge to those of the curve as 'num_parts' grows.
+    pub(super) fn to_spline(self, num_parts: usize) -> Spline {
+        let num_parts = std::cmp::max(num_parts, 1);
+        let step = 1.0 / num_parts as f64;
+
+        let mut spline = Spline::new(self.control_points[0]);
+        let mut from = self.control_points[0];
+        for i in 0..num_parts {
+            let (t0, t1) = (i as f64 * step, (i + 1) as f64 * step);
+            let to = self.point_at(t1);
+            spline.add(
+                from + self.derivative_at(t0) * (step / 3.0),
+                to - self.derivative_at(t1) * (step / 3.0),
+                to,
+            );
+            from = to;
+        }
+        spline
+    }
+}
+
+// Helper functions
+impl QuinticHermite {
+    /// Evaluate the bezier curve with 'control_points' at 't' by de Casteljau's algorithm.
+    fn evaluate(control_points: &[PointF64], t: f64) -> PointF64 {
+        let mut points = control_points.to_vec();
+        for len in (1..points.len()).rev() {
+            for i in 0..len {
+                points[i] = points[i] * (1.0 - t) + points[i + 1] * t;
+            }
+        }
+        points[0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fitted_curve_matches_tangents_and_curvatures_at_both_ends() {
+        // GIVEN an S-shaped configuration with different curvatures at the ends
+        let (from_point, to_point) = (PointF64::new(0.0, 0.0), PointF64::new(20.0, 5.0));
+        let (from_tangent, to_tangent) = (PointF64::new(1.0, 1.0), PointF64::new(1.0, 0.5));
+        let (from_curvature, to_curvature) = (-0.05, 0.1);
+
+        // WHEN
+        let curve = QuinticHermite::fit(
+            from_point,
+            from_tangent,
+            from_curvature,
+            to_point,
+            to_tangent,
+            to_curvature,
+        )
+        .unwrap();
+
+        // THEN
+        assert!(curve.point_at(0.0).distance_to(from_point) < 1e-9);
+        assert!(curve.point_at(1.0).distance_to(to_point) < 1e-9);
+        let unit = |p: PointF64| p.get_normalized();
+        assert!(unit(curve.derivative_at(0.0)).distance_to(unit(from_tangent)) < 1e-9);
+        assert!(unit(curve.derivative_at(1.0)).distance_to(unit(to_tangent)) < 1e-9);
+        assert!((curve.curvature_at(0.0) - from_curvature).abs() < 1e-9);
+        assert!((curve.curvature_at(1.0) - to_curvature).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fitted_curve_approximates_a_circular_arc() {
+        // GIVEN the endpoints of a quarter circle of radius 10 centered at the origin
+        let (from_point, to_point) = (PointF64::new(10.0, 0.0), PointF64::new(0.0, 10.0));
+        let (from_tangent, to_tangent) = (PointF64::new(0.0, 1.0), PointF64::new(-1.0, 0.0));
+
+        // WHEN
+        let curve =
+            QuinticHermite::fit(from_point, from_tangent, 0.1, to_point, to_tangent, 0.1).unwrap();
+        let spline = curve.to_spline(4);
+
+        // THEN
+        for i in 0..=16 {
+            let radius = curve.point_at(i as f64 / 16.0).norm();
+            assert!((radius - 10.0).abs() < 0.05, "radius {}", radius);
+        }
+        let (length, turning_angle) = curve.measure();
+        assert!((length - 5.0 * std::f64::consts::PI).abs() < 0.1);
+        assert!((turning_angle - std::f64::consts::FRAC_PI_2).abs() < 0.05);
+        assert_eq!(spline.points.len(), 13);
+    }
+}
@@ -0,0 +1,591 @@
+use std::collections::HashMap;
+
+use visioncortex::{BinaryImage, BoundingRect, PathI32, PointF64, PointI32};
+
+/// The region of an image whose content is to be recovered.
+///
+/// A hole is either a whole axis-aligned rectangle or an arbitrary set of pixels (mask)
+/// inside its bounding rectangle. Pixels are addressed in image coordinates, whereas
+/// boundary points are the corners of pixels, which is the coordinate system used by
+/// the paths walked from the image.
+#[derive(Clone, Debug)]
+pub struct Hole {
+    rect: BoundingRect,
+    /// Pixels belonging to the hole relative to the top-left corner of 'rect'.
+    /// None if every pixel in 'rect' belongs to the hole.
+    mask: Option<BinaryImage>,
+}
+
+// API
+impl Hole {
+    pub fn from_rect(rect: BoundingRect) -> Self {
+        Self { rect, mask: None }
+    }
+
+    /// Create a hole from 'mask', which has the same size as the image to be completed.
+    /// Pixels set in 'mask' belong to the hole.
+    /// The behavior is undefined unless the set pixels form one 4-connected region.
+    pub fn from_mask(mask: &BinaryImage) -> Self {
+        let rect = mask.bounding_rect();
+        Self {
+            rect,
+            mask: Some(mask.crop_with_rect(rect)),
+        }
+    }
+
+    /// Create a hole from a closed 'polygon' in image coordinates.
+    /// A pixel belongs to the hole iff its center is inside 'polygon' (even-odd rule).
+    pub fn from_polygon(polygon: &PathI32) -> Self {
+        let polygon = polygon.to_open();
+        let rect = if polygon.is_empty() {
+            BoundingRect::default()
+        } else {
+            polygon.iter().fold(
+                BoundingRect::new_x_y_w_h(polygon[0].x, polygon[0].y, 0, 0),
+                |mut rect, p| {
+                    rect.left = std::cmp::min(rect.left, p.x);
+                    rect.top = std::cmp::min(rect.top, p.y);
+                    rect.right = std::cmp::max(rect.right, p.x);
+                    rect.bottom = std::cmp::max(rect.bottom, p.y);
+                    rect
+                },
+            )
+        };
+
+        let mut mask = BinaryImage::new_w_h(rect.width() as usize, rect.height() as usize);
+        for y in 0..mask.height {
+            for x in 0..mask.width {
+                let center = PointF64::new(
+                    (rect.left + x as i32) as f64 + 0.5,
+                    (rect.top + y as i32) as f64 + 0.5,
+                );
+                if polygon_contains_point(&polygon, center) {
+                    mask.set_pixel(x, y, true);
+                }
+            }
+        }
+
+        Self {
+            rect,
+            mask: Some(mask),
+        }
+    }
+
+    /// The bounding rect of the hole.
+    pub fn rect(&self) -> BoundingRect {
+        self.rect
+    }
+
+    /// The top-left corner of the bounding rect of the hole.
+    pub fn origin(&self) -> PointI32 {
+        PointI32::new(self.rect.left, self.rect.top)
+    }
+
+    pub fn is_rect(&self) -> bool {
+        self.mask.is_none()
+    }
+
+    /// Return true iff the pixel at 'p' (image coordinates) belongs to the hole.
+    pub fn have_pixel_inside(&self, p: PointI32) -> bool {
+        match &self.mask {
+            None => {
+                self.rect.left <= p.x
+                    && p.x < self.rect.right
+                    && self.rect.top <= p.y
+                    && p.y < self.rect.bottom
+            }
+            Some(mask) => mask.get_pixel_at_safe(p - self.origin()),
+        }
+    }
+
+    /// Return true iff the closed box from ('left', 'top') to ('right', 'bottom') (image
+    /// coordinates) is covered by the pixels of the hole, where the pixel (x, y) covers the closed
+    /// square from (x, y) to (x + 1, y + 1). Points on the boundary of the hole are covered.
+    /// False is returned if the box is empty (e.g. 'left' > 'right') or not finite.
+    pub fn covers_box(&self, left: f64, top: f64, right: f64, bottom: f64) -> bool {
+        let rect = self.rect;
+        let in_rect = rect.left as f64 <= left
+            && left <= right
+            && right <= rect.right as f64
+            && rect.top as f64 <= top
+            && top <= bottom
+            && bottom <= rect.bottom as f64;
+        if !in_rect || self.mask.is_none() {
+            return in_rect;
+        }
+
+        // The pixels each column (row) of the box may be covered by: one per column the box spans,
+        // or either of the 2 columns sharing the line the box is on, if it is only a line
+        let spans = |from: f64, to: f64| -> Vec<Vec<i32>> {
+            if from < to {
+                (from.floor() as i32..to.ceil() as i32)
+                    .map(|i| vec![i])
+                    .collect()
+            } else if from.fract() == 0.0 {
+                vec![vec![from as i32 - 1, from as i32]]
+            } else {
+                vec![vec![from.floor() as i32]]
+            }
+        };
+        let (columns, rows) = (spans(left, right), spans(top, bottom));
+        rows.iter().all(|row| {
+            columns.iter().all(|column| {
+                row.iter().any(|&y| {
+                    column
+                        .iter()
+                        .any(|&x| self.have_pixel_inside(PointI32::new(x, y)))
+                })
+            })
+        })
+    }
+
+    /// Return true iff the corner point 'p' touches the hole from outside, i.e. at least one
+    /// pixel around 'p' belongs to the hole and at least one does not, or 'p' is within
+    /// 'tolerance' pixels beyond the end of a boundary edge, on the line extending it.
+    /// This is the definition of BoundingRect::have_point_on_boundary() for rect holes, whose
+    /// sides are extended by 'tolerance' beyond the corners, so a mask covering a rect has the
+    /// same boundary as the rect for any 'tolerance'.
+    pub fn have_point_on_boundary(&self, p: PointI32, tolerance: usize) -> bool {
+        if self.mask.is_none() {
+            return self.rect.have_point_on_boundary(p, tolerance);
+        }
+        if self.touches_from_outside(p) {
+            return true;
+        }
+        let t = tolerance as i32;
+        (1..=t).any(|k| {
+            IntoIterator::into_iter([
+                PointI32::new(1, 0),
+                PointI32::new(0, 1),
+                PointI32::new(-1, 0),
+                PointI32::new(0, -1),
+            ])
+            .any(|d| {
+                let end = p + PointI32::new(d.x * k, d.y * k);
+                self.have_boundary_edge(end, end + d)
+            })
+        })
+    }
+
+    /// For definition of 'tolerance', see Hole::have_point_on_boundary().
+    pub fn have_point_on_boundary_or_inside(&self, p: PointI32, tolerance: usize) -> bool {
+        if self.mask.is_none() {
+            return self.rect.have_point_on_boundary_or_inside(p, tolerance);
+        }
+        self.have_point_on_boundary(p, tolerance) || self.have_point_strictly_inside(p)
+    }
+
+    /// Return true iff the two holes share a pixel or have 4-adjacent pixels.
+    pub fn touches(&self, other: &Hole) -> bool {
+        if !self.rect.hit(other.rect) {
+            return false;
+        }
+        self.pixels().any(|p| {
+            IntoIterator::into_iter([
+                PointI32::new(0, 0),
+                PointI32::new(1, 0),
+                PointI32::new(-1, 0),
+                PointI32::new(0, 1),
+                PointI32::new(0, -1),
+            ])
+            .any(|offset| other.have_pixel_inside(p + offset))
+        })
+    }
+
+    /// Return the hole containing the pixels of both holes.
+    /// The result is a rect hole iff it covers its whole bounding rect.
+    pub fn union(&self, other: &Hole) -> Hole {
+        let mut rect = self.rect;
+        rect.merge(other.rect);
+        let origin = PointI32::new(rect.left, rect.top);
+
+        let mut mask = BinaryImage::new_w_h(rect.width() as usize, rect.height() as usize);
+        self.pixels()
+            .chain(other.pixels())
+            .for_each(|p| mask.set_pixel_at(p - origin, true));
+
+        if mask.pixels.all() {
+            Hole::from_rect(rect)
+        } else {
+            Hole {
+                rect,
+                mask: Some(mask),
+            }
+        }
+    }
+
+    /// Merge holes that overlap or touch each other (see Hole::touches()) until
+    /// no two of the returned holes touch.
+    pub fn merge_touching(holes: &[Hole]) -> Vec<Hole> {
+        let mut merged_holes: Vec<Hole> = vec![];
+        for hole in holes.iter() {
+            let mut hole = hole.clone();
+            // Absorb every merged hole touching the current one, which may grow and touch more
+            while let Some(i) = merged_holes
+                .iter()
+                .position(|merged_hole| merged_hole.touches(&hole))
+            {
+                hole = merged_holes.remove(i).union(&hole);
+            }
+            merged_holes.push(hole);
+        }
+        merged_holes
+    }
+
+    /// A matrix of the size of the bounding rect, where each pixel is true iff it belongs to the hole.
+    pub fn to_mask(&self) -> BinaryImage {
+        match &self.mask {
+            None => {
+                let (w, h) = (self.rect.width() as usize, self.rect.height() as usize);
+                let mut mask = BinaryImage::new_w_h(w, h);
+                mask.pixels.set_all();
+                mask
+            }
+            Some(mask) => mask.clone(),
+        }
+    }
+
+    /// Starting from 'p', return the boundary points of the hole in the orientation specified by
+    /// 'clockwise'. If 'p' is not a point on boundary (strictly), the points start from the
+    /// top-left corner of the hole instead.
+    pub fn get_boundary_points_from(&self, p: PointI32, clockwise: bool) -> Vec<PointI32> {
+        if self.is_proper_rect() && self.rect.have_point_on_boundary(p, 0) {
+            return self.rect.get_boundary_points_from(p, clockwise);
+        }
+
+        let mut boundary_points = self.trace_boundary();
+        if !clockwise {
+            boundary_points.reverse();
+        }
+        if let Some(start) = boundary_points.iter().position(|&point| point == p) {
+            boundary_points.rotate_left(start);
+        }
+        boundary_points
+    }
+
+    /// Given a point on the boundary, return a pixel right outside the hole next to it.
+    pub fn get_closest_pixel_outside(&self, p: PointI32) -> PointI32 {
+        if self.is_proper_rect() && self.rect.have_point_on_boundary(p, 0) {
+            return if p.x == self.rect.right || p.y == self.rect.bottom {
+                p
+            } else {
+                self.rect.get_closest_point_outside(p)
+            };
+        }
+        Self::pixels_around(p)
+            .find(|&pixel| !self.have_pixel_inside(pixel))
+            .unwrap_or(p)
+    }
+
+    /// Given a point on the boundary, return a pixel inside the hole next to it.
+    pub fn get_closest_pixel_inside(&self, p: PointI32) -> PointI32 {
+        if self.is_proper_rect() && self.rect.have_point_on_boundary(p, 0) {
+            return if p.x == self.rect.left || p.y == self.rect.top {
+                p
+            } else {
+                self.rect.get_closest_point_inside(p)
+            };
+        }
+        Self::pixels_around(p)
+            .find(|&pixel| self.have_pixel_inside(pixel))
+            .unwrap_or(p)
+    }
+
+    /// For each of 'points', return the index of its closest point on the (outer) boundary, where
+    /// the boundary points are numbered clockwise from the top-left corner of the top-most pixel.
+    /// Sorting points on the boundary by their positions thus orders them along the boundary.
+    pub fn get_boundary_positions(&self, points: &[PointI32]) -> Vec<usize> {
+        let boundary_points = self.trace_boundary();
+        let positions: HashMap<PointI32, usize> = boundary_points
+            .iter()
+            .enumerate()
+            .map(|(position, &point)| (point, position))
+            .collect();
+        points
+            .iter()
+            .map(|&p| match positions.get(&p) {
+                Some(&position) => position,
+                None => boundary_points
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|&(_, &point)| {
+                        let diff = point - p;
+                        diff.x * diff.x + diff.y * diff.y
+                    })
+                    .map_or(0, |(position, _)| position),
+            })
+            .collect()
+    }
+
+    /// Return the closest point on the (outer) boundary to 'p', which may be slightly off the boundary.
+    pub fn snap_to_boundary(&self, p: PointI32) -> PointI32 {
+        match &self.mask {
+            None => self.snap_to_rect_boundary(p),
+            Some(_) => self
+                .trace_boundary()
+                .into_iter()
+                .min_by_key(|&point| {
+                    let diff = point - p;
+                    diff.x * diff.x + diff.y * diff.y
+                })
+                .unwrap_or(p),
+        }
+    }
+}
+
+// Helper functions
+impl Hole {
+    /// All pixels (image coordinates) belonging to the hole.
+    fn pixels(&self) -> impl Iterator<Item = PointI32> + '_ {
+        let rect = self.rect;
+        (rect.top..rect.bottom)
+            .flat_map(move |y| (rect.left..rect.right).map(move |x| PointI32::new(x, y)))
+            .filter(move |&p| self.have_pixel_inside(p))
+    }
+
+    /// Whether at least one pixel around the corner point 'p' belongs to the hole and at least one
+    /// does not.
+    fn touches_from_outside(&self, p: PointI32) -> bool {
+        let mut pixels = Self::pixels_around(p).map(|pixel| self.have_pixel_inside(pixel));
+        let first = pixels.next().unwrap_or(false);
+        pixels.any(|inside| inside != first)
+    }
+
+    /// Whether the edge of unit length between the corner points 'from' and 'to' separates a pixel
+    /// of the hole from a pixel not belonging to it.
+    fn have_boundary_edge(&self, from: PointI32, to: PointI32) -> bool {
+        let corner = PointI32::new(from.x.min(to.x), from.y.min(to.y));
+        let (before, after) = if from.x == to.x {
+            (corner - PointI32::new(1, 0), corner)
+        } else {
+            (corner - PointI32::new(0, 1), corner)
+        };
+        self.have_pixel_inside(before) != self.have_pixel_inside(after)
+    }
+
+    /// The 4 pixels sharing the corner point 'p'.
+    fn pixels_around(p: PointI32) -> impl Iterator<Item = PointI32> {
+        IntoIterator::into_iter([
+            PointI32::new(-1, -1),
+            PointI32::new(0, -1),
+            PointI32::new(-1, 0),
+            PointI32::new(0, 0),
+        ])
+        .map(move |offset| p + offset)
+    }
+
+    /// Whether the hole is a rect that is at least 2 pixels wide and tall, for which the boundary
+    /// utilities of BoundingRect are well-defined.
+    fn is_proper_rect(&self) -> bool {
+        self.mask.is_none() && self.rect.width() > 1 && self.rect.height() > 1
+    }
+
+    fn have_point_strictly_inside(&self, p: PointI32) -> bool {
+        Self::pixels_around(p).all(|pixel| self.have_pixel_inside(pixel))
+    }
+
+    fn snap_to_rect_boundary(&self, endpoint: PointI32) -> PointI32 {
+        let hole_rect = &self.rect;
+        if hole_rect.have_point_on_boundary(endpoint, 0) {
+            endpoint
+        } else {
+            // Determine if endpoint is vertically or horizontally aligned with the rect
+            if hole_rect.left <= endpoint.x && endpoint.x <= hole_rect.right {
+                // Should be adjusted to either top or bottom side
+                PointI32::new(
+                    endpoint.x,
+                    if (hole_rect.top - endpoint.y).abs() < (hole_rect.bottom - endpoint.y).abs() {
+                        hole_rect.top
+                    } else {
+                        hole_rect.bottom
+                    },
+                )
+            } else if hole_rect.top <= endpoint.y && endpoint.y <= hole_rect.bottom {
+                // Should be adjusted to either left and right side
+                PointI32::new(
+                    if (hole_rect.left - endpoint.x).abs() < (hole_rect.right - endpoint.x).abs() {
+                        hole_rect.left
+                    } else {
+                        hole_rect.right
+                    },
+                    endpoint.y,
+                )
+            } else {
+                // Should be adjusted to one of the corners
+                IntoIterator::into_iter([
+                    hole_rect.top_left(),
+                    hole_rect.top_right(),
+                    hole_rect.bottom_left(),
+                    hole_rect.bottom_right(),
+                ])
+                .min_by_key(|&corner| {
+                    endpoint.to_point_f64().distance_to(corner.to_point_f64()) as i32
+                })
+                .unwrap()
+            }
+        }
+    }
+
+    /// Walk the outer boundary of the hole clockwise with unit steps, keeping the hole on the right.
+    /// Walking starts from the top-left corner of the top-most (then left-most) pixel of the hole.
+    /// At pinch points (diagonally touching pixels), the walk always turns right.
+    fn trace_boundary(&self) -> Vec<PointI32> {
+        let start = match self.pixels().next() {
+            Some(start) => start,
+            None => return vec![],
+        };
+
+        // Offsets of the pixels ahead-right and ahead-left of a corner point, given the direction
+        let ahead_pixels = |direction: PointI32| match (direction.x, direction.y) {
+            (1, 0) => (PointI32::new(0, 0), PointI32::new(0, -1)),
+            (0, 1) => (PointI32::new(-1, 0), PointI32::new(0, 0)),
+            (-1, 0) => (PointI32::new(-1, -1), PointI32::new(-1, 0)),
+            _ => (PointI32::new(0, -1), PointI32::new(-1, -1)),
+        };
+        let turn_right = |d: PointI32| PointI32::new(-d.y, d.x);
+        let turn_left = |d: PointI32| PointI32::new(d.y, -d.x);
+
+        let mut boundary_points = vec![];
+        let mut curr = start;
+        let mut direction = PointI32::new(1, 0);
+        // Each boundary point is visited once, except at pinches
+        let max_num_points =
+            2 * (self.rect.width() as usize + 1) * (self.rect.height() as usize + 1);
+        while boundary_points.len() < max_num_points {
+            boundary_points.push(curr);
+            curr += direction;
+            // 'start' can never be a pinch, so the walk is complete once it is reached again
+            if curr == start {
+                break;
+            }
+            let (ahead_right, ahead_left) = ahead_pixels(direction);
+            direction = if !self.have_pixel_inside(curr + ahead_right) {
+                turn_right(direction)
+            } else if self.have_pixel_inside(curr + ahead_left) {
+                turn_left(direction)
+            } else {
+                direction
+            };
+        }
+
+        boundary_points
+    }
+}
+
+/// Even-odd rule point-in-polygon test. 'polygon' is considered closed.
+fn polygon_contains_point(polygon: &PathI32, p: PointF64) -> bool {
+    let len = polygon.len();
+    let mut inside = false;
+    for i in 0..len {
+        let (a, b) = (
+            polygon[i].to_point_f64(),
+            polygon[(i + 1) % len].to_point_f64(),
+        );
+        if (a.y > p.y) != (b.y > p.y) {
+            let x_intersection = a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x);
+            if p.x < x_intersection {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mask_hole_boundary_matches_rect_hole_boundary() {
+        // GIVEN
+        let rect = BoundingRect::new_x_y_w_h(3, 4, 5, 2);
+        let mut mask = BinaryImage::new_w_h(12, 12);
+        for y in rect.top..rect.bottom {
+            for x in rect.left..rect.right {
+                mask.set_pixel(x as usize, y as usize, true);
+            }
+        }
+        let rect_hole = Hole::from_rect(rect);
+        let mask_hole = Hole::from_mask(&mask);
+
+        // WHEN
+        let start = rect.top_left();
+        let rect_boundary = rect_hole.get_boundary_points_from(start, true);
+        let mask_boundary = mask_hole.get_boundary_points_from(start, true);
+
+        // THEN
+        assert_eq!(rect_hole.rect(), mask_hole.rect());
+        assert_eq!(rect_boundary, mask_boundary);
+        assert!(mask_boundary
+            .iter()
+            .all(|&p| mask_hole.have_point_on_boundary(p, 0)));
+        // Including the tolerance used when walking paths
+        for tolerance in 0..=2 {
+            for y in 0..12 {
+                for x in 0..12 {
+                    let p = PointI32::new(x, y);
+                    assert_eq!(
+                        rect_hole.have_point_on_boundary(p, tolerance),
+                        mask_hole.have_point_on_boundary(p, tolerance),
+                        "{:?} at tolerance {}",
+                        p,
+                        tolerance
+                    );
+                    assert_eq!(
+                        rect_hole.have_point_on_boundary_or_inside(p, tolerance),
+                        mask_hole.have_point_on_boundary_or_inside(p, tolerance),
+                        "{:?} at tolerance {}",
+                        p,
+                        tolerance
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn polygon_hole_excludes_pixels_outside_triangle() {
+        // GIVEN
+        let triangle = PathI32::from_points(vec![
+            PointI32::new(0, 0),
+            PointI32::new(8, 0),
+            PointI32::new(0, 8),
+        ]);
+
+        // WHEN
+        let hole = Hole::from_polygon(&triangle);
+
+        // THEN
+        assert!(hole.have_pixel_inside(PointI32::new(1, 1)));
+        assert!(!hole.have_pixel_inside(PointI32::new(6, 6)));
+        assert!(hole.have_point_on_boundary(PointI32::new(4, 4), 0));
+        assert!(!hole.have_point_on_boundary(PointI32::new(7, 7), 0));
+        let boundary = hole.get_boundary_points_from(PointI32::new(0, 0), true);
+        assert!(boundary
+            .windows(2)
+            .all(|w| (w[1] - w[0]).x.abs() + (w[1] - w[0]).y.abs() == 1));
+    }
+
+    #[test]
+    fn touching_holes_are_merged() {
+        // GIVEN
+        let holes = [
+            Hole::from_rect(BoundingRect::new_x_y_w_h(0, 0, 4, 4)),
+            Hole::from_rect(BoundingRect::new_x_y_w_h(10, 0, 4, 4)),
+            Hole::from_rect(BoundingRect::new_x_y_w_h(4, 0, 6, 4)),
+            Hole::from_rect(BoundingRect::new_x_y_w_h(15, 5, 2, 2)),
+        ];
+
+        // WHEN
+        let merged_holes = Hole::merge_touching(&holes);
+
+        // THEN
+        assert_eq!(merged_holes.len(), 2);
+        assert!(merged_holes[0].is_rect());
+        assert_eq!(
+            merged_holes[0].rect(),
+            BoundingRect::new_x_y_w_h(0, 0, 14, 4)
+        );
+        assert_eq!(merged_holes[1].rect(), holes[3].rect());
+    }
+}
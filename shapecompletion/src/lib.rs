@@ -1,7 +1,20 @@
+mod blossom;
+mod clothoid;
 pub mod completor;
+mod conic;
 pub mod curve;
 pub mod debugger;
+pub mod error;
 pub mod filler;
+pub mod fragment;
 mod geo;
+mod hermite;
+pub mod hole;
+pub mod match_cost;
 pub mod matcher;
 pub mod matcher_helper;
+pub mod score;
+pub mod simplify;
+pub mod skeleton;
+pub mod symmetry;
+mod tangent;
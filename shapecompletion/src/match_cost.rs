@@ -0,0 +1,253 @@
+use visioncortex::{PointF64, PointI32};
+
+use crate::matcher_helper::{MatchItem, MatchItemSet, MatchSegment};
+
+/// A model of how likely match items are to be the ends of the same curve, used by Matcher to
+/// rank matchings. Lower costs are better.
+pub trait MatchCost {
+    /// The cost of pairing 'item1' with 'item2', which are the tails of 'segment1' and 'segment2'.
+    /// The behavior is undefined unless the returned cost is finite and non-negative.
+    fn pair_cost(
+        &self,
+        item1: &MatchItem,
+        segment1: &MatchSegment,
+        item2: &MatchItem,
+        segment2: &MatchSegment,
+    ) -> f64;
+
+    /// The cost of partitioning the items into 'set1' and 'set2', where items in one set are only
+    /// paired with items in the other set. Used by MatcherAlgorithm::Exhaustive to order partitions.
+    /// By default, the average variance of directions within the 2 sets.
+    fn partition_cost(&self, set1: &MatchItemSet, set2: &MatchItemSet) -> f64 {
+        calculate_average_direction_variance(set1, set2)
+    }
+
+    /// The factor pair costs are multiplied by before they are truncated to integers for the
+    /// Hungarian algorithm, which MatcherAlgorithm::Exhaustive pairs the items of partitions by.
+    /// By default 1.0, i.e. costs are truncated to whole pixels of distance.
+    fn hungarian_cost_scale(&self) -> f64 {
+        1.0
+    }
+}
+
+/// The Euclidean distance between the items.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultMatchCost;
+
+/// The distance between the items, multiplied by 1 plus how much their directions deviate from
+/// pointing at each other along the line joining them ([0.0, 4.0]).
+/// Facing items (e.g. both ends of a stroke crossing the hole) are thus preferred over close
+/// items pointing in parallel (e.g. both sides of the same stroke), which matters to matchers
+/// that do not partition the items by direction first (see MatcherAlgorithm::default_match_cost()).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FacingMatchCost;
+
+/// Cost of good continuation (Gestalt): the distance between the items, multiplied by 1 plus the
+/// weighted sum of their direction deviation (see FacingMatchCost), their curvature discontinuity
+/// and whether they close up the same contour.
+#[derive(Clone, Copy, Debug)]
+pub struct GestaltMatchCost {
+    /// Weight of the deviation of directions from the line joining the items ([0.0, 4.0])
+    pub tangent_weight: f64,
+    /// Weight of the difference in curvature of the 2 segments, when one continues into the other,
+    /// multiplied by the distance between the items (i.e. the mismatch in turning angle)
+    pub curvature_weight: f64,
+    /// Subtracted if both segments are on the same path, so that a contour partially covered by
+    /// the hole tends to be closed up. The total multiplier is never below 0.
+    pub closure_bonus: f64,
+}
+
+impl Default for GestaltMatchCost {
+    fn default() -> Self {
+        Self {
+            tangent_weight: 1.0,
+            curvature_weight: 1.0,
+            closure_bonus: 0.25,
+        }
+    }
+}
+
+impl MatchCost for DefaultMatchCost {
+    fn pair_cost(
+        &self,
+        item1: &MatchItem,
+        _segment1: &MatchSegment,
+        item2: &MatchItem,
+        _segment2: &MatchSegment,
+    ) -> f64 {
+        item1.point.distance_to(item2.point)
+    }
+}
+
+impl MatchCost for FacingMatchCost {
+    fn pair_cost(
+        &self,
+        item1: &MatchItem,
+        _segment1: &MatchSegment,
+        item2: &MatchItem,
+        _segment2: &MatchSegment,
+    ) -> f64 {
+        let distance = item1.point.distance_to(item2.point);
+        distance * (1.0 + calculate_direction_deviation(item1, item2))
+    }
+
+    /// Scaled, so that the fractional differences made by the direction deviation are not lost.
+    fn hungarian_cost_scale(&self) -> f64 {
+        1024.0
+    }
+}
+
+impl MatchCost for GestaltMatchCost {
+    fn pair_cost(
+        &self,
+        item1: &MatchItem,
+        segment1: &MatchSegment,
+        item2: &MatchItem,
+        segment2: &MatchSegment,
+    ) -> f64 {
+        let distance = item1.point.distance_to(item2.point);
+        // Walking into the hole along 'segment1' and out of it along 'segment2' reverses the
+        // walking direction of 'segment2', which negates its signed curvature
+        let curvature_difference =
+            (calculate_tail_curvature(segment1) + calculate_tail_curvature(segment2)).abs();
+        let closure_bonus = if segment1.path_index == segment2.path_index {
+            self.closure_bonus
+        } else {
+            0.0
+        };
+        let multiplier = 1.0
+            + self.tangent_weight * calculate_direction_deviation(item1, item2)
+            + self.curvature_weight * curvature_difference * distance
+            - closure_bonus;
+        distance * multiplier.max(0.0)
+    }
+
+    /// Scaled, so that the fractional differences made by the multiplier are not lost.
+    fn hungarian_cost_scale(&self) -> f64 {
+        1024.0
+    }
+}
+
+/// How much the directions of 'item1' and 'item2' deviate from pointing at each other along the
+/// line joining them, in [0.0, 4.0]. 2.0 is returned if any of the directions is zero, or the
+/// items are at the same point.
+pub fn calculate_direction_deviation(item1: &MatchItem, item2: &MatchItem) -> f64 {
+    let distance = item1.point.distance_to(item2.point);
+    if distance == 0.0 {
+        return 2.0;
+    }
+    let unit = (item2.point - item1.point) / distance;
+    let dot = |a: PointF64, b: PointF64| a.x * b.x + a.y * b.y;
+    let deviation = (1.0 - dot(item1.direction.get_normalized(), unit))
+        + (1.0 + dot(item2.direction.get_normalized(), unit));
+    if deviation.is_finite() {
+        deviation
+    } else {
+        2.0
+    }
+}
+
+/// The average variance of directions within 'set1' and within 'set2'.
+pub fn calculate_average_direction_variance(set1: &MatchItemSet, set2: &MatchItemSet) -> f64 {
+    let calculate_average_direction = |set: &MatchItemSet| {
+        let len = set.len();
+        let sum_direction: PointF64 = set
+            .iter()
+            .fold(PointF64::default(), |acc, item2| acc + item2.direction);
+        let average_direction = sum_direction / (len as f64);
+        average_direction.get_normalized()
+    };
+
+    let calculate_variance = |set: &MatchItemSet| {
+        let len = set.len();
+        if len == 1 {
+            return 0.0;
+        }
+
+        let average_direction = calculate_average_direction(set);
+        let sum_distances: f64 = set
+            .iter()
+            .map(|item| {
+                item.direction
+                    .get_normalized()
+                    .distance_to(average_direction)
+            })
+            .sum();
+        sum_distances / (len - 1) as f64
+    };
+
+    let (variance1, variance2) = (calculate_variance(set1), calculate_variance(set2));
+    (variance1 + variance2) / 2.0
+}
+
+/// The signed curvature of 'segment' at its tail, when walked towards the tail, estimated from
+/// its first 3 points. 0.0 is returned if there are fewer than 3 distinct points.
+fn calculate_tail_curvature(segment: &MatchSegment) -> f64 {
+    let points = &segment.points.path;
+    if points.len() < 3 {
+        return 0.0;
+    }
+    let to_point_f64 = |p: PointI32| p.to_point_f64();
+    let (p0, p1, p2) = (
+        to_point_f64(points[0]),
+        to_point_f64(points[1]),
+        to_point_f64(points[2]),
+    );
+    let (v1, v2) = (p1 - p2, p0 - p1);
+    let (length1, length2) = (v1.norm(), v2.norm());
+    if length1 == 0.0 || length2 == 0.0 {
+        return 0.0;
+    }
+    let turning_angle = (v1.x * v2.y - v1.y * v2.x).atan2(v1.x * v2.x + v1.y * v2.y);
+    turning_angle / ((length1 + length2) / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use visioncortex::PathI32;
+
+    use super::*;
+
+    #[test]
+    fn gestalt_cost_prefers_continuous_curvature() {
+        // GIVEN 2 arcs of the same circle (radius 100) approaching a gap at its top from both sides,
+        // and the second arc reflected about its tangent at the gap
+        let point_at = |degrees: f64| {
+            let radians = degrees.to_radians();
+            PointF64::new(radians.cos(), radians.sin()) * 100.0
+        };
+        let to_segment = |points: Vec<PointF64>| MatchSegment {
+            points: PathI32::from_points(points.into_iter().map(|p| p.to_point_i32()).collect()),
+            path_index: 0,
+        };
+        let item1 = MatchItem::new_with_default_id(point_at(120.0), PointF64::new(0.866, 0.5));
+        let segment1 = to_segment(vec![point_at(120.0), point_at(135.0), point_at(150.0)]);
+        let item2 = MatchItem::new_with_default_id(point_at(60.0), PointF64::new(-0.866, 0.5));
+        let segment2 = to_segment(vec![point_at(60.0), point_at(45.0), point_at(30.0)]);
+        let normal = point_at(60.0) / 100.0;
+        let reflect = |p: PointF64| {
+            let offset = p - item2.point;
+            p - normal * (2.0 * (offset.x * normal.x + offset.y * normal.y))
+        };
+        let reflected_segment2 = to_segment(vec![
+            point_at(60.0),
+            reflect(point_at(45.0)),
+            reflect(point_at(30.0)),
+        ]);
+
+        // WHEN
+        let gestalt = GestaltMatchCost::default();
+        let continuous_cost = gestalt.pair_cost(&item1, &segment1, &item2, &segment2);
+        let discontinuous_cost = gestalt.pair_cost(&item1, &segment1, &item2, &reflected_segment2);
+
+        // THEN
+        assert!(continuous_cost < discontinuous_cost);
+        let facing_cost = FacingMatchCost.pair_cost(&item1, &segment1, &item2, &segment2);
+        assert!(
+            (facing_cost
+                - FacingMatchCost.pair_cost(&item1, &segment1, &item2, &reflected_segment2))
+            .abs()
+                < f64::EPSILON
+        );
+    }
+}
@@ -1,12 +1,161 @@
 use std::{
-    collections::{HashMap, HashSet},
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
     convert::TryInto,
 };
 
+use crate::{
+    blossom::max_weight_matching,
+    error::CompletionError,
+    match_cost::{DefaultMatchCost, FacingMatchCost, MatchCost},
+    matcher_helper::{
+        MatchItem, MatchItemSet, MatchSegment, Matching, PairCostMatrix, SquareDistanceMatrix,
+    },
+    score::ScoreWeights,
+};
 use permutator::{factorial, multiply_factorial, Combination};
-use visioncortex::PointF64;
 
-use crate::matcher_helper::{MatchItem, MatchItemSet, Matching, SquareDistanceMatrix};
+/// The largest number of match items whose matchings are enumerated exhaustively.
+/// (The number of balanced partitions, each matched by the Hungarian algorithm, grows
+/// exponentially: 462 for 12 items, but 92378 for 20 items)
+pub const MAX_NUM_MATCH_ITEMS: usize = 12;
+
+/// Pair costs are scaled by this factor and rounded to integers when ranking matchings.
+const RANKED_COST_SCALE: f64 = 1024.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatcherAlgorithm {
+    /// Enumerate the matchings of all balanced partitions of the items, sorted by the partition
+    /// costs (see MatchCost::partition_cost()), up to 'max_num_exhaustive_matchings' (see
+    /// MatcherConfig) of them.
+    /// For more than MAX_NUM_MATCH_ITEMS items, this silently switches to MatcherAlgorithm::Ranked,
+    /// without an error.
+    Exhaustive,
+    /// Find the matching with the minimum total cost on the complete graph of the items,
+    /// followed by the next best matchings in ascending order of total cost.
+    /// Each matching takes polynomial time to generate: about 4 ms for the first one and 0.5 s for
+    /// 16 of them across 128 endpoints (see benches/matcher.rs).
+    Ranked,
+    /// Find the matchings with the minimum total costs among the matchings whose pairs do not
+    /// cross each other, in ascending order of total cost.
+    /// See Matcher::find_best_non_crossing_matchings() for the order the items must be in.
+    NonCrossing,
+}
+
+impl MatcherAlgorithm {
+    /// The match cost used where none is specified: DefaultMatchCost (distance) for
+    /// MatcherAlgorithm::Exhaustive, whose partitions already separate the items by direction, and
+    /// FacingMatchCost for the others, which may pair any 2 items, where the closest items are often
+    /// the 2 sides of the same stroke.
+    pub fn default_match_cost(&self) -> Box<dyn MatchCost> {
+        match self {
+            MatcherAlgorithm::Exhaustive => Box::new(DefaultMatchCost),
+            MatcherAlgorithm::Ranked | MatcherAlgorithm::NonCrossing => Box::new(FacingMatchCost),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MatcherConfig {
+    /// Whether items may be left unmatched. If so, an odd number of items is
+    /// allowed, and exactly one item is left unmatched in that case.
+    pub allow_unmatched: bool,
+    pub algorithm: MatcherAlgorithm,
+    /// The maximum number of matchings generated by MatcherAlgorithm::Ranked and
+    /// MatcherAlgorithm::NonCrossing.
+    pub max_num_ranked_matchings: usize,
+    /// The maximum number of matchings returned by MatcherAlgorithm::Exhaustive.
+    pub max_num_exhaustive_matchings: usize,
+    /// The number of successfully intrapolated matchings to be scored, among which the one with
+    /// the lowest score is chosen. 1 means the first successful matching is chosen.
+    pub num_evaluated_matchings: usize,
+    pub score_weights: ScoreWeights,
+    /// Whether the curves of 2 pairs may cross each other inside the hole, as where 2 strokes form
+    /// an X. Each 2 curves may only cross once, at an angle of at least 'min_crossing_angle'.
+    /// MatcherAlgorithm::NonCrossing never generates such matchings.
+    pub allow_crossings: bool,
+    /// The smallest angle (in radians) at which 2 curves may cross each other
+    pub min_crossing_angle: f64,
+    /// Whether an unmatched item may be extended until it meets the curve of a pair, as where a
+    /// stroke ends on another one to form a T, instead of ending inside the hole.
+    /// Items are only left unmatched if 'allow_unmatched' is true.
+    pub allow_t_junctions: bool,
+}
+
+impl Default for MatcherConfig {
+    fn default() -> Self {
+        Self {
+            allow_unmatched: false,
+            algorithm: MatcherAlgorithm::Exhaustive,
+            max_num_ranked_matchings: 16,
+            max_num_exhaustive_matchings: 1024,
+            num_evaluated_matchings: 1,
+            score_weights: ScoreWeights::default(),
+            allow_crossings: false,
+            min_crossing_angle: std::f64::consts::FRAC_PI_4,
+            allow_t_junctions: false,
+        }
+    }
+}
+
+/// A lazily generated list of perfect matchings of match items in ascending order of total
+/// pair cost, generated by partitioning the solution space (Murty's/Lawler's method).
+/// Each matching is found as a minimum-weight perfect matching on a general graph.
+pub struct RankedMatchings {
+    /// The item id of each vertex, or None for the dummy vertex that pairs with the unmatched item
+    vertex_ids: Vec<Option<usize>>,
+    /// Row-major matrix of the costs of pairing each 2 vertices
+    costs: Vec<i64>,
+    subproblems: BinaryHeap<Reverse<RankedSubproblem>>,
+    num_subproblems: usize,
+}
+
+/// The best non-crossing matchings of each interval of the items along the hole boundary,
+/// found by dynamic programming.
+struct NonCrossingMatchings {
+    num_items: usize,
+    max_num_matchings: usize,
+    /// Row-major matrix of the costs of pairing each 2 items
+    costs: Vec<f64>,
+    /// The best matchings of the items in [i, j) with u items unmatched, in ascending order of
+    /// cost, at index (i * (num_items + 1) + j) * 2 + u
+    table: Vec<Vec<NonCrossingEntry>>,
+}
+
+#[derive(Clone, Copy)]
+struct NonCrossingEntry {
+    cost: f64,
+    choice: NonCrossingChoice,
+}
+
+/// How the first item of an interval is matched, referring to entries of the sub-intervals by rank
+#[derive(Clone, Copy)]
+enum NonCrossingChoice {
+    /// The interval is empty
+    Empty,
+    /// The first item is unmatched, and the rest are matched
+    Unmatched { rest_rank: usize },
+    /// The first item is paired with item 'k', so the items between them are matched
+    /// among themselves, and so are the items after 'k'
+    Paired {
+        k: usize,
+        inner_num_unmatched: usize,
+        inner_rank: usize,
+        right_rank: usize,
+    },
+}
+
+/// A subset of the matchings, which contain all 'forced' pairs and none of the 'forbidden' pairs.
+struct RankedSubproblem {
+    /// The cost of the best matching of this subproblem if solved, otherwise a lower bound of it
+    cost: i64,
+    /// Used to break ties in the order of creation
+    order: usize,
+    forced: Vec<(usize, usize)>,
+    forbidden: Vec<(usize, usize)>,
+    /// The best matching of this subproblem, or None if not yet solved
+    solution: Option<Vec<(usize, usize)>>,
+}
 
 /// Given a set of an even number of 2-D points and direction vectors at those points,
 /// find a complete, disjoint, pair matching of those points such that the sum of distances between the pairs is at minimum.
@@ -40,18 +189,506 @@ impl Matcher {
         )
     }
 
-    /// Find all possible matchings for each possible partition.
-    /// The behavior is undefined unless 'match_items' contains n items where n is even and n>0.
-    pub fn find_all_possible_matchings(match_items: MatchItemSet) -> Result<Vec<Matching>, String> {
+    /// Find the candidate matchings in the order they should be tried, using the algorithm in 'config'.
+    /// For MatcherAlgorithm::Exhaustive, all possible matchings for each possible partition are found,
+    /// and the first 'max_num_exhaustive_matchings' of them are returned, unless there are more than
+    /// MAX_NUM_MATCH_ITEMS items, where MatcherAlgorithm::Ranked is used.
+    /// If 'config' allows unmatched items and there is an odd number of items, each item takes turn
+    /// to be left unmatched. The items are tried in ascending order of the total pair cost of the best
+    /// matching of the remaining items, and all matchings of one choice come before those of the next.
+    /// For MatcherAlgorithm::Ranked, the first 'max_num_ranked_matchings' matchings of
+    /// 'rank_matchings' are returned.
+    /// For MatcherAlgorithm::NonCrossing, 'find_best_non_crossing_matchings' is used.
+    /// Matchings are ranked by 'match_cost', where segments[id] is the segment of the item with that id.
+    pub fn find_all_possible_matchings(
+        match_items: MatchItemSet,
+        segments: &[MatchSegment],
+        match_cost: &dyn MatchCost,
+        config: &MatcherConfig,
+    ) -> Result<Vec<Matching>, CompletionError> {
         let len = match_items.len();
-        if len % 2 != 0 {
-            return Err("There must be an even number of match items.".into());
+        if len == 0 {
+            return Err(CompletionError::NoEndpoints);
+        }
+        match config.algorithm {
+            MatcherAlgorithm::Exhaustive if len <= MAX_NUM_MATCH_ITEMS => {}
+            MatcherAlgorithm::Exhaustive | MatcherAlgorithm::Ranked => {
+                return Ok(Self::rank_matchings(
+                    &match_items,
+                    segments,
+                    match_cost,
+                    config.allow_unmatched,
+                )?
+                .take(config.max_num_ranked_matchings)
+                .collect());
+            }
+            MatcherAlgorithm::NonCrossing => {
+                return Self::find_best_non_crossing_matchings(
+                    &match_items,
+                    segments,
+                    match_cost,
+                    config.allow_unmatched,
+                    config.max_num_ranked_matchings,
+                );
+            }
+        }
+        let pair_costs = PairCostMatrix::from_match_items(&match_items, segments, match_cost);
+        if len.is_multiple_of(2) {
+            let mut matchings =
+                Self::find_all_possible_complete_matchings(&match_items, &pair_costs, match_cost);
+            matchings.truncate(config.max_num_exhaustive_matchings);
+            return Ok(matchings);
+        }
+        if !config.allow_unmatched {
+            return Err(CompletionError::OddNumberOfEndpoints(len));
+        }
+
+        let mut matchings_with_costs: Vec<(Vec<Matching>, f64)> = (0..len)
+            .map(|unmatched_index| {
+                let mut remaining_items = MatchItemSet::new();
+                match_items
+                    .iter()
+                    .enumerate()
+                    .filter(|&(i, _)| i != unmatched_index)
+                    .for_each(|(_, &item)| remaining_items.push_as_is(item));
+
+                let unmatched = vec![match_items[unmatched_index].id];
+                let matchings: Vec<Matching> = Self::find_all_possible_complete_matchings(
+                    &remaining_items,
+                    &pair_costs,
+                    match_cost,
+                )
+                .into_iter()
+                .map(|matching| {
+                    Matching::from_pairs_and_unmatched(matching.index_pairs, unmatched.clone())
+                })
+                .collect();
+                let cost = matchings
+                    .iter()
+                    .map(|matching| Self::calculate_total_cost(&pair_costs, matching))
+                    .fold(f64::INFINITY, f64::min);
+                (matchings, cost)
+            })
+            .collect();
+
+        // Sort by cost (NaN at last)
+        matchings_with_costs.sort_by(|(_, cost1), (_, cost2)| {
+            cost1
+                .partial_cmp(cost2)
+                .unwrap_or_else(|| cost1.is_nan().cmp(&cost2.is_nan()))
+        });
+
+        Ok(matchings_with_costs
+            .into_iter()
+            .flat_map(|(matchings, _)| matchings)
+            .take(config.max_num_exhaustive_matchings)
+            .collect())
+    }
+
+    /// Return the matchings of 'match_items' lazily, in ascending order of total pair cost,
+    /// as evaluated by 'match_cost' (segments[id] is the segment of the item with that id).
+    /// If 'allow_unmatched' is true and there is an odd number of items, every matching leaves
+    /// exactly one item unmatched.
+    pub fn rank_matchings(
+        match_items: &MatchItemSet,
+        segments: &[MatchSegment],
+        match_cost: &dyn MatchCost,
+        allow_unmatched: bool,
+    ) -> Result<RankedMatchings, CompletionError> {
+        let len = match_items.len();
+        if len == 0 {
+            return Err(CompletionError::NoEndpoints);
+        }
+        if !len.is_multiple_of(2) && !allow_unmatched {
+            return Err(CompletionError::OddNumberOfEndpoints(len));
         }
+        let pair_costs = PairCostMatrix::from_match_items(match_items, segments, match_cost);
+        Ok(RankedMatchings::new(match_items, &pair_costs))
+    }
+
+    /// Find at most 'max_num_matchings' matchings with the minimum total pair costs, in ascending order
+    /// of total cost, among the matchings whose pairs do not cross each other.
+    /// Pair costs are evaluated by 'match_cost' (segments[id] is the segment of the item with that id).
+    /// Two pairs (a, b) and (c, d) cross iff exactly one of c and d lies between a and b in the order
+    /// of 'match_items', which is considered cyclic. Since curves inside a hole connecting points on
+    /// its boundary must intersect if their endpoints interleave along the boundary, only the non-crossing
+    /// matchings can be intrapolated without intersections when the items are in boundary order.
+    /// If 'allow_unmatched' is true and there is an odd number of items, every matching leaves
+    /// exactly one item unmatched.
+    /// The behavior is undefined unless the items of 'match_items' are in order along the hole boundary.
+    pub fn find_best_non_crossing_matchings(
+        match_items: &MatchItemSet,
+        segments: &[MatchSegment],
+        match_cost: &dyn MatchCost,
+        allow_unmatched: bool,
+        max_num_matchings: usize,
+    ) -> Result<Vec<Matching>, CompletionError> {
+        let len = match_items.len();
         if len == 0 {
-            return Err("There must be some match items.".into());
+            return Err(CompletionError::NoEndpoints);
+        }
+        if !len.is_multiple_of(2) && !allow_unmatched {
+            return Err(CompletionError::OddNumberOfEndpoints(len));
+        }
+        let pair_costs = PairCostMatrix::from_match_items(match_items, segments, match_cost);
+        Ok(
+            NonCrossingMatchings::new(match_items, &pair_costs, max_num_matchings)
+                .into_matchings(match_items),
+        )
+    }
+}
+
+impl NonCrossingMatchings {
+    fn new(
+        match_items: &MatchItemSet,
+        pair_costs: &PairCostMatrix,
+        max_num_matchings: usize,
+    ) -> Self {
+        let n = match_items.len();
+        let mut costs = vec![0.0; n * n];
+        for i in 0..n {
+            for j in 0..n {
+                costs[i * n + j] = pair_costs.cost(match_items[i].id, match_items[j].id);
+            }
+        }
+
+        let mut matchings = Self {
+            num_items: n,
+            max_num_matchings,
+            costs,
+            table: vec![vec![]; (n + 1) * (n + 1) * 2],
+        };
+        for length in 0..=n {
+            for i in 0..=(n - length) {
+                for num_unmatched in 0..2 {
+                    let entries = matchings.find_best_entries(i, i + length, num_unmatched);
+                    let index = matchings.table_index(i, i + length, num_unmatched);
+                    matchings.table[index] = entries;
+                }
+            }
+        }
+        matchings
+    }
+
+    fn table_index(&self, i: usize, j: usize, num_unmatched: usize) -> usize {
+        (i * (self.num_items + 1) + j) * 2 + num_unmatched
+    }
+
+    fn entries(&self, i: usize, j: usize, num_unmatched: usize) -> &[NonCrossingEntry] {
+        &self.table[self.table_index(i, j, num_unmatched)]
+    }
+
+    /// The best entries of [i, j) with 'num_unmatched' items unmatched, given that those of
+    /// all shorter intervals are found.
+    fn find_best_entries(&self, i: usize, j: usize, num_unmatched: usize) -> Vec<NonCrossingEntry> {
+        let max_num_matchings = self.max_num_matchings;
+        if i == j {
+            return if num_unmatched == 0 && max_num_matchings > 0 {
+                vec![NonCrossingEntry {
+                    cost: 0.0,
+                    choice: NonCrossingChoice::Empty,
+                }]
+            } else {
+                vec![]
+            };
+        }
+        if !(j - i - num_unmatched).is_multiple_of(2) {
+            return vec![];
+        }
+
+        let mut candidates = vec![];
+        if num_unmatched == 1 {
+            candidates.extend(self.entries(i + 1, j, 0).iter().enumerate().map(
+                |(rest_rank, rest)| NonCrossingEntry {
+                    cost: rest.cost,
+                    choice: NonCrossingChoice::Unmatched { rest_rank },
+                },
+            ));
+        }
+        // Without unmatched items, only every other item can be paired with the first item
+        let step = if num_unmatched == 0 { 2 } else { 1 };
+        for k in ((i + 1)..j).step_by(step) {
+            let pair_cost = self.costs[i * self.num_items + k];
+            for inner_num_unmatched in 0..=num_unmatched {
+                let inner = self.entries(i + 1, k, inner_num_unmatched);
+                let right = self.entries(k + 1, j, num_unmatched - inner_num_unmatched);
+                for (inner_rank, inner_entry) in inner.iter().enumerate() {
+                    // At least (inner_rank + 1) * (right_rank + 1) - 1 candidates of the same 'k'
+                    // are no worse than this one
+                    let max_right_rank = max_num_matchings / (inner_rank + 1);
+                    for (right_rank, right_entry) in right.iter().enumerate().take(max_right_rank) {
+                        candidates.push(NonCrossingEntry {
+                            cost: pair_cost + inner_entry.cost + right_entry.cost,
+                            choice: NonCrossingChoice::Paired {
+                                k,
+                                inner_num_unmatched,
+                                inner_rank,
+                                right_rank,
+                            },
+                        });
+                    }
+                }
+            }
+        }
+
+        // Keep the best ones sorted by cost (NaN at last)
+        let compare = |entry1: &NonCrossingEntry, entry2: &NonCrossingEntry| {
+            entry1
+                .cost
+                .partial_cmp(&entry2.cost)
+                .unwrap_or_else(|| entry1.cost.is_nan().cmp(&entry2.cost.is_nan()))
+        };
+        if candidates.len() > max_num_matchings && max_num_matchings > 0 {
+            candidates.select_nth_unstable_by(max_num_matchings - 1, compare);
+        }
+        candidates.truncate(max_num_matchings);
+        candidates.sort_by(compare);
+        candidates
+    }
+
+    /// Collect the pairs and unmatched items of the entry of [i, j) at 'rank'.
+    fn collect_entry(
+        &self,
+        (i, j, num_unmatched): (usize, usize, usize),
+        rank: usize,
+        pairs: &mut Vec<(usize, usize)>,
+        unmatched: &mut Vec<usize>,
+    ) {
+        match self.entries(i, j, num_unmatched)[rank].choice {
+            NonCrossingChoice::Empty => {}
+            NonCrossingChoice::Unmatched { rest_rank } => {
+                unmatched.push(i);
+                self.collect_entry((i + 1, j, 0), rest_rank, pairs, unmatched);
+            }
+            NonCrossingChoice::Paired {
+                k,
+                inner_num_unmatched,
+                inner_rank,
+                right_rank,
+            } => {
+                pairs.push((i, k));
+                self.collect_entry(
+                    (i + 1, k, inner_num_unmatched),
+                    inner_rank,
+                    pairs,
+                    unmatched,
+                );
+                self.collect_entry(
+                    (k + 1, j, num_unmatched - inner_num_unmatched),
+                    right_rank,
+                    pairs,
+                    unmatched,
+                );
+            }
+        }
+    }
+
+    /// The best matchings of all items, in terms of the ids of 'match_items'.
+    fn into_matchings(self, match_items: &MatchItemSet) -> Vec<Matching> {
+        let n = self.num_items;
+        let num_unmatched = n % 2;
+        (0..self.entries(0, n, num_unmatched).len())
+            .map(|rank| {
+                let (mut pairs, mut unmatched) = (vec![], vec![]);
+                self.collect_entry((0, n, num_unmatched), rank, &mut pairs, &mut unmatched);
+                Matching::from_pairs_and_unmatched(
+                    pairs
+                        .into_iter()
+                        .map(|(i, j)| (match_items[i].id, match_items[j].id))
+                        .collect(),
+                    unmatched.into_iter().map(|i| match_items[i].id).collect(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl RankedMatchings {
+    fn new(match_items: &MatchItemSet, pair_costs: &PairCostMatrix) -> Self {
+        let mut vertex_ids: Vec<Option<usize>> =
+            match_items.iter().map(|item| Some(item.id)).collect();
+        if !vertex_ids.len().is_multiple_of(2) {
+            vertex_ids.push(None);
+        }
+        let n = vertex_ids.len();
+        let mut costs = vec![0; n * n];
+        for i in 0..match_items.len() {
+            for j in 0..match_items.len() {
+                let cost = pair_costs.cost(match_items[i].id, match_items[j].id);
+                costs[i * n + j] = (cost * RANKED_COST_SCALE).round() as i64;
+            }
+        }
+
+        let mut subproblems = BinaryHeap::new();
+        subproblems.push(Reverse(RankedSubproblem {
+            cost: 0,
+            order: 0,
+            forced: vec![],
+            forbidden: vec![],
+            solution: None,
+        }));
+        Self {
+            vertex_ids,
+            costs,
+            subproblems,
+            num_subproblems: 1,
+        }
+    }
+
+    fn cost(&self, (i, j): (usize, usize)) -> i64 {
+        self.costs[i * self.vertex_ids.len() + j]
+    }
+
+    /// Find the perfect matching with the minimum cost among the vertices which are not in
+    /// 'forced', and return it together with 'forced' and its total cost.
+    /// None is returned if there is no such matching without using 'forbidden' pairs.
+    fn solve(
+        &self,
+        forced: &[(usize, usize)],
+        forbidden: &[(usize, usize)],
+    ) -> Option<(Vec<(usize, usize)>, i64)> {
+        let n = self.vertex_ids.len();
+        let mut is_free = vec![true; n];
+        forced.iter().for_each(|&(i, j)| {
+            is_free[i] = false;
+            is_free[j] = false;
+        });
+        let forbidden: HashSet<(usize, usize)> = forbidden.iter().copied().collect();
+
+        let max_cost = self.costs.iter().copied().max().unwrap_or(0);
+        let mut edges = vec![];
+        for i in (0..n).filter(|&i| is_free[i]) {
+            for j in ((i + 1)..n).filter(|&j| is_free[j]) {
+                if !forbidden.contains(&(i, j)) {
+                    // Maximizing the weights minimizes the costs
+                    edges.push((i, j, max_cost - self.cost((i, j))));
+                }
+            }
+        }
+
+        let mates = max_weight_matching(n, &edges, true);
+        let mut pairs = forced.to_vec();
+        for i in (0..n).filter(|&i| is_free[i]) {
+            match mates[i] {
+                Some(j) if i < j => pairs.push((i, j)),
+                Some(_) => {}
+                None => return None,
+            }
+        }
+        let cost = pairs.iter().map(|&pair| self.cost(pair)).sum();
+        Some((pairs, cost))
+    }
+
+    fn push_subproblem(&mut self, mut subproblem: RankedSubproblem) {
+        subproblem.order = self.num_subproblems;
+        self.num_subproblems += 1;
+        self.subproblems.push(Reverse(subproblem));
+    }
+
+    fn to_matching(&self, pairs: &[(usize, usize)]) -> Matching {
+        let mut index_pairs = vec![];
+        let mut unmatched = vec![];
+        for &(i, j) in pairs.iter() {
+            match (self.vertex_ids[i], self.vertex_ids[j]) {
+                (Some(id1), Some(id2)) => index_pairs.push((id1, id2)),
+                (Some(id), None) | (None, Some(id)) => unmatched.push(id),
+                (None, None) => {}
+            }
+        }
+        Matching::from_pairs_and_unmatched(index_pairs, unmatched)
+    }
+}
+
+impl Iterator for RankedMatchings {
+    type Item = Matching;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(Reverse(mut subproblem)) = self.subproblems.pop() {
+            let solution = match subproblem.solution.take() {
+                Some(solution) => solution,
+                None => {
+                    // Solve it only when its lower bound becomes the smallest
+                    if let Some((solution, cost)) =
+                        self.solve(&subproblem.forced, &subproblem.forbidden)
+                    {
+                        self.push_subproblem(RankedSubproblem {
+                            cost,
+                            solution: Some(solution),
+                            ..subproblem
+                        });
+                    }
+                    continue;
+                }
+            };
+
+            // Partition the rest of this subproblem: the i-th child keeps the first i free pairs
+            // of the solution and forbids the next one. The last child is always infeasible.
+            let free_pairs: Vec<(usize, usize)> = solution
+                .iter()
+                .filter(|pair| !subproblem.forced.contains(pair))
+                .copied()
+                .collect();
+            for i in 0..free_pairs.len().saturating_sub(1) {
+                let mut forced = subproblem.forced.clone();
+                forced.extend_from_slice(&free_pairs[..i]);
+                let mut forbidden = subproblem.forbidden.clone();
+                forbidden.push(free_pairs[i]);
+                self.push_subproblem(RankedSubproblem {
+                    cost: subproblem.cost,
+                    order: 0,
+                    forced,
+                    forbidden,
+                    solution: None,
+                });
+            }
+
+            return Some(self.to_matching(&solution));
         }
+        None
+    }
+}
+
+impl PartialEq for RankedSubproblem {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
 
-        let indices: Vec<usize> = (0..len).into_iter().collect();
+impl Eq for RankedSubproblem {}
+
+impl PartialOrd for RankedSubproblem {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RankedSubproblem {
+    /// Unsolved subproblems come first among those of the same cost, so that a solved
+    /// subproblem is only taken when no other subproblem can be better.
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.cost, self.solution.is_some(), self.order).cmp(&(
+            other.cost,
+            other.solution.is_some(),
+            other.order,
+        ))
+    }
+}
+
+// Helper functions
+impl Matcher {
+    /// Find all possible matchings that leave no items unmatched, sorted by the partition costs
+    /// of 'match_cost'. Items within partitions are paired by 'pair_costs'.
+    /// The behavior is undefined unless 'match_items' contains n items where n is even.
+    fn find_all_possible_complete_matchings(
+        match_items: &MatchItemSet,
+        pair_costs: &PairCostMatrix,
+        match_cost: &dyn MatchCost,
+    ) -> Vec<Matching> {
+        let len = match_items.len();
+        if len == 0 {
+            return vec![Matching::new()];
+        }
+        let indices: Vec<usize> = (0..len).collect();
 
         // nCr
         let (n, r): (u64, u64) = (len as u64, (len >> 1) as u64);
@@ -74,9 +711,14 @@ impl Matcher {
                     }
                 }
 
-                let variance = Self::calculate_average_variance(&set1, &set2);
+                let variance = match_cost.partition_cost(&set1, &set2);
 
-                let distance_matrix = SquareDistanceMatrix::from_two_sets(&set1, &set2);
+                let distance_matrix = SquareDistanceMatrix::from_two_sets_and_pair_costs(
+                    &set1,
+                    &set2,
+                    pair_costs,
+                    match_cost.hungarian_cost_scale(),
+                );
 
                 let index_matching = distance_matrix.into_matching();
 
@@ -104,50 +746,27 @@ impl Matcher {
         let mut matchings_with_variances: Vec<(Matching, f64)> =
             unique_matchings_with_lowest_variances.into_iter().collect();
 
-        // Sort by variance
-        matchings_with_variances
-            .sort_by(|(_, variance1), (_, variance2)| variance1.partial_cmp(variance2).unwrap());
+        // Sort by variance (NaN at last)
+        matchings_with_variances.sort_by(|(_, variance1), (_, variance2)| {
+            variance1
+                .partial_cmp(variance2)
+                .unwrap_or_else(|| variance1.is_nan().cmp(&variance2.is_nan()))
+        });
 
         // Keep only matchings
-        Ok(matchings_with_variances
+        matchings_with_variances
             .into_iter()
             .map(|(matching, _)| matching)
-            .collect())
+            .collect()
     }
-}
 
-// Helper functions
-impl Matcher {
-    fn calculate_average_variance(set1: &MatchItemSet, set2: &MatchItemSet) -> f64 {
-        let calculate_average_direction = |set: &MatchItemSet| {
-            let len = set.len();
-            let sum_direction: PointF64 = set
-                .iter()
-                .fold(PointF64::default(), |acc, item2| acc + item2.direction);
-            let average_direction = sum_direction / (len as f64);
-            average_direction.get_normalized()
-        };
-
-        let calculate_variance = |set: &MatchItemSet| {
-            let len = set.len();
-            if len == 1 {
-                return 0.0;
-            }
-
-            let average_direction = calculate_average_direction(set);
-            let sum_distances: f64 = set
-                .iter()
-                .map(|item| {
-                    item.direction
-                        .get_normalized()
-                        .distance_to(average_direction)
-                })
-                .sum();
-            sum_distances / (len - 1) as f64
-        };
-
-        let (variance1, variance2) = (calculate_variance(set1), calculate_variance(set2));
-        (variance1 + variance2) / 2.0
+    /// The sum of the costs of all pairs in 'matching'.
+    /// The behavior is undefined unless all ids in 'matching' are ids of items in 'pair_costs'.
+    fn calculate_total_cost(pair_costs: &PairCostMatrix, matching: &Matching) -> f64 {
+        matching
+            .iter()
+            .map(|&(id1, id2)| pair_costs.cost(id1, id2))
+            .sum()
     }
 
     // ============================================================================================================
@@ -296,3 +915,242 @@ impl Matcher {
         (set1, set2)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use visioncortex::PointF64;
+
+    use super::*;
+    use crate::match_cost::{DefaultMatchCost, FacingMatchCost};
+
+    fn total_cost(match_items: &MatchItemSet, matching: &Matching) -> f64 {
+        let pair_costs = PairCostMatrix::from_match_items(match_items, &[], &DefaultMatchCost);
+        matching
+            .iter()
+            .map(|&(i, j)| pair_costs.cost(match_items[i].id, match_items[j].id))
+            .sum()
+    }
+
+    #[test]
+    fn ranked_matchings_are_all_matchings_in_ascending_order_of_cost() {
+        // GIVEN
+        let points = [
+            (3.0, 4.0),
+            (17.5, 2.0),
+            (9.0, 13.0),
+            (1.0, 22.0),
+            (25.0, 19.5),
+            (14.0, 8.0),
+            (30.0, 1.0),
+            (6.5, 30.0),
+        ];
+        let match_items = MatchItemSet::from_match_items_and_set_ids(
+            points
+                .iter()
+                .map(|&(x, y)| {
+                    MatchItem::new_with_default_id(
+                        PointF64::new(x, y),
+                        PointF64::new(15.0 - y, x - 15.0),
+                    )
+                })
+                .collect(),
+        );
+
+        // WHEN
+        let matchings: Vec<Matching> =
+            Matcher::rank_matchings(&match_items, &[], &DefaultMatchCost, false)
+                .unwrap()
+                .collect();
+
+        // THEN
+        // There are 7 * 5 * 3 * 1 perfect matchings of 8 items
+        assert_eq!(matchings.len(), 105);
+        let unique_matchings: HashSet<Matching> = matchings.iter().cloned().collect();
+        assert_eq!(unique_matchings.len(), 105);
+        let costs: Vec<f64> = matchings
+            .iter()
+            .map(|matching| total_cost(&match_items, matching))
+            .collect();
+        assert!(costs.windows(2).all(|pair| pair[0] <= pair[1] + 1e-2));
+    }
+
+    #[test]
+    fn ranked_matchings_leave_one_item_unmatched_if_odd() {
+        // GIVEN
+        let match_items = MatchItemSet::from_match_items_and_set_ids(
+            [(0.0, 0.0), (1.0, 0.0), (50.0, 50.0)]
+                .iter()
+                .map(|&(x, y)| {
+                    MatchItem::new_with_default_id(PointF64::new(x, y), PointF64::default())
+                })
+                .collect(),
+        );
+
+        // WHEN
+        let best_matching = Matcher::rank_matchings(&match_items, &[], &DefaultMatchCost, true)
+            .unwrap()
+            .next()
+            .unwrap();
+
+        // THEN
+        assert_eq!(best_matching.index_pairs.len(), 1);
+        assert_eq!(best_matching.unmatched, vec![2]);
+    }
+
+    #[test]
+    fn ranked_matchings_prefer_facing_items_over_closer_parallel_items() {
+        // GIVEN the endpoints of a 10-pixel-thick bar crossing a 15-pixel-wide hole
+        let match_items = MatchItemSet::from_match_items_and_set_ids(vec![
+            MatchItem::new_with_default_id(PointF64::new(20.0, 15.0), PointF64::new(1.0, 0.0)),
+            MatchItem::new_with_default_id(PointF64::new(20.0, 25.0), PointF64::new(1.0, 0.0)),
+            MatchItem::new_with_default_id(PointF64::new(35.0, 15.0), PointF64::new(-1.0, 0.0)),
+            MatchItem::new_with_default_id(PointF64::new(35.0, 25.0), PointF64::new(-1.0, 0.0)),
+        ]);
+
+        // WHEN
+        let best_matching = Matcher::rank_matchings(&match_items, &[], &FacingMatchCost, false)
+            .unwrap()
+            .next()
+            .unwrap();
+
+        // THEN
+        assert_eq!(best_matching, Matching::from_pairs(vec![(0, 2), (1, 3)]));
+    }
+
+    #[test]
+    fn non_crossing_matchings_are_all_best_non_crossing_matchings_in_ascending_order_of_cost() {
+        // GIVEN 8 items in clockwise order on a circle, pointing inwards at various angles
+        let match_items = MatchItemSet::from_match_items_and_set_ids(
+            (0..8)
+                .map(|i| {
+                    let angle = i as f64 * std::f64::consts::PI / 4.0 + (i % 3) as f64 * 0.2;
+                    let point = PointF64::new(angle.cos(), angle.sin()) * 10.0;
+                    let direction = PointF64::new((i % 2) as f64, 1.0) - point / 10.0;
+                    MatchItem::new_with_default_id(point, direction)
+                })
+                .collect(),
+        );
+        let crosses = |(a, b): (usize, usize), (c, d): (usize, usize)| {
+            let (a, b) = (a.min(b), a.max(b));
+            let between = |x: usize| a < x && x < b;
+            between(c) != between(d)
+        };
+        let is_non_crossing = |matching: &Matching| {
+            matching
+                .iter()
+                .all(|&pair1| matching.iter().all(|&pair2| !crosses(pair1, pair2)))
+        };
+
+        // WHEN
+        let matchings = Matcher::find_best_non_crossing_matchings(
+            &match_items,
+            &[],
+            &DefaultMatchCost,
+            false,
+            20,
+        )
+        .unwrap();
+
+        // THEN
+        // There are Catalan(4) = 14 non-crossing perfect matchings of 8 items
+        assert_eq!(matchings.len(), 14);
+        let unique_matchings: HashSet<Matching> = matchings.iter().cloned().collect();
+        assert_eq!(unique_matchings.len(), 14);
+        assert!(matchings.iter().all(is_non_crossing));
+        let costs: Vec<f64> = matchings
+            .iter()
+            .map(|matching| total_cost(&match_items, matching))
+            .collect();
+        assert!(costs.windows(2).all(|pair| pair[0] <= pair[1] + 1e-9));
+
+        let best_ranked_non_crossing_matching =
+            Matcher::rank_matchings(&match_items, &[], &DefaultMatchCost, false)
+                .unwrap()
+                .find(|matching| is_non_crossing(matching))
+                .unwrap();
+        assert!(
+            (total_cost(&match_items, &best_ranked_non_crossing_matching) - costs[0]).abs() < 1e-2
+        );
+    }
+
+    #[test]
+    fn non_crossing_matchings_leave_one_item_unmatched_if_odd() {
+        // GIVEN
+        let match_items = MatchItemSet::from_match_items_and_set_ids(
+            (0..5)
+                .map(|i| {
+                    let angle = i as f64 * std::f64::consts::PI * 0.4;
+                    let point = PointF64::new(angle.cos(), angle.sin()) * 10.0;
+                    MatchItem::new_with_default_id(point, -point)
+                })
+                .collect(),
+        );
+
+        // WHEN
+        let matchings = Matcher::find_best_non_crossing_matchings(
+            &match_items,
+            &[],
+            &DefaultMatchCost,
+            true,
+            100,
+        )
+        .unwrap();
+
+        // THEN
+        // Each of the 5 items can be left unmatched, and the other 4 matched in Catalan(2) = 2 ways
+        assert_eq!(matchings.len(), 10);
+        assert!(matchings
+            .iter()
+            .all(|matching| matching.index_pairs.len() == 2 && matching.unmatched.len() == 1));
+        assert!(matches!(
+            Matcher::find_best_non_crossing_matchings(
+                &match_items,
+                &[],
+                &DefaultMatchCost,
+                false,
+                100
+            ),
+            Err(CompletionError::OddNumberOfEndpoints(5))
+        ));
+    }
+
+    #[test]
+    fn many_items_are_matched_exhaustively_or_by_ranking() {
+        for &(num_items, allow_unmatched) in
+            [(12, false), (13, true), (14, false), (22, false)].iter()
+        {
+            // GIVEN items on a circle pointing to its center
+            let match_items = MatchItemSet::from_match_items_and_set_ids(
+                (0..num_items)
+                    .map(|i| {
+                        let angle = 2.0 * std::f64::consts::PI * i as f64 / num_items as f64;
+                        let direction = PointF64::new(angle.cos(), angle.sin());
+                        MatchItem::new_with_default_id(direction * 50.0, -direction)
+                    })
+                    .collect(),
+            );
+            let config = MatcherConfig {
+                allow_unmatched,
+                ..Default::default()
+            };
+
+            // WHEN
+            let matchings =
+                Matcher::find_all_possible_matchings(match_items, &[], &DefaultMatchCost, &config)
+                    .unwrap();
+
+            // THEN up to 'max_num_exhaustive_matchings' matchings are enumerated exhaustively, and
+            // up to 'max_num_ranked_matchings' are ranked above MAX_NUM_MATCH_ITEMS items
+            let max_num_matchings = if num_items <= MAX_NUM_MATCH_ITEMS {
+                config.max_num_exhaustive_matchings
+            } else {
+                config.max_num_ranked_matchings
+            };
+            assert!(!matchings.is_empty(), "{} items", num_items);
+            assert!(matchings.len() <= max_num_matchings, "{} items", num_items);
+            assert!(matchings
+                .iter()
+                .all(|matching| matching.index_pairs.len() == num_items / 2));
+        }
+    }
+}
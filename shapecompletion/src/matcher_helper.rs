@@ -6,7 +6,9 @@ use std::{
     vec::IntoIter,
 };
 
-use visioncortex::PointF64;
+use visioncortex::{PathI32, PointF64};
+
+use crate::match_cost::MatchCost;
 
 #[derive(Clone, Copy, Debug)]
 pub struct MatchItem {
@@ -15,14 +17,31 @@ pub struct MatchItem {
     pub direction: PointF64,
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct MatchItemSet {
     pub items: Vec<MatchItem>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct Matching {
     pub index_pairs: Vec<(usize, usize)>,
+    /// Indices that are not in any pair.
+    pub unmatched: Vec<usize>,
+}
+
+/// The path segment whose tail is a match item.
+#[derive(Clone, Debug, Default)]
+pub struct MatchSegment {
+    /// The points of the segment, starting from the tail on the hole boundary
+    pub points: PathI32,
+    /// The index of the path the segment is on. Segments on the same path are on the same contour.
+    pub path_index: usize,
+}
+
+/// A square matrix storing the pair costs of match items, indexed by their ids
+pub struct PairCostMatrix {
+    pub n: usize,
+    pub costs: Vec<f64>, // row-major
 }
 
 pub trait Distanced {
@@ -81,7 +100,7 @@ impl MatchItemSet {
         self.items.is_empty()
     }
 
-    pub fn iter(&self) -> Iter<MatchItem> {
+    pub fn iter(&self) -> Iter<'_, MatchItem> {
         self.items.iter()
     }
 
@@ -178,10 +197,20 @@ impl Matching {
     }
 
     pub fn from_pairs(pairs: Vec<(usize, usize)>) -> Self {
-        Self { index_pairs: pairs }
+        Self {
+            index_pairs: pairs,
+            unmatched: vec![],
+        }
+    }
+
+    pub fn from_pairs_and_unmatched(pairs: Vec<(usize, usize)>, unmatched: Vec<usize>) -> Self {
+        Self {
+            index_pairs: pairs,
+            unmatched,
+        }
     }
 
-    pub fn iter(&self) -> Iter<(usize, usize)> {
+    pub fn iter(&self) -> Iter<'_, (usize, usize)> {
         self.index_pairs.iter()
     }
 
@@ -192,6 +221,7 @@ impl Matching {
             .map(|(i, j_option)| (i, j_option.unwrap()));
         Self {
             index_pairs: index_pairs_iter.collect(),
+            unmatched: vec![],
         }
     }
 }
@@ -228,6 +258,29 @@ impl SquareDistanceMatrix {
         Self { n, distances }
     }
 
+    /// Create a DistanceMatrix of the costs in 'pair_costs' between the items of 'set1' and 'set2',
+    /// multiplied by 'scale' (see MatchCost::hungarian_cost_scale()).
+    /// The behavior is undefined unless 'set1' and 'set2' have the same number of items.
+    pub fn from_two_sets_and_pair_costs(
+        set1: &MatchItemSet,
+        set2: &MatchItemSet,
+        pair_costs: &PairCostMatrix,
+        scale: f64,
+    ) -> Self {
+        assert_eq!(set1.len(), set2.len());
+        let n = set1.len();
+
+        let mut distances = vec![0.0; n * n];
+
+        for i in 0..n {
+            for j in 0..n {
+                distances[i * n + j] = pair_costs.cost(set1[i].id, set2[j].id) * scale;
+            }
+        }
+
+        Self { n, distances }
+    }
+
     pub fn into_matching(self) -> Matching {
         let n = self.n;
         let matrix: Vec<u64> = self.distances.into_iter().map(|dist| dist as u64).collect();
@@ -236,6 +289,39 @@ impl SquareDistanceMatrix {
     }
 }
 
+impl PairCostMatrix {
+    /// Evaluate 'match_cost' on every pair of 'match_items', where segments[id] is the segment of
+    /// the item with that id. Items without segments are given an empty segment.
+    pub fn from_match_items(
+        match_items: &MatchItemSet,
+        segments: &[MatchSegment],
+        match_cost: &dyn MatchCost,
+    ) -> Self {
+        let n = match_items
+            .iter()
+            .map(|item| item.id + 1)
+            .max()
+            .unwrap_or(0);
+        let empty_segment = MatchSegment::default();
+        let segment_of = |item: &MatchItem| segments.get(item.id).unwrap_or(&empty_segment);
+
+        let mut costs = vec![0.0; n * n];
+        for item1 in match_items.iter() {
+            for item2 in match_items.iter() {
+                costs[item1.id * n + item2.id] =
+                    match_cost.pair_cost(item1, segment_of(item1), item2, segment_of(item2));
+            }
+        }
+
+        Self { n, costs }
+    }
+
+    /// The cost of pairing the items with ids 'id1' and 'id2'.
+    pub fn cost(&self, id1: usize, id2: usize) -> f64 {
+        self.costs[id1 * self.n + id2]
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,201 @@
+use visioncortex::{CompoundPath, PointF64};
+
+use crate::geo::flatten_compound_path;
+
+/// The number of line segments each cubic bezier curve is flattened into for scoring.
+const NUM_SAMPLES_PER_BEZIER: usize = 16;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ScoreWeights {
+    /// Weight of the bending energy (integral of squared curvature) of the curves
+    pub bending_energy: f64,
+    /// Weight of the total length of the curves (per pixel)
+    pub length: f64,
+    /// Weight of the total angle (in radians) between the curves and the tails they continue
+    pub tangent_deviation: f64,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        Self {
+            bending_energy: 1.0,
+            length: 0.05,
+            tangent_deviation: 1.0,
+        }
+    }
+}
+
+/// The score of the curves intrapolated for a matching. Lower is better.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MatchingScore {
+    /// Index of the matching among the candidate matchings
+    pub matching_index: usize,
+    pub bending_energy: f64,
+    pub length: f64,
+    pub tangent_deviation: f64,
+    /// The weighted sum of the above
+    pub total: f64,
+}
+
+// API
+impl MatchingScore {
+    /// Score 'curves' which continue tails on the hole boundary, where curve_tails[i] are the tails
+    /// continued by curves[i] (same order): 2 for the curve of a pair, 1 for the stub or junction
+    /// of an unmatched endpoint. Each tail is a point and the direction pointing into the hole.
+    /// Each tail is compared with the end of its curve closest to it (the 2 tails of a curve with
+    /// different ends). Ends which continue no tail, such as the inner end of a stub, are not
+    /// compared with any tail.
+    /// The behavior is undefined unless curve_tails.len() == curves.len().
+    pub fn evaluate(
+        matching_index: usize,
+        curves: &[CompoundPath],
+        curve_tails: &[Vec<(PointF64, PointF64)>],
+        weights: &ScoreWeights,
+    ) -> Self {
+        let (mut bending_energy, mut length, mut tangent_deviation) = (0.0, 0.0, 0.0);
+        for (curve, tails) in curves.iter().zip(curve_tails.iter()) {
+            let points = flatten_compound_path(curve, NUM_SAMPLES_PER_BEZIER);
+            bending_energy += Self::calculate_bending_energy(&points);
+            length += points
+                .windows(2)
+                .map(|pair| pair[0].distance_to(pair[1]))
+                .sum::<f64>();
+            tangent_deviation += Self::calculate_tangent_deviation(&points, tails);
+        }
+
+        let total = weights.bending_energy * bending_energy
+            + weights.length * length
+            + weights.tangent_deviation * tangent_deviation;
+        Self {
+            matching_index,
+            bending_energy,
+            length,
+            tangent_deviation,
+            total,
+        }
+    }
+}
+
+// Helper functions
+impl MatchingScore {
+    /// The discrete integral of squared curvature along 'points': the squared turning angle at each
+    /// inner point, divided by the average length of the 2 line segments around it.
+    fn calculate_bending_energy(points: &[PointF64]) -> f64 {
+        points
+            .windows(3)
+            .map(|triple| {
+                let (v1, v2) = (triple[1] - triple[0], triple[2] - triple[1]);
+                let average_length = (v1.norm() + v2.norm()) / 2.0;
+                if average_length == 0.0 {
+                    return 0.0;
+                }
+                let angle = Self::calculate_angle(v1, v2);
+                angle * angle / average_length
+            })
+            .sum()
+    }
+
+    /// The angles between the directions of 'tails' and the tangents at the ends of 'points' they
+    /// are compared with (see MatchingScore::evaluate()), pointing inwards along the curve.
+    fn calculate_tangent_deviation(points: &[PointF64], tails: &[(PointF64, PointF64)]) -> f64 {
+        let len = points.len();
+        if len < 2 {
+            return 0.0;
+        }
+        let ends = [
+            (points[0], points[1] - points[0]),
+            (points[len - 1], points[len - 2] - points[len - 1]),
+        ];
+        let distance = |tail: &(PointF64, PointF64), end: usize| tail.0.distance_to(ends[end].0);
+        let end_indices: Vec<usize> = match tails {
+            [tail1, tail2] => {
+                if distance(tail1, 0) + distance(tail2, 1)
+                    <= distance(tail1, 1) + distance(tail2, 0)
+                {
+                    vec![0, 1]
+                } else {
+                    vec![1, 0]
+                }
+            }
+            _ => tails
+                .iter()
+                .map(|tail| {
+                    if distance(tail, 0) <= distance(tail, 1) {
+                        0
+                    } else {
+                        1
+                    }
+                })
+                .collect(),
+        };
+        tails
+            .iter()
+            .zip(end_indices)
+            .map(|(&(_, direction), end)| Self::calculate_angle(direction, ends[end].1).abs())
+            .sum()
+    }
+
+    /// The signed angle from 'v1' to 'v2', in [-PI, PI].
+    fn calculate_angle(v1: PointF64, v2: PointF64) -> f64 {
+        (v1.x * v2.y - v1.y * v2.x).atan2(v1.x * v2.x + v1.y * v2.y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use visioncortex::PathF64;
+
+    use super::*;
+
+    fn polyline(points: &[(f64, f64)]) -> CompoundPath {
+        let mut curve = CompoundPath::new();
+        curve.add_path_f64(PathF64::from_points(
+            points.iter().map(|&(x, y)| PointF64::new(x, y)).collect(),
+        ));
+        curve
+    }
+
+    #[test]
+    fn straight_continuation_scores_lower_than_detour() {
+        // GIVEN 2 tails facing each other across a gap
+        let tails = [
+            (PointF64::new(0.0, 0.0), PointF64::new(1.0, 0.0)),
+            (PointF64::new(10.0, 0.0), PointF64::new(-1.0, 0.0)),
+        ];
+        let straight = polyline(&[(0.0, 0.0), (5.0, 0.0), (10.0, 0.0)]);
+        let detour = polyline(&[(0.0, 0.0), (5.0, 5.0), (10.0, 0.0)]);
+
+        // WHEN
+        let weights = ScoreWeights::default();
+        let straight_score = MatchingScore::evaluate(0, &[straight], &[tails.to_vec()], &weights);
+        let detour_score = MatchingScore::evaluate(1, &[detour], &[tails.to_vec()], &weights);
+
+        // THEN
+        assert!(straight_score.bending_energy.abs() < 1e-9);
+        assert!(straight_score.tangent_deviation.abs() < 1e-9);
+        assert!((straight_score.length - 10.0).abs() < 1e-9);
+        assert!(straight_score.total < detour_score.total);
+        assert!((detour_score.tangent_deviation - std::f64::consts::FRAC_PI_2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn inner_end_of_stub_is_not_compared_with_unrelated_tail() {
+        // GIVEN a straight stub continuing its tail, whose inner end is close to an unrelated tail
+        // pointing across it
+        let tail = (PointF64::new(0.0, 0.0), PointF64::new(1.0, 0.0));
+        let unrelated_tail = (PointF64::new(6.0, 0.0), PointF64::new(0.0, 1.0));
+        let stub = polyline(&[(0.0, 0.0), (5.0, 0.0)]);
+        let curve = polyline(&[(6.0, 0.0), (6.0, 5.0), (6.0, 10.0)]);
+
+        // WHEN
+        let score = MatchingScore::evaluate(
+            0,
+            &[stub, curve],
+            &[vec![tail], vec![unrelated_tail]],
+            &ScoreWeights::default(),
+        );
+
+        // THEN
+        assert!(score.tangent_deviation.abs() < 1e-9);
+    }
+}
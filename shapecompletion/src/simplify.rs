@@ -0,0 +1,274 @@
+use flo_curves::{
+    bezier::{fit_curve, Curve},
+    Coord2, Coordinate, Coordinate2D,
+};
+use visioncortex::{CompoundPath, PathF64, PathI32, PointF64, PointI32, Spline};
+
+use crate::{
+    error::CompletionError,
+    geo::{find_corners_open_path, flatten_compound_path},
+};
+
+/// The number of line segments each cubic bezier curve of a fitted spline is flattened into
+const NUM_SPLINE_SAMPLES_PER_BEZIER: usize = 8;
+
+/// How the walked path segments are simplified before tail tangents are estimated on them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimplifyMode {
+    /// The walked boundary pixels are used as they are
+    None,
+    /// Radial distance and Ramer-Douglas-Peucker simplification (visioncortex::reduce::reduce())
+    /// within the simplify tolerance
+    RamerDouglasPeucker,
+    /// Visvalingam-Whyatt simplification, which repeatedly removes the point forming the triangle
+    /// of the smallest area with its neighbors while that area is below the square of the simplify
+    /// tolerance
+    VisvalingamWhyatt,
+    /// Spline fitting as in PathSimplifyMode::Spline of visioncortex: the segment is approximated by
+    /// a polygon (by Ramer-Douglas-Peucker within the simplify tolerance), which is smoothed by 4-point
+    /// subdivision keeping corners, and cubic bezier curves are fitted to the smoothed path between
+    /// corners within the simplify tolerance. The spline is then flattened and simplified within the
+    /// simplify tolerance again.
+    Spline,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SimplifyConfig {
+    pub mode: SimplifyMode,
+    // Spline fitting (SimplifyMode::Spline only)
+    /// The smallest turning angle (in radians) of a corner kept by smoothing, in (0.0, PI]
+    pub corner_threshold: f64,
+    /// In [1.0, Inf]
+    pub outset_ratio: f64,
+    /// Segments of this length (in pixels) or shorter are not subdivided
+    pub segment_length: f64,
+    pub max_iterations: usize, // [1, Inf]
+}
+
+impl SimplifyConfig {
+    /// Return an error describing the first invalid field, if any.
+    pub fn validate(&self) -> Result<(), CompletionError> {
+        if self.mode != SimplifyMode::Spline {
+            return Ok(());
+        }
+        if !(self.corner_threshold > 0.0 && self.corner_threshold <= std::f64::consts::PI) {
+            return Err(CompletionError::InvalidConfiguration(
+                "corner_threshold must be in (0.0, PI].",
+            ));
+        }
+        if self.outset_ratio.is_nan() || self.outset_ratio < 1.0 {
+            return Err(CompletionError::InvalidConfiguration(
+                "outset_ratio must be at least 1.0.",
+            ));
+        }
+        if self.segment_length.is_nan() || self.segment_length <= 0.0 {
+            return Err(CompletionError::InvalidConfiguration(
+                "segment_length must be positive.",
+            ));
+        }
+        if self.max_iterations == 0 {
+            return Err(CompletionError::InvalidConfiguration(
+                "max_iterations must be at least 1.",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for SimplifyConfig {
+    fn default() -> Self {
+        Self {
+            mode: SimplifyMode::RamerDouglasPeucker,
+            corner_threshold: std::f64::consts::FRAC_PI_3,
+            outset_ratio: 8.0,
+            segment_length: 4.0,
+            max_iterations: 10,
+        }
+    }
+}
+
+/// Simplify the open path 'segment' by the mode of 'config' within 'tolerance' (in pixels).
+/// The first and the last points of 'segment' are always kept.
+/// The behavior is undefined unless 'config' is valid (see SimplifyConfig::validate()).
+pub fn simplify_segment(segment: &PathI32, tolerance: f64, config: &SimplifyConfig) -> PathI32 {
+    match config.mode {
+        SimplifyMode::None => segment.clone(),
+        SimplifyMode::RamerDouglasPeucker => {
+            PathI32::from_points(visioncortex::reduce::reduce(&segment.path, tolerance))
+        }
+        SimplifyMode::VisvalingamWhyatt => PathI32::from_points(simplify_visvalingam_whyatt(
+            &segment.path,
+            tolerance * tolerance,
+        )),
+        SimplifyMode::Spline => simplify_by_spline(segment, tolerance, config),
+    }
+}
+
+/// Remove the point forming the triangle of the smallest area with its remaining neighbors from
+/// 'points', until every such area is at least 'min_area'. The first and the last points are kept.
+fn simplify_visvalingam_whyatt(points: &[PointI32], min_area: f64) -> Vec<PointI32> {
+    let mut points = points.to_vec();
+    let area = |a: PointI32, b: PointI32, c: PointI32| -> f64 {
+        let (ab, ac) = (b - a, c - a);
+        0.5 * (ab.x as f64 * ac.y as f64 - ab.y as f64 * ac.x as f64).abs()
+    };
+    while points.len() > 2 {
+        let (index, smallest) = (1..points.len() - 1)
+            .map(|i| (i, area(points[i - 1], points[i], points[i + 1])))
+            .min_by(|(_, a1), (_, a2)| a1.total_cmp(a2))
+            .unwrap();
+        if smallest >= min_area {
+            break;
+        }
+        points.remove(index);
+    }
+    points
+}
+
+/// PathI32::smooth() works on closed paths. 'segment' is closed by walking it back from its tail
+/// to its head, so that both ends are 180 degree corners, which are kept as they are, and the forward
+/// half of the smoothed path is taken.
+/// The polygon approximating 'segment' is returned where the smoothed path does not reach its tail.
+fn simplify_by_spline(segment: &PathI32, tolerance: f64, config: &SimplifyConfig) -> PathI32 {
+    let polygon = PathI32::from_points(visioncortex::reduce::reduce(&segment.path, tolerance));
+    if polygon.len() <= 2 {
+        return polygon;
+    }
+    let tail = polygon[polygon.len() - 1].to_point_f64();
+
+    let mut closed = polygon.clone();
+    polygon
+        .path
+        .iter()
+        .rev()
+        .skip(1)
+        .for_each(|&p| closed.add(p));
+    let smoothed = closed.smooth(
+        config.corner_threshold,
+        config.outset_ratio,
+        config.segment_length,
+        config.max_iterations,
+    );
+    let tail_index = match smoothed.path.iter().position(|&p| p == tail) {
+        Some(tail_index) => tail_index,
+        None => return polygon,
+    };
+    let mut forward = PathF64::new();
+    smoothed.path[0..=tail_index].iter().for_each(|&p| {
+        if forward.path.last() != Some(&p) {
+            forward.add(p);
+        }
+    });
+
+    // Fit cubic bezier curves between corners
+    let corners = find_corners_open_path(&forward, config.corner_threshold);
+    let mut spline = Spline::new(forward[0]);
+    let mut piece_start = 0;
+    for i in 1..forward.len() {
+        if i < forward.len() - 1 && !corners.get(i).copied().unwrap_or(false) {
+            continue;
+        }
+        let to_coord = |p: &PointF64| Coord2::from_components(&[p.x, p.y]);
+        let piece: Vec<Coord2> = forward.path[piece_start..=i].iter().map(to_coord).collect();
+        for curve in fit_curve::<Curve<Coord2>>(&piece, tolerance).unwrap_or_default() {
+            let (control_point1, control_point2) = curve.control_points;
+            spline.add(
+                PointF64::new(control_point1.x(), control_point1.y()),
+                PointF64::new(control_point2.x(), control_point2.y()),
+                PointF64::new(curve.end_point.x(), curve.end_point.y()),
+            );
+        }
+        piece_start = i;
+    }
+    if spline.points.last() != Some(&tail) {
+        return polygon;
+    }
+
+    let mut compound_path = CompoundPath::new();
+    compound_path.add_spline(spline);
+    let flattened = flatten_compound_path(&compound_path, NUM_SPLINE_SAMPLES_PER_BEZIER);
+    let mut simplified = PathI32::new();
+    for p in visioncortex::reduce::reduce(&flattened, tolerance).into_iter() {
+        let p = PointI32::new(p.x.round() as i32, p.y.round() as i32);
+        if simplified.path.last() != Some(&p) {
+            simplified.add(p);
+        }
+    }
+    simplified
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 4-connected staircase of 'length' steps from 'head' approximating the line at 'angle'
+    /// (in radians), as walked along a pixel boundary.
+    fn staircase_line(head: PointI32, angle: f64, length: usize) -> PathI32 {
+        let direction = PointF64::new(angle.cos(), angle.sin());
+        let normal = PointF64::new(-direction.y, direction.x);
+        let steps = [
+            PointI32::new(direction.x.signum() as i32, 0),
+            PointI32::new(0, direction.y.signum() as i32),
+        ];
+        let mut path = PathI32::new();
+        let mut current = head;
+        path.add(current);
+        for _ in 0..length {
+            // The step staying the closest to the line
+            current = steps
+                .iter()
+                .map(|&step| current + step)
+                .min_by(|p1, p2| {
+                    let distance = |p: PointI32| (p - head).to_point_f64().dot(normal).abs();
+                    distance(*p1).total_cmp(&distance(*p2))
+                })
+                .unwrap();
+            path.add(current);
+        }
+        path
+    }
+
+    /// The angle (in degrees) between 'direction' and the first edge of 'segment' (from index 1 to
+    /// index 0), which is what the tail tangent at the head of a walked segment is mostly based on.
+    fn tail_tangent_error(segment: &PathI32, direction: PointF64) -> f64 {
+        let edge = (segment[0] - segment[1]).to_point_f64().get_normalized();
+        edge.dot(direction).clamp(-1.0, 1.0).acos().to_degrees()
+    }
+
+    #[test]
+    fn simplification_improves_tail_tangents_of_rotated_lines() {
+        let modes = [
+            SimplifyMode::None,
+            SimplifyMode::RamerDouglasPeucker,
+            SimplifyMode::VisvalingamWhyatt,
+            SimplifyMode::Spline,
+        ];
+        let mut mean_errors = [0.0; 4];
+        let num_angles = 36;
+        for i in 0..num_angles {
+            // GIVEN a pixelated line walked away from its head at an angle of 10 * i + 3 degrees
+            let angle = (10.0 * i as f64 + 3.0).to_radians();
+            let segment = staircase_line(PointI32::new(50, 50), angle, 30);
+            let direction = -PointF64::new(angle.cos(), angle.sin());
+
+            for (mode, mean_error) in modes.iter().zip(mean_errors.iter_mut()) {
+                // WHEN
+                let config = SimplifyConfig {
+                    mode: *mode,
+                    ..Default::default()
+                };
+                let simplified = simplify_segment(&segment, 2.0, &config);
+
+                // THEN
+                assert_eq!(simplified[0], segment[0]);
+                assert_eq!(simplified[simplified.len() - 1], segment[segment.len() - 1]);
+                *mean_error += tail_tangent_error(&simplified, direction) / num_angles as f64;
+            }
+        }
+        let [none, rdp, vw, spline] = mean_errors;
+        assert!(none > 20.0, "{:?}", mean_errors);
+        for &error in [rdp, vw, spline].iter() {
+            assert!(error < 5.0, "{:?}", mean_errors);
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::HashSet;
+
+use visioncortex::{BinaryImage, BoundingRect, PathI32, PointI32};
+
+use crate::hole::Hole;
+
+#[derive(Clone, Copy, Debug)]
+pub struct CenterlineConfig {
+    /// The largest number of skeleton pixels walked from the hole boundary along each branch
+    pub max_branch_length: usize,
+    /// The width (in pixels) the completed centerlines are drawn at. If None, the width is measured
+    /// around the hole (see estimate_line_width()).
+    pub line_width: Option<f64>,
+}
+
+impl Default for CenterlineConfig {
+    fn default() -> Self {
+        Self {
+            max_branch_length: 32,
+            line_width: None,
+        }
+    }
+}
+
+/// The 8 neighbors of a pixel, clockwise from the one above it.
+const NEIGHBOR_OFFSETS: [(i32, i32); 8] = [
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+/// Thin the foreground of 'image' into lines of 1 pixel wide by the Zhang-Suen algorithm.
+/// Pixels inside any of 'holes' are unknown. They are thinned as foreground that is never removed,
+/// so that lines running into a hole are not shortened there, and they are blank in the skeleton.
+pub fn skeletonize(image: &BinaryImage, holes: &[&Hole]) -> BinaryImage {
+    let mut skeleton = image.clone();
+    let is_in_holes = |p: PointI32| holes.iter().any(|hole| hole.have_pixel_inside(p));
+    for hole in holes.iter() {
+        let rect = hole.rect();
+        for y in rect.top..rect.bottom {
+            for x in rect.left..rect.right {
+                if hole.have_pixel_inside(PointI32::new(x, y)) {
+                    skeleton.set_pixel_at_safe(PointI32::new(x, y), true);
+                }
+            }
+        }
+    }
+    let get = |skeleton: &BinaryImage, x: i32, y: i32| -> bool {
+        skeleton.get_pixel_at_safe(PointI32::new(x, y))
+    };
+    loop {
+        let mut changed = false;
+        for step in 0..2 {
+            let mut to_clear = vec![];
+            for y in 0..skeleton.height as i32 {
+                for x in 0..skeleton.width as i32 {
+                    if !get(&skeleton, x, y) || is_in_holes(PointI32::new(x, y)) {
+                        continue;
+                    }
+                    let neighbors: Vec<bool> = NEIGHBOR_OFFSETS
+                        .iter()
+                        .map(|&(dx, dy)| get(&skeleton, x + dx, y + dy))
+                        .collect();
+                    let num_foreground = neighbors.iter().filter(|&&n| n).count();
+                    let num_transitions = (0..8)
+                        .filter(|&i| !neighbors[i] && neighbors[(i + 1) % 8])
+                        .count();
+                    // North, east, south and west
+                    let (n, e, s, w) = (neighbors[0], neighbors[2], neighbors[4], neighbors[6]);
+                    let removable = if step == 0 {
+                        !(e && s && (n || w))
+                    } else {
+                        !(n && w && (e || s))
+                    };
+                    if (2..=6).contains(&num_foreground) && num_transitions == 1 && removable {
+                        to_clear.push((x as usize, y as usize));
+                    }
+                }
+            }
+            changed |= !to_clear.is_empty();
+            to_clear
+                .into_iter()
+                .for_each(|(x, y)| skeleton.set_pixel(x, y, false));
+        }
+        if !changed {
+            break;
+        }
+    }
+    for hole in holes.iter() {
+        let rect = hole.rect();
+        for y in rect.top..rect.bottom {
+            for x in rect.left..rect.right {
+                if hole.have_pixel_inside(PointI32::new(x, y)) {
+                    skeleton.set_pixel_at_safe(PointI32::new(x, y), false);
+                }
+            }
+        }
+    }
+    skeleton
+}
+
+/// Walk the branches of 'skeleton' from the boundary of 'hole' as open paths of pixel positions,
+/// whose heads (index 0) are pixels outside the hole next to it (8-connectivity).
+/// Each group of connected pixels next to the hole gives at most one branch. Walking stops after
+/// 'max_branch_length' pixels, where the branch ends, or where it reaches the hole or any of
+/// 'other_holes' again. At junctions, 4-connected pixels are followed first.
+/// Branches of fewer than 2 pixels are discarded.
+pub fn find_branches_from_hole(
+    skeleton: &BinaryImage,
+    hole: &Hole,
+    other_holes: &[&Hole],
+    max_branch_length: usize,
+) -> Vec<PathI32> {
+    let is_skeleton = |p: PointI32| skeleton.get_pixel_at_safe(p);
+    let is_in_holes = |p: PointI32| {
+        hole.have_pixel_inside(p) || other_holes.iter().any(|hole| hole.have_pixel_inside(p))
+    };
+    let neighbors = |p: PointI32| {
+        // 4-connected neighbors first
+        [0, 2, 4, 6, 1, 3, 5, 7].map(|i| {
+            let (dx, dy) = NEIGHBOR_OFFSETS[i];
+            p + PointI32::new(dx, dy)
+        })
+    };
+    let is_next_to_hole = |p: PointI32| neighbors(p).iter().any(|&q| hole.have_pixel_inside(q));
+    let is_usable = |p: PointI32| is_skeleton(p) && !is_in_holes(p);
+
+    let rect = hole.rect();
+    let mut candidates: Vec<PointI32> = vec![];
+    for y in rect.top - 1..=rect.bottom {
+        for x in rect.left - 1..=rect.right {
+            let p = PointI32::new(x, y);
+            if is_usable(p) && is_next_to_hole(p) {
+                candidates.push(p);
+            }
+        }
+    }
+
+    let mut grouped: HashSet<PointI32> = HashSet::new();
+    let mut branches = vec![];
+    for &head in candidates.iter() {
+        if grouped.contains(&head) {
+            continue;
+        }
+        // The connected group of pixels next to the hole
+        let mut visited: HashSet<PointI32> = HashSet::new();
+        let mut stack = vec![head];
+        visited.insert(head);
+        while let Some(p) = stack.pop() {
+            for &q in neighbors(p).iter() {
+                if is_usable(q) && is_next_to_hole(q) && visited.insert(q) {
+                    stack.push(q);
+                }
+            }
+        }
+        grouped.extend(visited.iter().copied());
+
+        let mut branch = PathI32::new();
+        branch.add(head);
+        let mut current = head;
+        while branch.len() < max_branch_length {
+            let next = neighbors(current)
+                .iter()
+                .copied()
+                .find(|&q| is_usable(q) && !is_next_to_hole(q) && !visited.contains(&q));
+            match next {
+                Some(next) => {
+                    visited.insert(next);
+                    branch.add(next);
+                    current = next;
+                }
+                None => break,
+            }
+        }
+        if branch.len() >= 2 {
+            branches.push(branch);
+        }
+    }
+    branches
+}
+
+/// Estimate the width of the lines of 'image' within 'rect' as the number of foreground pixels
+/// divided by the number of 'skeleton' pixels, ignoring pixels inside 'hole'.
+/// None is returned if there is no skeleton pixel.
+pub fn estimate_line_width(
+    image: &BinaryImage,
+    skeleton: &BinaryImage,
+    hole: &Hole,
+    rect: BoundingRect,
+) -> Option<f64> {
+    let (mut num_foreground, mut num_skeleton) = (0, 0);
+    for y in rect.top..rect.bottom {
+        for x in rect.left..rect.right {
+            let p = PointI32::new(x, y);
+            if hole.have_pixel_inside(p) {
+                continue;
+            }
+            if image.get_pixel_at_safe(p) {
+                num_foreground += 1;
+            }
+            if skeleton.get_pixel_at_safe(p) {
+                num_skeleton += 1;
+            }
+        }
+    }
+    if num_skeleton == 0 {
+        None
+    } else {
+        Some(num_foreground as f64 / num_skeleton as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thick_line_crossing_hole_gives_one_branch_per_side() {
+        // GIVEN a horizontal line of width 3 crossing a hole
+        let mut image = BinaryImage::new_w_h(40, 20);
+        for y in 9..12 {
+            for x in 2..38 {
+                image.set_pixel(x, y, true);
+            }
+        }
+        let hole = Hole::from_rect(BoundingRect::new_x_y_w_h(15, 5, 10, 10));
+        for y in 5..15 {
+            for x in 15..25 {
+                image.set_pixel(x, y, false);
+            }
+        }
+
+        // WHEN
+        let skeleton = skeletonize(&image, &[&hole]);
+        let branches = find_branches_from_hole(&skeleton, &hole, &[], 8);
+        let width = estimate_line_width(
+            &image,
+            &skeleton,
+            &hole,
+            BoundingRect::new_x_y_w_h(5, 0, 30, 20),
+        );
+
+        // THEN
+        for x in 5..12 {
+            let column: Vec<bool> = (0..20).map(|y| skeleton.get_pixel(x, y)).collect();
+            assert_eq!(column.iter().filter(|&&p| p).count(), 1);
+            assert!(column[10]);
+        }
+        assert_eq!(branches.len(), 2);
+        let mut heads: Vec<PointI32> = branches.iter().map(|branch| branch[0]).collect();
+        heads.sort_by_key(|p| p.x);
+        assert_eq!(heads, vec![PointI32::new(14, 10), PointI32::new(25, 10)]);
+        assert!(branches.iter().all(|branch| branch.len() == 8));
+        assert!((width.unwrap() - 3.0).abs() < 0.5);
+    }
+}
@@ -0,0 +1,563 @@
+use bit_vec::BitVec;
+use visioncortex::{BinaryImage, PathI32, PointF64, PointI32};
+
+use crate::{
+    filler::{FilledHoleElement, FilledHoleMatrix},
+    hole::Hole,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct SymmetryConfig {
+    /// The angular resolution of the axes: the number of axis directions evenly spaced over 180 degrees
+    pub num_angles: usize,
+    /// The distance (in pixels) between the parallel axes in each direction
+    pub offset_step: f64,
+    /// The number of axis directions, evenly spaced over 180 degrees, tried by the coarse search
+    pub num_coarse_angles: usize,
+    /// The distance (in pixels) between the parallel axes tried by the coarse search in each direction
+    pub coarse_offset_step: f64,
+    /// The number of best axes of the coarse search refined at the resolution of 'num_angles' and
+    /// 'offset_step' around them
+    pub num_refined_axes: usize,
+    /// The largest number of contour points used to evaluate each axis. Points are subsampled evenly.
+    pub max_num_sample_points: usize,
+    /// The smallest fraction of visible contour points whose reflections are also on the visible
+    /// contour for an axis to be used. The same fraction of the pixels around the hole must agree
+    /// with their reflections.
+    pub min_support: f64,
+}
+
+impl Default for SymmetryConfig {
+    fn default() -> Self {
+        Self {
+            num_angles: 180,
+            offset_step: 0.5,
+            num_coarse_angles: 36,
+            coarse_offset_step: 2.0,
+            num_refined_axes: 8,
+            max_num_sample_points: 256,
+            min_support: 0.9,
+        }
+    }
+}
+
+/// The line of points p with 'normal'.dot(p) == 'offset'.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SymmetryAxis {
+    /// Unit normal of the axis
+    pub normal: PointF64,
+    pub offset: f64,
+    /// The fraction of visible contour points whose reflections are also on the visible contour
+    pub support: f64,
+}
+
+impl SymmetryAxis {
+    /// The reflection of 'p' across the axis.
+    pub fn reflect(&self, p: PointF64) -> PointF64 {
+        p - self.normal * (2.0 * (self.normal.dot(p) - self.offset))
+    }
+}
+
+/// Find the dominant reflective symmetry axis of the contours in 'paths', ignoring the parts of them
+/// on the boundary of (or inside) any of 'holes', which are unknown. Only axes within the largest
+/// width or height of 'holes' from the centroid of the visible contour points are tried.
+/// The axes are searched coarse-to-fine: a coarse search ranks the axes at the resolution of
+/// 'num_coarse_angles' and 'coarse_offset_step' by how close the reflections are to the contour, and
+/// the best 'num_refined_axes' of them are refined at the resolution of 'num_angles' and
+/// 'offset_step' (see SymmetryConfig). Offsets are multiples of 'offset_step'. Among refined axes with
+/// the same support, the one with the most reflections exactly on the contour is returned.
+/// Contour points whose reflections are unknown or outside an image of 'width' x 'height' neither
+/// support nor oppose an axis, but at least half of the sampled points must be evaluated.
+/// None is returned if no axis has a support of at least 'min_support' (see SymmetryConfig).
+pub fn detect_symmetry_axis(
+    paths: &[PathI32],
+    holes: &[&Hole],
+    (width, height): (usize, usize),
+    config: &SymmetryConfig,
+) -> Option<SymmetryAxis> {
+    search_symmetry_axis(paths, holes, (width, height), config).0
+}
+
+/// The evaluation of the reflections of the sampled contour points across an axis.
+struct AxisEvaluation {
+    /// The fraction of evaluated reflections within 1 pixel (chessboard distance) of the contour
+    support: f64,
+    /// The number of reflections exactly on the contour
+    num_exact: usize,
+    /// The average distance of the evaluated reflections to the contour, capped at the tolerance
+    /// given to ReflectionEvaluator::evaluate()
+    mean_distance: f64,
+}
+
+/// Evaluates the reflections of the sampled contour points across axes against the lattice points
+/// of an image of 'width' x 'height', i.e. (width + 1) x (height + 1) points.
+struct ReflectionEvaluator {
+    samples: Vec<PointF64>,
+    width: usize,
+    height: usize,
+    /// The chessboard distance from each lattice point (row-major) to the nearest contour point
+    distances: Vec<u16>,
+    /// Whether each lattice point (row-major) is unknown
+    is_unknown_mask: BitVec,
+}
+
+// API
+impl ReflectionEvaluator {
+    /// The contour points are the points of 'paths' which are not on the boundary of (or inside) any
+    /// of 'holes'. At most 'max_num_sample_points' of them, subsampled evenly, are reflected.
+    /// None is returned if there is no contour point.
+    fn new(
+        paths: &[PathI32],
+        holes: &[&Hole],
+        (width, height): (usize, usize),
+        max_num_sample_points: usize,
+    ) -> Option<Self> {
+        let (grid_width, grid_height) = (width + 1, height + 1);
+        let index_of = |p: PointI32| {
+            if p.x < 0 || p.y < 0 || p.x as usize >= grid_width || p.y as usize >= grid_height {
+                None
+            } else {
+                Some(p.y as usize * grid_width + p.x as usize)
+            }
+        };
+        let is_unknown = |p: PointI32| {
+            holes
+                .iter()
+                .any(|hole| hole.have_point_on_boundary_or_inside(p, 1))
+        };
+
+        let mut is_unknown_mask = BitVec::from_elem(grid_width * grid_height, false);
+        for hole in holes.iter() {
+            let rect = hole.rect();
+            for y in (rect.top - 2)..=(rect.bottom + 2) {
+                for x in (rect.left - 2)..=(rect.right + 2) {
+                    let p = PointI32::new(x, y);
+                    if let Some(index) = index_of(p) {
+                        if is_unknown(p) {
+                            is_unknown_mask.set(index, true);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut contour: Vec<PointI32> = paths
+            .iter()
+            .flat_map(|path| path.iter().copied())
+            .filter(|&p| index_of(p).is_some_and(|index| !is_unknown_mask[index]))
+            .collect();
+        contour.sort_by_key(|p| (p.y, p.x));
+        contour.dedup();
+        if contour.is_empty() {
+            return None;
+        }
+
+        let mut distances = vec![u16::MAX; grid_width * grid_height];
+        contour
+            .iter()
+            .filter_map(|&p| index_of(p))
+            .for_each(|index| distances[index] = 0);
+        Self::calculate_chessboard_distances(&mut distances, grid_width, grid_height);
+
+        let stride = std::cmp::max(contour.len() / std::cmp::max(max_num_sample_points, 1), 1);
+        let samples: Vec<PointF64> = contour
+            .iter()
+            .step_by(stride)
+            .map(|p| p.to_point_f64())
+            .collect();
+        Some(Self {
+            samples,
+            width,
+            height,
+            distances,
+            is_unknown_mask,
+        })
+    }
+
+    /// The centroid of the sampled contour points.
+    fn centroid(&self) -> PointF64 {
+        self.samples
+            .iter()
+            .fold(PointF64::default(), |acc, &p| acc + p)
+            / self.samples.len() as f64
+    }
+
+    /// The largest distance of the sampled contour points from 'point'.
+    fn radius_around(&self, point: PointF64) -> f64 {
+        self.samples
+            .iter()
+            .map(|p| p.distance_to(point))
+            .fold(0.0, f64::max)
+    }
+
+    /// Evaluate the reflections of the sampled contour points across 'axis', whose distances to the
+    /// contour are capped at 'tolerance'. Reflections which are unknown or outside the image are not
+    /// evaluated. None is returned if fewer than half of the sampled points are evaluated.
+    fn evaluate(&self, axis: &SymmetryAxis, tolerance: u16) -> Option<AxisEvaluation> {
+        let (mut num_evaluated, mut num_supporting, mut num_exact) = (0, 0, 0);
+        let mut distance_acc = 0.0;
+        for &p in self.samples.iter() {
+            let reflection = axis.reflect(p);
+            let reflection =
+                PointI32::new(reflection.x.round() as i32, reflection.y.round() as i32);
+            if reflection.x < 0
+                || reflection.y < 0
+                || reflection.x > self.width as i32
+                || reflection.y > self.height as i32
+            {
+                continue;
+            }
+            let index = reflection.y as usize * (self.width + 1) + reflection.x as usize;
+            if self.is_unknown_mask[index] {
+                continue;
+            }
+            num_evaluated += 1;
+            let distance = self.distances[index];
+            if distance == 0 {
+                num_exact += 1;
+            }
+            if distance <= 1 {
+                num_supporting += 1;
+            }
+            distance_acc += std::cmp::min(distance, tolerance) as f64;
+        }
+        if num_evaluated == 0 || num_evaluated * 2 < self.samples.len() {
+            return None;
+        }
+        Some(AxisEvaluation {
+            support: num_supporting as f64 / num_evaluated as f64,
+            num_exact,
+            mean_distance: distance_acc / num_evaluated as f64,
+        })
+    }
+}
+
+// Helper functions
+impl ReflectionEvaluator {
+    /// Propagate the zero 'distances' of a 'width' x 'height' grid (row-major) into the chessboard
+    /// distances to the nearest zero, by a forward and a backward pass.
+    fn calculate_chessboard_distances(distances: &mut [u16], width: usize, height: usize) {
+        let neighbours_before = [(-1, 0), (-1, -1), (0, -1), (1, -1)];
+        let mut relax = |x: usize, y: usize, neighbours: &[(i32, i32)]| {
+            let index = y * width + x;
+            for &(dx, dy) in neighbours.iter() {
+                let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                if nx < 0 || ny < 0 || nx as usize >= width || ny as usize >= height {
+                    continue;
+                }
+                let neighbour = distances[ny as usize * width + nx as usize].saturating_add(1);
+                distances[index] = std::cmp::min(distances[index], neighbour);
+            }
+        };
+        for y in 0..height {
+            for x in 0..width {
+                relax(x, y, &neighbours_before);
+            }
+        }
+        let neighbours_after: Vec<(i32, i32)> = neighbours_before
+            .iter()
+            .map(|&(dx, dy)| (-dx, -dy))
+            .collect();
+        for y in (0..height).rev() {
+            for x in (0..width).rev() {
+                relax(x, y, &neighbours_after);
+            }
+        }
+    }
+}
+
+/// See detect_symmetry_axis(). The number of axes evaluated is also returned.
+fn search_symmetry_axis(
+    paths: &[PathI32],
+    holes: &[&Hole],
+    (width, height): (usize, usize),
+    config: &SymmetryConfig,
+) -> (Option<SymmetryAxis>, usize) {
+    if config.num_angles == 0
+        || config.num_coarse_angles == 0
+        || config.num_refined_axes == 0
+        || config.offset_step.is_nan()
+        || config.offset_step <= 0.0
+        || config.coarse_offset_step.is_nan()
+        || config.coarse_offset_step <= 0.0
+    {
+        return (None, 0);
+    }
+    let evaluator =
+        match ReflectionEvaluator::new(paths, holes, (width, height), config.max_num_sample_points)
+        {
+            Some(evaluator) => evaluator,
+            None => return (None, 0),
+        };
+    let mut num_evaluated_axes = 0;
+
+    let centroid = evaluator.centroid();
+    let max_shift = holes
+        .iter()
+        .map(|hole| std::cmp::max(hole.rect().width(), hole.rect().height()))
+        .max()
+        .unwrap_or(0) as f64;
+
+    //# Coarse search
+    // An axis within half a coarse step (in angle and offset) of the true axis reflects each sampled
+    // point within this distance of where the true axis does
+    let coarse_angle_step = std::f64::consts::PI / config.num_coarse_angles as f64;
+    let coarse_tolerance = (config.coarse_offset_step
+        + 2.0 * evaluator.radius_around(centroid) * (coarse_angle_step / 2.0).sin())
+    .ceil()
+    .min(u16::MAX as f64 - 1.0) as u16
+        + 1;
+    let num_coarse_offsets = (max_shift / config.coarse_offset_step).ceil() as i32;
+    let mut coarse_axes: Vec<(SymmetryAxis, f64)> = vec![];
+    for i in 0..config.num_coarse_angles {
+        let angle = coarse_angle_step * i as f64;
+        let normal = PointF64::new(angle.cos(), angle.sin());
+        let central_offset_index =
+            (normal.dot(centroid) / config.coarse_offset_step).round() as i32;
+        for j in -num_coarse_offsets..=num_coarse_offsets {
+            let axis = SymmetryAxis {
+                normal,
+                offset: (central_offset_index + j) as f64 * config.coarse_offset_step,
+                support: 0.0,
+            };
+            num_evaluated_axes += 1;
+            if let Some(evaluation) = evaluator.evaluate(&axis, coarse_tolerance) {
+                coarse_axes.push((axis, evaluation.mean_distance));
+            }
+        }
+    }
+    // Keep the first one among equally good ones
+    coarse_axes.sort_by(|(_, distance1), (_, distance2)| {
+        distance1
+            .partial_cmp(distance2)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    coarse_axes.truncate(config.num_refined_axes);
+
+    //# Refinement, within a coarse step (in angle and offset) of the coarse axes
+    let fine_angle_step = std::f64::consts::PI / config.num_angles as f64;
+    let num_fine_angles = (coarse_angle_step / fine_angle_step).ceil() as i32;
+    let num_fine_offsets = (config.coarse_offset_step / config.offset_step).ceil() as i32;
+    // The best axis and its number of reflections exactly on the contour
+    let mut best: Option<(SymmetryAxis, usize)> = None;
+    for (coarse_axis, _) in coarse_axes.iter() {
+        let coarse_angle = coarse_axis.normal.y.atan2(coarse_axis.normal.x);
+        let central_angle_index = (coarse_angle / fine_angle_step).round() as i32;
+        // The point of the coarse axis closest to the centroid, which the refined axes pass near
+        let pivot = coarse_axis.reflect(centroid) / 2.0 + centroid / 2.0;
+        for i in -num_fine_angles..=num_fine_angles {
+            let angle = fine_angle_step * (central_angle_index + i) as f64;
+            let normal = PointF64::new(angle.cos(), angle.sin());
+            let central_offset_index = (normal.dot(pivot) / config.offset_step).round() as i32;
+            for j in -num_fine_offsets..=num_fine_offsets {
+                let mut axis = SymmetryAxis {
+                    normal,
+                    offset: (central_offset_index + j) as f64 * config.offset_step,
+                    support: 0.0,
+                };
+                num_evaluated_axes += 1;
+                let evaluation = match evaluator.evaluate(&axis, 1) {
+                    Some(evaluation) => evaluation,
+                    None => continue,
+                };
+                axis.support = evaluation.support;
+                if best.is_none_or(|(best_axis, best_num_exact)| {
+                    (axis.support, evaluation.num_exact) > (best_axis.support, best_num_exact)
+                }) {
+                    best = Some((axis, evaluation.num_exact));
+                }
+            }
+        }
+    }
+
+    (
+        best.map(|(axis, _)| axis)
+            .filter(|axis| axis.support >= config.min_support),
+        num_evaluated_axes,
+    )
+}
+
+/// Fill 'hole' with the reflections of the pixels of 'image' across 'axis'.
+/// None is returned if the reflection of some pixel of 'hole' is outside 'image' or inside 'hole' or
+/// any of 'holes', or if less than 'min_support' (see SymmetryConfig) of the pixels
+/// around 'hole' (within its bounding rect expanded by 1 pixel) agree with their reflections.
+pub fn fill_hole_by_reflection(
+    image: &BinaryImage,
+    hole: &Hole,
+    holes: &[&Hole],
+    axis: &SymmetryAxis,
+    min_support: f64,
+) -> Option<FilledHoleMatrix> {
+    let is_in_holes = |pixel: PointI32| {
+        hole.have_pixel_inside(pixel) || holes.iter().any(|hole| hole.have_pixel_inside(pixel))
+    };
+    // The pixel whose center is the reflection of the center of 'pixel', if it is known
+    let reflect_pixel = |pixel: PointI32| {
+        let center = pixel.to_point_f64() + PointF64::new(0.5, 0.5);
+        let reflection = axis.reflect(center);
+        let reflection = PointI32::new(reflection.x.floor() as i32, reflection.y.floor() as i32);
+        let in_image = 0 <= reflection.x
+            && reflection.x < image.width as i32
+            && 0 <= reflection.y
+            && reflection.y < image.height as i32;
+        if in_image && !is_in_holes(reflection) {
+            Some(image.get_pixel_at(reflection))
+        } else {
+            None
+        }
+    };
+
+    let rect = hole.rect();
+    let (mut num_around, mut num_agreeing) = (0, 0);
+    for y in rect.top - 1..=rect.bottom {
+        for x in rect.left - 1..=rect.right {
+            let pixel = PointI32::new(x, y);
+            let in_image = 0 <= x && x < image.width as i32 && 0 <= y && y < image.height as i32;
+            if !in_image || is_in_holes(pixel) {
+                continue;
+            }
+            if let Some(reflected_value) = reflect_pixel(pixel) {
+                num_around += 1;
+                if image.get_pixel_at(pixel) == reflected_value {
+                    num_agreeing += 1;
+                }
+            }
+        }
+    }
+    if num_around == 0 || (num_agreeing as f64) < min_support * num_around as f64 {
+        return None;
+    }
+
+    let mut filled_hole = FilledHoleMatrix::new_for_hole(hole);
+    let origin = hole.origin();
+    for i in 0..filled_hole.height {
+        for j in 0..filled_hole.width {
+            if filled_hole[i][j] == FilledHoleElement::Outside {
+                continue;
+            }
+            filled_hole[i][j] = if reflect_pixel(origin + PointI32::new(j as i32, i as i32))? {
+                FilledHoleElement::Texture
+            } else {
+                FilledHoleElement::Blank
+            };
+        }
+    }
+    Some(filled_hole)
+}
+
+#[cfg(test)]
+mod tests {
+    use visioncortex::{BoundingRect, PathSimplifyMode};
+
+    use super::*;
+
+    /// The unsimplified contours of the clusters of 'image'.
+    fn contour_paths(image: &BinaryImage) -> Vec<PathI32> {
+        let paths = image.to_clusters(false).into_iter().flat_map(|cluster| {
+            let origin = PointI32::new(cluster.rect.left, cluster.rect.top);
+            let mut paths = visioncortex::clusters::Cluster::image_to_paths(
+                &cluster.to_binary_image(),
+                PathSimplifyMode::None,
+            );
+            paths.iter_mut().for_each(|path| path.offset(&origin));
+            paths
+        });
+        paths.collect()
+    }
+
+    #[test]
+    fn axis_of_partially_covered_t_shape_is_found() {
+        // GIVEN a T shape symmetric about x = 30, with the right end of its bar erased
+        let mut image = BinaryImage::new_w_h(60, 60);
+        for y in 10..18 {
+            for x in 10..50 {
+                image.set_pixel(x, y, true);
+            }
+        }
+        for y in 18..50 {
+            for x in 26..34 {
+                image.set_pixel(x, y, true);
+            }
+        }
+        let hole = Hole::from_rect(BoundingRect::new_x_y_w_h(42, 6, 12, 16));
+        let original = image.clone();
+        for y in 6..22 {
+            for x in 42..54 {
+                image.set_pixel(x, y, false);
+            }
+        }
+        let paths = contour_paths(&image);
+
+        // WHEN
+        let config = SymmetryConfig::default();
+        let axis = detect_symmetry_axis(&paths, &[&hole], (60, 60), &config).unwrap();
+        let filled_hole =
+            fill_hole_by_reflection(&image, &hole, &[&hole], &axis, config.min_support).unwrap();
+
+        // THEN
+        assert!(axis.normal.x.abs() > 0.999);
+        assert!((axis.offset / axis.normal.x - 30.0).abs() < 1e-9);
+        for i in 0..filled_hole.height {
+            for j in 0..filled_hole.width {
+                let expected = if original.get_pixel(42 + j, 6 + i) {
+                    FilledHoleElement::Texture
+                } else {
+                    FilledHoleElement::Blank
+                };
+                assert_eq!(filled_hole[i][j], expected);
+            }
+        }
+    }
+
+    #[test]
+    fn coarse_to_fine_search_evaluates_a_bounded_number_of_axes() {
+        // GIVEN an ellipse with a disk at one end of its major axis, which lies on y = x + 7 and is
+        // the only symmetry axis, in a 320 x 240 image with a 48 x 48 hole beside the axis
+        let center = PointF64::new(130.0, 137.0);
+        let (u, v) = (
+            PointF64::new(1.0, 1.0).get_normalized(),
+            PointF64::new(1.0, -1.0).get_normalized(),
+        );
+        let mut image = BinaryImage::new_w_h(320, 240);
+        for y in 0..240 {
+            for x in 0..320 {
+                let p = PointF64::new(x as f64 + 0.5, y as f64 + 0.5) - center;
+                let (a, b) = (p.dot(u), p.dot(v));
+                let in_ellipse = (a / 70.0).powi(2) + (b / 40.0).powi(2) <= 1.0;
+                let in_disk = (a - 70.0).powi(2) + b * b <= 20.0 * 20.0;
+                image.set_pixel(x, y, in_ellipse || in_disk);
+            }
+        }
+        let hole_center = center + v * 30.0;
+        let hole = Hole::from_rect(BoundingRect::new_x_y_w_h(
+            hole_center.x as i32 - 24,
+            hole_center.y as i32 - 24,
+            48,
+            48,
+        ));
+        let rect = hole.rect();
+        for y in rect.top..rect.bottom {
+            for x in rect.left..rect.right {
+                image.set_pixel(x as usize, y as usize, false);
+            }
+        }
+        let paths = contour_paths(&image);
+        let config = SymmetryConfig::default();
+
+        // WHEN
+        let (axis, num_evaluated_axes) =
+            search_symmetry_axis(&paths, &[&hole], (320, 240), &config);
+
+        // THEN the axis is found at the fine resolution
+        let axis = axis.unwrap();
+        assert!(axis.normal.dot(v).abs() > 1.0 - 1e-9);
+        assert!((axis.normal.dot(center) - axis.offset).abs() <= config.offset_step / 2.0);
+        // Only the coarse axes and the neighbourhoods of the best of them are evaluated, instead of
+        // every axis at the fine resolution
+        let num_coarse_axes = config.num_coarse_angles * (2 * 24 + 1);
+        let num_refined_axes = config.num_refined_axes * (2 * 5 + 1) * (2 * 4 + 1);
+        let num_fine_axes = config.num_angles * (2 * 96 + 1);
+        assert!(num_evaluated_axes <= num_coarse_axes + num_refined_axes);
+        assert!(num_evaluated_axes * 10 < num_fine_axes);
+    }
+}
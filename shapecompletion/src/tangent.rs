@@ -0,0 +1,220 @@
+use visioncortex::PointF64;
+
+/// A circle is only fitted instead of a line if its residual is below this fraction of the
+/// residual of the line, so that pixel staircases along straight boundaries are not mistaken for arcs
+const CIRCLE_RESIDUAL_RATIO: f64 = 0.5;
+
+/// Estimate the tangent and signed curvature at the last point (the tail) of 'points' by fitting a
+/// straight line or a circular arc to them by least squares.
+/// The tangent is a unit vector pointing in the direction the points are traversed towards the
+/// tail. A positive curvature turns from the tangent towards its normal rotated by +90 degrees, as
+/// the angle given by atan2() increases. The curvature of a line is 0.0.
+/// None is returned if there are fewer than 2 distinct points.
+pub(super) fn fit_tail_tangent(points: &[PointF64]) -> Option<(PointF64, f64)> {
+    let (&first, &tail) = (points.first()?, points.last()?);
+    let direction = tail - first;
+    if direction.norm() == 0.0 {
+        return None;
+    }
+
+    let (line_direction, line_residual) = fit_line(points)?;
+    let line_tangent = if line_direction.dot(direction) < 0.0 {
+        -line_direction
+    } else {
+        line_direction
+    };
+
+    let circle = fit_circle(points).filter(|&(center, radius)| {
+        circle_residual(points, center, radius) < CIRCLE_RESIDUAL_RATIO * line_residual
+    });
+    let (center, radius) = match circle {
+        Some(circle) => circle,
+        None => return Some((line_tangent, 0.0)),
+    };
+    let radial = (tail - center).get_normalized();
+    let mut tangent = PointF64::new(-radial.y, radial.x);
+    if tangent.dot(direction) < 0.0 {
+        tangent = -tangent;
+    }
+    // Turning left if the center is on the left of the tangent
+    let left_normal = PointF64::new(-tangent.y, tangent.x);
+    let curvature = if (center - tail).dot(left_normal) > 0.0 {
+        1.0 / radius
+    } else {
+        -1.0 / radius
+    };
+    Some((tangent, curvature))
+}
+
+/// The unit direction of the total least squares line through 'points' (principal component), and the
+/// root mean square distance from 'points' to it.
+/// None is returned if 'points' is empty.
+pub(super) fn fit_line(points: &[PointF64]) -> Option<(PointF64, f64)> {
+    if points.is_empty() {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean = points.iter().fold(PointF64::default(), |acc, &p| acc + p) / n;
+    let (mut sxx, mut sxy, mut syy) = (0.0, 0.0, 0.0);
+    for &p in points.iter() {
+        let d = p - mean;
+        sxx += d.x * d.x;
+        sxy += d.x * d.y;
+        syy += d.y * d.y;
+    }
+    let angle = 0.5 * (2.0 * sxy).atan2(sxx - syy);
+    let direction = PointF64::new(angle.cos(), angle.sin());
+    let normal = PointF64::new(-direction.y, direction.x);
+    let sum_squares: f64 = points
+        .iter()
+        .map(|&p| {
+            let distance = (p - mean).dot(normal);
+            distance * distance
+        })
+        .sum();
+    Some((direction, (sum_squares / n).sqrt()))
+}
+
+/// The center and radius of the circle fitted to 'points' algebraically (Kasa's method), on points
+/// normalized for numerical stability.
+/// None is returned if there are fewer than 3 points or they are (nearly) collinear.
+fn fit_circle(points: &[PointF64]) -> Option<(PointF64, f64)> {
+    if points.len() < 3 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean = points.iter().fold(PointF64::default(), |acc, &p| acc + p) / n;
+    let scale = (points
+        .iter()
+        .map(|&p| (p - mean).dot(p - mean))
+        .sum::<f64>()
+        / n)
+        .sqrt();
+    if scale == 0.0 {
+        return None;
+    }
+
+    // Solve for (d, e, f) minimizing the sum of (x^2 + y^2 + d x + e y + f)^2
+    let mut normal_matrix = [[0.0; 3]; 3];
+    let mut rhs = [0.0; 3];
+    for &p in points.iter() {
+        let q = (p - mean) / scale;
+        let row = [q.x, q.y, 1.0];
+        let z = q.x * q.x + q.y * q.y;
+        for i in 0..3 {
+            for j in 0..3 {
+                normal_matrix[i][j] += row[i] * row[j];
+            }
+            rhs[i] -= row[i] * z;
+        }
+    }
+    let [d, e, f] = solve_3x3(normal_matrix, rhs)?;
+
+    let center = PointF64::new(-d / 2.0, -e / 2.0);
+    let squared_radius = center.dot(center) - f;
+    if squared_radius.is_nan() || squared_radius <= 0.0 {
+        return None;
+    }
+    let (center, radius) = (center * scale + mean, squared_radius.sqrt() * scale);
+    if !center.x.is_finite() || !center.y.is_finite() || !radius.is_finite() {
+        return None;
+    }
+    Some((center, radius))
+}
+
+/// The root mean square distance from 'points' to the circle at 'center' with 'radius'.
+fn circle_residual(points: &[PointF64], center: PointF64, radius: f64) -> f64 {
+    let sum_squares: f64 = points
+        .iter()
+        .map(|&p| {
+            let distance = p.distance_to(center) - radius;
+            distance * distance
+        })
+        .sum();
+    (sum_squares / points.len() as f64).sqrt()
+}
+
+/// Solve 'matrix' x = 'rhs' by Cramer's rule. None is returned if 'matrix' is (nearly) singular.
+fn solve_3x3(matrix: [[f64; 3]; 3], rhs: [f64; 3]) -> Option<[f64; 3]> {
+    let determinant = |m: &[[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let denominator = determinant(&matrix);
+    let magnitude = matrix
+        .iter()
+        .flat_map(|row| row.iter())
+        .fold(0.0_f64, |acc, &x| acc.max(x.abs()));
+    if denominator.abs() <= 1e-12 * magnitude.powi(3) {
+        return None;
+    }
+    let mut solution = [0.0; 3];
+    for (column, value) in solution.iter_mut().enumerate() {
+        let mut replaced = matrix;
+        for (row, &b) in rhs.iter().enumerate() {
+            replaced[row][column] = b;
+        }
+        *value = determinant(&replaced) / denominator;
+    }
+    Some(solution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Round 'p' to the nearest pixel position, as on a rasterized boundary.
+    fn to_pixel(p: PointF64) -> PointF64 {
+        PointF64::new(p.x.round(), p.y.round())
+    }
+
+    #[test]
+    fn tangents_of_pixelated_lines_are_their_directions() {
+        for i in 0..24 {
+            // GIVEN a rasterized line at an angle of 15 * i degrees ending at the tail
+            let angle = (15.0 * i as f64).to_radians();
+            let direction = PointF64::new(angle.cos(), angle.sin());
+            let points: Vec<PointF64> = (0..=12)
+                .map(|t| to_pixel(PointF64::new(40.0, 40.0) + direction * t as f64))
+                .collect();
+
+            // WHEN
+            let (tangent, curvature) = fit_tail_tangent(&points).unwrap();
+
+            // THEN
+            assert!(
+                tangent.dot(direction) > 3.0_f64.to_radians().cos(),
+                "angle {}",
+                15 * i
+            );
+            assert!(curvature.abs() < 0.02, "angle {}", 15 * i);
+        }
+    }
+
+    #[test]
+    fn tangents_and_curvatures_of_circular_arcs_are_recovered() {
+        let radius = 20.0;
+        for i in 0..12 {
+            for &sign in [1.0, -1.0].iter() {
+                // GIVEN a counterclockwise (sign = 1) or clockwise arc of 60 degrees ending at
+                // the angle of 30 * i degrees on the circle
+                let end_angle = (30.0 * i as f64).to_radians();
+                let points: Vec<PointF64> = (0..=20)
+                    .map(|t| {
+                        let angle = end_angle - sign * (20 - t) as f64 * 3.0_f64.to_radians();
+                        PointF64::new(50.0, 50.0) + PointF64::new(angle.cos(), angle.sin()) * radius
+                    })
+                    .collect();
+
+                // WHEN
+                let (tangent, curvature) = fit_tail_tangent(&points).unwrap();
+
+                // THEN
+                let expected = PointF64::new(-end_angle.sin(), end_angle.cos()) * sign;
+                assert!(tangent.distance_to(expected) < 1e-6);
+                assert!((curvature - sign / radius).abs() < 1e-6);
+            }
+        }
+    }
+}
@@ -70,7 +70,7 @@ impl Canvas {
     }
 
     pub fn clear(&self) {
-        self.cctx.set_fill_style(&"#FFFFFF".into());
+        self.cctx.set_fill_style_str("#FFFFFF");
         self.cctx
             .fill_rect(0.0, 0.0, self.width() as f64, self.height() as f64);
     }
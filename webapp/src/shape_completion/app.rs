@@ -1,7 +1,9 @@
 use visioncortex::BoundingRect;
 use wasm_bindgen::prelude::*;
 
-use shapecompletion::completor::ShapeCompletor;
+use shapecompletion::{
+    completor::ShapeCompletor, skeleton::CenterlineConfig, symmetry::SymmetryConfig,
+};
 
 use crate::shape_completion::ShapeCompletorAPIConfig;
 
@@ -35,10 +37,12 @@ impl ShapeCompletorAPI {
 
         let hole_rect = BoundingRect::new_x_y_w_h(x as i32, y as i32, w as i32, h as i32);
 
-        // Remove hole from image
-        for x_offset in 0..hole_rect.width() {
-            for y_offset in 0..hole_rect.height() {
-                image.set_pixel(x + x_offset as usize, y + y_offset as usize, false);
+        // Remove hole from image, unless the damage is partial and foreground survives in it
+        if !config.partial_damage {
+            for x_offset in 0..hole_rect.width() {
+                for y_offset in 0..hole_rect.height() {
+                    image.set_pixel(x + x_offset as usize, y + y_offset as usize, false);
+                }
             }
         }
 
@@ -48,7 +52,24 @@ impl ShapeCompletorAPI {
             config.curve_intrapolator_config(),
             config.filler_blank_boundary_pixels_tolerance,
             Some(Box::new(draw_util)),
-        );
+        )
+        .with_matcher_config(config.matcher_config())
+        .with_simplify_config(config.simplify_config())
+        .with_fill_rule(config.fill_rule());
+        let shape_completor = match config.match_cost() {
+            Some(match_cost) => shape_completor.with_match_cost(match_cost),
+            None => shape_completor,
+        };
+        let shape_completor = if config.symmetry {
+            shape_completor.with_symmetry_config(SymmetryConfig::default())
+        } else {
+            shape_completor
+        };
+        let shape_completor = if config.centerline {
+            shape_completor.with_centerline_config(CenterlineConfig::default())
+        } else {
+            shape_completor
+        };
 
         let result = shape_completor.complete_shape_and_draw_expandable(hole_rect);
 
@@ -1,5 +1,11 @@
 use super::draw::DisplaySelector;
-use shapecompletion::curve::CurveIntrapolatorConfig;
+use shapecompletion::{
+    curve::{CurveIntrapolatorConfig, CurveModel, TailTangentEstimator},
+    filler::FillRule,
+    match_cost::{GestaltMatchCost, MatchCost},
+    matcher::{MatcherAlgorithm, MatcherConfig},
+    simplify::{SimplifyConfig, SimplifyMode},
+};
 use wasm_bindgen::prelude::*;
 
 /// Configuration to ShapeCompletor
@@ -14,13 +20,29 @@ pub struct ShapeCompletorAPIConfig {
     pub hole_top: usize,
     pub hole_width: usize,
     pub hole_height: usize,
+    /// Keep the foreground inside the hole as surviving fragments instead of clearing it
+    pub partial_damage: bool,
 
     // Simplify path segments
     pub simplify_tolerance: f64,
+    simplify_config: SimplifyConfig,
 
     // Curve intrrpolator
     curve_intrapolator_config: CurveIntrapolatorConfig,
 
+    // Matcher
+    matcher_config: MatcherConfig,
+    /// Use GestaltMatchCost instead of the default cost model
+    pub match_cost_gestalt: bool,
+
+    // Symmetry
+    /// Fill the hole by reflection where the shape is mirror-symmetric
+    pub symmetry: bool,
+
+    // Centerline
+    /// Complete line art along the centerlines of its lines
+    pub centerline: bool,
+
     // Filler
     /// The maximum number of blank pixels outside the hole boundary allowed
     /// for a subregion to still be filled.
@@ -29,6 +51,7 @@ pub struct ShapeCompletorAPIConfig {
     /// `filler_blank_boundary_pixels_tolerance` or less pixels are blank, then
     /// that subregion will be filled.
     pub filler_blank_boundary_pixels_tolerance: usize,
+    fill_rule: FillRule,
 }
 
 impl Default for ShapeCompletorAPIConfig {
@@ -42,9 +65,16 @@ impl Default for ShapeCompletorAPIConfig {
             hole_top: 0,
             hole_width: 15,
             hole_height: 15,
+            partial_damage: false,
             simplify_tolerance: 2.0,
+            simplify_config: Default::default(),
             curve_intrapolator_config: Default::default(),
+            matcher_config: Default::default(),
+            match_cost_gestalt: false,
+            symmetry: false,
+            centerline: false,
             filler_blank_boundary_pixels_tolerance: 3,
+            fill_rule: FillRule::BoundarySampling,
         }
     }
 }
@@ -106,11 +136,48 @@ impl ShapeCompletorAPIConfig {
         self
     }
 
+    pub fn partialDamage(mut self, value: bool) -> Self {
+        self.partial_damage = value;
+        self
+    }
+
     pub fn pathSimplifyTolerance(mut self, value: f64) -> Self {
         self.simplify_tolerance = value;
         self
     }
 
+    /// 'value' is one of "none", "ramerDouglasPeucker", "visvalingamWhyatt" and "spline".
+    /// SimplifyMode::RamerDouglasPeucker is used for any other value.
+    pub fn pathSimplifyMode(mut self, value: &str) -> Self {
+        self.simplify_config.mode = match value {
+            "none" => SimplifyMode::None,
+            "visvalingamWhyatt" => SimplifyMode::VisvalingamWhyatt,
+            "spline" => SimplifyMode::Spline,
+            _ => SimplifyMode::RamerDouglasPeucker,
+        };
+        self
+    }
+
+    pub fn pathSplineCornerThreshold(mut self, value: f64) -> Self {
+        self.simplify_config.corner_threshold = value;
+        self
+    }
+
+    pub fn pathSplineOutsetRatio(mut self, value: f64) -> Self {
+        self.simplify_config.outset_ratio = value;
+        self
+    }
+
+    pub fn pathSplineSegmentLength(mut self, value: f64) -> Self {
+        self.simplify_config.segment_length = value;
+        self
+    }
+
+    pub fn pathSplineMaxIterations(mut self, value: usize) -> Self {
+        self.simplify_config.max_iterations = value;
+        self
+    }
+
     // CurveInterpolatorConfig
 
     pub fn curveOutsetRatio(mut self, value: f64) -> Self {
@@ -147,6 +214,151 @@ impl ShapeCompletorAPIConfig {
         self.curve_intrapolator_config.control_points_retract_ratio = value;
         self
     }
+
+    /// 'value' is one of "weightedAverage" and "leastSquares".
+    /// TailTangentEstimator::WeightedAverage is used for any other value.
+    pub fn curveTailTangentEstimator(mut self, value: &str) -> Self {
+        self.curve_intrapolator_config.tail_tangent_estimator = match value {
+            "leastSquares" => TailTangentEstimator::LeastSquares,
+            _ => TailTangentEstimator::WeightedAverage,
+        };
+        self
+    }
+
+    pub fn curveTailFitLength(mut self, value: f64) -> Self {
+        self.curve_intrapolator_config.tail_fit_length = value;
+        self
+    }
+
+    pub fn curveStubLengthRatio(mut self, value: f64) -> Self {
+        self.curve_intrapolator_config.stub_length_ratio = value;
+        self
+    }
+
+    /// 'value' is one of "bezier", "clothoid" and "curvatureContinuous".
+    /// CurveModel::Bezier is used for any other value.
+    pub fn curveModel(mut self, value: &str) -> Self {
+        self.curve_intrapolator_config.curve_model = match value {
+            "clothoid" => CurveModel::Clothoid,
+            "curvatureContinuous" => CurveModel::CurvatureContinuous,
+            _ => CurveModel::Bezier,
+        };
+        self
+    }
+
+    pub fn curvePreserveCorners(mut self, value: bool) -> Self {
+        self.curve_intrapolator_config.preserve_corners = value;
+        self
+    }
+
+    pub fn curveStraightTailMaxCurvature(mut self, value: f64) -> Self {
+        self.curve_intrapolator_config.straight_tail_max_curvature = value;
+        self
+    }
+
+    pub fn curveFitConics(mut self, value: bool) -> Self {
+        self.curve_intrapolator_config.fit_conics = value;
+        self
+    }
+
+    pub fn curveConicMaxResidual(mut self, value: f64) -> Self {
+        self.curve_intrapolator_config.conic_max_residual = value;
+        self
+    }
+
+    pub fn curveCompleteStrokes(mut self, value: bool) -> Self {
+        self.curve_intrapolator_config.complete_strokes = value;
+        self
+    }
+
+    pub fn curveMaxStrokeWidth(mut self, value: f64) -> Self {
+        self.curve_intrapolator_config.max_stroke_width = value;
+        self
+    }
+
+    pub fn curveMaxStrokeEdgeAngle(mut self, value: f64) -> Self {
+        self.curve_intrapolator_config.max_stroke_edge_angle = value;
+        self
+    }
+
+    // MatcherConfig
+
+    pub fn matcherAllowUnmatched(mut self, value: bool) -> Self {
+        self.matcher_config.allow_unmatched = value;
+        self
+    }
+
+    /// 'value' is one of "exhaustive", "ranked" and "nonCrossing".
+    /// MatcherAlgorithm::Exhaustive is used for any other value.
+    pub fn matcherAlgorithm(mut self, value: &str) -> Self {
+        self.matcher_config.algorithm = match value {
+            "ranked" => MatcherAlgorithm::Ranked,
+            "nonCrossing" => MatcherAlgorithm::NonCrossing,
+            _ => MatcherAlgorithm::Exhaustive,
+        };
+        self
+    }
+
+    pub fn matcherMaxNumRankedMatchings(mut self, value: usize) -> Self {
+        self.matcher_config.max_num_ranked_matchings = value;
+        self
+    }
+
+    pub fn matcherMaxNumExhaustiveMatchings(mut self, value: usize) -> Self {
+        self.matcher_config.max_num_exhaustive_matchings = value;
+        self
+    }
+
+    pub fn matcherNumEvaluatedMatchings(mut self, value: usize) -> Self {
+        self.matcher_config.num_evaluated_matchings = value;
+        self
+    }
+
+    pub fn matcherAllowCrossings(mut self, value: bool) -> Self {
+        self.matcher_config.allow_crossings = value;
+        self
+    }
+
+    pub fn matcherMinCrossingAngle(mut self, value: f64) -> Self {
+        self.matcher_config.min_crossing_angle = value;
+        self
+    }
+
+    pub fn matcherAllowTJunctions(mut self, value: bool) -> Self {
+        self.matcher_config.allow_t_junctions = value;
+        self
+    }
+
+    pub fn matchCostGestalt(mut self, value: bool) -> Self {
+        self.match_cost_gestalt = value;
+        self
+    }
+
+    // Symmetry
+
+    pub fn symmetry(mut self, value: bool) -> Self {
+        self.symmetry = value;
+        self
+    }
+
+    // Centerline
+
+    pub fn centerline(mut self, value: bool) -> Self {
+        self.centerline = value;
+        self
+    }
+
+    // Filler
+
+    /// 'value' is one of "boundarySampling" and "evenOdd".
+    /// FillRule::BoundarySampling is used for any other value.
+    pub fn fillerRule(mut self, value: &str) -> Self {
+        self.fill_rule = match value {
+            "evenOdd" => FillRule::EvenOdd,
+            _ => FillRule::BoundarySampling,
+        };
+        self
+    }
 }
 
 // API
@@ -155,9 +367,30 @@ impl ShapeCompletorAPIConfig {
         &self.canvas_id
     }
 
+    pub fn simplify_config(&self) -> SimplifyConfig {
+        self.simplify_config
+    }
+
     pub fn curve_intrapolator_config(&self) -> CurveIntrapolatorConfig {
         self.curve_intrapolator_config
     }
+
+    pub fn matcher_config(&self) -> MatcherConfig {
+        self.matcher_config
+    }
+
+    pub fn fill_rule(&self) -> FillRule {
+        self.fill_rule
+    }
+
+    /// None for the default cost model
+    pub fn match_cost(&self) -> Option<Box<dyn MatchCost>> {
+        if self.match_cost_gestalt {
+            Some(Box::new(GestaltMatchCost::default()))
+        } else {
+            None
+        }
+    }
 }
 
 // Helper functions
@@ -79,7 +79,7 @@ impl Debugger for DrawUtil {
 
     fn fill_rect(&self, color: &Color, x: usize, y: usize, w: usize, h: usize) {
         let ctx = self.ctx();
-        ctx.set_fill_style(&color.to_hex_string().into());
+        ctx.set_fill_style_str(&color.to_hex_string());
         ctx.fill_rect(x as f64, y as f64, w as f64, h as f64);
     }
 
@@ -94,7 +94,7 @@ impl Debugger for DrawUtil {
 
     fn draw_path_i32(&self, color: &Color, path: &PathI32) {
         let ctx = self.ctx();
-        ctx.set_stroke_style(&color.to_hex_string().into());
+        ctx.set_stroke_style_str(&color.to_hex_string());
 
         ctx.begin_path();
         ctx.move_to(path[0].x as f64, path[0].y as f64);
@@ -110,7 +110,7 @@ impl Debugger for DrawUtil {
         }
 
         let ctx = self.ctx();
-        ctx.set_stroke_style(&color.to_hex_string().into());
+        ctx.set_stroke_style_str(&color.to_hex_string());
 
         ctx.begin_path();
         ctx.move_to(path[0].x, path[0].y);
@@ -122,7 +122,7 @@ impl Debugger for DrawUtil {
 
     fn draw_line_f64(&self, color: &Color, from: PointF64, to: PointF64) {
         let ctx = self.ctx();
-        ctx.set_stroke_style(&color.to_hex_string().into());
+        ctx.set_stroke_style_str(&color.to_hex_string());
 
         ctx.begin_path();
         ctx.move_to(from.x, from.y);
@@ -144,7 +144,7 @@ impl Debugger for DrawUtil {
 
     fn draw_cubic_bezier_curve(&self, color: &Color, control_points: [PointF64; 4]) {
         let ctx = self.ctx();
-        ctx.set_stroke_style(&color.to_hex_string().into());
+        ctx.set_stroke_style_str(&color.to_hex_string());
 
         ctx.begin_path();
         ctx.move_to(control_points[0].x, control_points[0].y);
@@ -184,6 +184,7 @@ impl Debugger for DrawUtil {
                     FilledHoleElement::Blank => blank,
                     FilledHoleElement::Structure => structure,
                     FilledHoleElement::Texture => texture,
+                    FilledHoleElement::Outside => continue,
                 };
                 self.draw_pixel_i32(&color, target);
             }
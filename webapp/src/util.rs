@@ -23,6 +23,7 @@ where
     console::log_1(&content.to_string().into());
 }
 
+#[allow(dead_code)]
 pub(crate) fn console_log_debug_util<T>(content: T)
 where
     T: std::fmt::Debug,